@@ -0,0 +1,55 @@
+//! Lists the exported items and the `#[import_tokens_attr]` / `#[import_tokens_proc]`
+//! definitions of a crate, and flags problems such as colliding hidden macros or exports in
+//! inaccessible modules. Works offline by reading the crate's source.
+//!
+//! ```text
+//! cargo macro-magic [CRATE_DIR] [--alias NAME]...
+//! ```
+//!
+//! Exits with status 1 if any problems are found.
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use macro_magic_core::{exploration_report, explore_crate};
+
+const USAGE: &str = "usage: cargo macro-magic [CRATE_DIR] [--alias NAME]...";
+
+fn main() -> ExitCode {
+    // when invoked as `cargo macro-magic`, cargo passes the subcommand name first
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("macro-magic") {
+        args.next();
+    }
+    let mut crate_dir = PathBuf::from(".");
+    let mut aliases = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--alias" => match args.next() {
+                Some(alias) => aliases.push(alias),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            dir => crate_dir = PathBuf::from(dir),
+        }
+    }
+    let aliases: Vec<&str> = aliases.iter().map(String::as_str).collect();
+    match explore_crate(&crate_dir, &aliases) {
+        Ok(exploration) => {
+            print!("{}", exploration_report(&exploration));
+            match exploration.problems.is_empty() {
+                true => ExitCode::SUCCESS,
+                false => ExitCode::FAILURE,
+            }
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Records the `#[export_tokens]` items of a crate in a baseline file and checks the crate
+//! against it, reporting additive and breaking changes.
+//!
+//! ```text
+//! macro_magic_api snapshot [CRATE_DIR] [--baseline FILE]
+//! macro_magic_api check [CRATE_DIR] [--baseline FILE]
+//! ```
+//!
+//! `check` exits with status 1 if any breaking changes are found.
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use macro_magic_core::{
+    check_api_snapshot, default_api_snapshot_path, write_api_snapshot, ApiChangeKind,
+};
+
+const USAGE: &str = "usage: macro_magic_api <snapshot|check> [CRATE_DIR] [--baseline FILE]";
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(command) = args.next() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let mut crate_dir = PathBuf::from(".");
+    let mut baseline = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--baseline" => match args.next() {
+                Some(file) => baseline = Some(PathBuf::from(file)),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            dir => crate_dir = PathBuf::from(dir),
+        }
+    }
+    let baseline = baseline.unwrap_or_else(|| default_api_snapshot_path(&crate_dir));
+    match command.as_str() {
+        "snapshot" => match write_api_snapshot(&crate_dir, &baseline) {
+            Ok(()) => {
+                println!("wrote {}", baseline.display());
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::from(2)
+            }
+        },
+        "check" => match check_api_snapshot(&crate_dir, &baseline) {
+            Ok(changes) => {
+                for change in &changes {
+                    println!("{}", change);
+                }
+                match changes
+                    .iter()
+                    .any(|change| change.kind == ApiChangeKind::Breaking)
+                {
+                    true => ExitCode::FAILURE,
+                    false => ExitCode::SUCCESS,
+                }
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::from(2)
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
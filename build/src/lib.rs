@@ -0,0 +1,562 @@
+//! This crate provides build-script support for `macro_magic`. It scans the `src/` directory
+//! of the crate being built for `#[export_tokens]` items and writes a manifest describing them
+//! into `OUT_DIR`, which can be `include!`d as a `const` registry of exported paths.
+//!
+//! Unlike writing to `OUT_DIR` from within a proc macro, which is not future-proof, build
+//! scripts are meant to do this, and cargo reruns them whenever a scanned file changes.
+//!
+//! It can also write a shim module exporting the tokens of items of dependencies that don't
+//! use `macro_magic` (see [`write_shims`]), so they can be imported as if they had opted in.
+//!
+//! The `cli` feature enables the `macro_magic_api` binary, which records the exported items of
+//! a crate in a baseline file and reports additive and breaking changes against it, and the
+//! `cargo-macro-magic` binary, which lists the exports and import macros of a crate and flags
+//! problems with them.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     macro_magic_build::write_manifest().unwrap();
+//! }
+//!
+//! // src/lib.rs
+//! pub const EXPORTS: &[macro_magic::manifest::ExportInfo] =
+//!     include!(concat!(env!("OUT_DIR"), "/macro_magic_manifest.rs"));
+//! ```
+#![warn(missing_docs)]
+
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use macro_magic_core::{
+    export_tokens_item_ident, pretty_print, read_item_from_crate, scan_crate, CrateScan,
+};
+use proc_macro2::{Delimiter, Group, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use serde_json::Value;
+use syn::Item;
+
+/// The name of the manifest file written into `OUT_DIR` by [`write_manifest`].
+pub const MANIFEST_FILE: &str = "macro_magic_manifest.rs";
+
+/// The name of the shim module file written into `OUT_DIR` by [`write_shims`].
+pub const SHIMS_FILE: &str = "macro_magic_shims.rs";
+
+/// Returns the value of the environment variable `name`, which cargo sets for build scripts.
+fn build_var(name: &str) -> io::Result<String> {
+    env::var(name).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "`{}` is not set, is this being called from a build script?",
+                name
+            ),
+        )
+    })
+}
+
+/// Returns the source of a manifest describing the exported items in `scan`, which is an
+/// expression of type `&[macro_magic::manifest::ExportInfo]`. Paths are prefixed with
+/// `crate_name`.
+pub fn manifest_source(scan: &CrateScan, crate_name: &str) -> String {
+    let mut source = String::from("&[\n");
+    for export in &scan.exports {
+        let path = format!("{}::{}", crate_name, export.path());
+        let macro_path = format!("{}::{}", crate_name, export.macro_path());
+        source.push_str(&format!(
+            "    ::macro_magic::manifest::ExportInfo {{\n        path: {:?},\n        \
+            macro_path: {:?},\n        kind: {:?},\n        emit: {},\n        \
+            accessible: {},\n    }},\n",
+            path,
+            macro_path,
+            export.kind(),
+            export.emit,
+            export.accessible,
+        ));
+    }
+    source.push(']');
+    source
+}
+
+/// Scans the crate located at `crate_dir` (treating the attributes named in `aliases` like
+/// `#[export_tokens]`) and writes its manifest to `out_file`, returning the scanned files.
+pub fn write_manifest_to(
+    crate_dir: impl AsRef<Path>,
+    crate_name: &str,
+    aliases: &[&str],
+    out_file: impl AsRef<Path>,
+) -> io::Result<Vec<PathBuf>> {
+    let scan = scan_crate(crate_dir, aliases)?;
+    fs::write(out_file, manifest_source(&scan, crate_name))?;
+    Ok(scan.files().into_iter().map(Path::to_path_buf).collect())
+}
+
+/// Writes the manifest of the crate being built to [`MANIFEST_FILE`] in `OUT_DIR`, returning
+/// the path of the manifest. Items that use custom aliases of `#[export_tokens]` (created via
+/// `export_tokens_alias!`) are detected if their names are listed in `aliases`.
+///
+/// Must be called from a build script. Emits `cargo:rerun-if-changed` for every scanned file.
+pub fn write_manifest_with_aliases(aliases: &[&str]) -> io::Result<PathBuf> {
+    let crate_dir = PathBuf::from(build_var("CARGO_MANIFEST_DIR")?);
+    let crate_name = build_var("CARGO_CRATE_NAME").or_else(|_| build_var("CARGO_PKG_NAME"))?;
+    let out_file = PathBuf::from(build_var("OUT_DIR")?).join(MANIFEST_FILE);
+    let files = write_manifest_to(
+        &crate_dir,
+        &crate_name.replace('-', "_"),
+        aliases,
+        &out_file,
+    )?;
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+    }
+    Ok(out_file)
+}
+
+/// Writes the manifest of the crate being built to [`MANIFEST_FILE`] in `OUT_DIR`, returning
+/// the path of the manifest. See [`write_manifest_with_aliases`].
+pub fn write_manifest() -> io::Result<PathBuf> {
+    write_manifest_with_aliases(&[])
+}
+
+/// A dependency that doesn't use `macro_magic`, along with the items a shim module should
+/// export the tokens of. See [`write_shims`].
+pub struct Shim {
+    /// The package name of the dependency, i.e. `proc-macro2`
+    pub dependency: String,
+    /// The paths of the items relative to the crate root of the dependency, i.e.
+    /// `location::LineColumn`
+    pub items: Vec<String>,
+}
+
+impl Shim {
+    /// Creates a [`Shim`] for the specified dependency, without any items.
+    pub fn new(dependency: impl Into<String>) -> Self {
+        Shim {
+            dependency: dependency.into(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Adds the item at `path` (relative to the crate root of the dependency) to the shim.
+    pub fn item(mut self, path: impl Into<String>) -> Self {
+        self.items.push(path.into());
+        self
+    }
+}
+
+/// Finds the lib target root file (i.e. `src/lib.rs`) of `dependency` in `metadata`, which is
+/// the output of `cargo metadata`. If several versions of the package are present, the one the
+/// package located at `manifest_dir` depends on directly is used.
+fn lib_root(metadata: &Value, manifest_dir: &Path, dependency: &str) -> io::Result<PathBuf> {
+    let normalized = dependency.replace('-', "_");
+    let candidates: Vec<(&Value, &str)> = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|package| {
+            package["name"]
+                .as_str()
+                .is_some_and(|name| name.replace('-', "_") == normalized)
+        })
+        .filter_map(|package| {
+            let lib = package["targets"].as_array()?.iter().find(|target| {
+                target["kind"].as_array().is_some_and(|kinds| {
+                    kinds
+                        .iter()
+                        .any(|kind| matches!(kind.as_str(), Some("lib" | "rlib" | "proc-macro")))
+                })
+            })?;
+            Some((&package["id"], lib["src_path"].as_str()?))
+        })
+        .collect();
+    // `resolve.root` is the root package of the workspace, which isn't necessarily the package
+    // being built, so the package is found via its manifest instead
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    let manifest_path = manifest_path.canonicalize().unwrap_or(manifest_path);
+    let package_id = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|package| {
+            package["manifest_path"].as_str().is_some_and(|path| {
+                let path = Path::new(path);
+                path == manifest_path || path.canonicalize().is_ok_and(|path| path == manifest_path)
+            })
+        })
+        .map(|package| &package["id"]);
+    let direct = |id: &Value| {
+        metadata["resolve"]["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|node| Some(&node["id"]) == package_id)
+            .filter_map(|node| node["dependencies"].as_array())
+            .any(|dependencies| dependencies.contains(id))
+    };
+    let found = match candidates.as_slice() {
+        [] => None,
+        [(_, src_path)] => Some(*src_path),
+        _ => {
+            let mut direct_candidates = candidates.iter().filter(|(id, _)| direct(id));
+            match (direct_candidates.next(), direct_candidates.next()) {
+                (Some((_, src_path)), None) => Some(*src_path),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "found several versions of `{}`, none of which is a direct \
+                            dependency",
+                            dependency
+                        ),
+                    ))
+                }
+            }
+        }
+    };
+    found.map(PathBuf::from).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("could not find the library of dependency `{}`", dependency),
+        )
+    })
+}
+
+/// Runs `cargo metadata` for the package located at `manifest_dir` and returns the lib target
+/// root files (i.e. `src/lib.rs`) of the specified dependencies, in the same order. Packages
+/// are matched by name, treating `-` and `_` as the same character.
+///
+/// The `cargo` binary running the build (`CARGO`) is used if set, and `cargo` otherwise.
+/// Dependencies are never fetched beyond what cargo has already downloaded for the build.
+pub fn dependency_lib_roots(
+    manifest_dir: impl AsRef<Path>,
+    dependencies: &[&str],
+) -> io::Result<Vec<PathBuf>> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args([
+            "metadata",
+            "--format-version",
+            "1",
+            "--offline",
+            "--manifest-path",
+        ])
+        .arg(manifest_dir.as_ref().join("Cargo.toml"))
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    dependencies
+        .iter()
+        .map(|dependency| lib_root(&metadata, manifest_dir.as_ref(), dependency))
+        .collect()
+}
+
+/// The items of a module of a shim, along with its child modules.
+#[derive(Default)]
+struct ShimModule {
+    items: Vec<Item>,
+    children: BTreeMap<String, ShimModule>,
+}
+
+impl ToTokens for ShimModule {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let items = &self.items;
+        let children = self.children.iter().map(|(name, child)| {
+            let name = format_ident!("{}", name);
+            quote!(pub mod #name { #child })
+        });
+        tokens.extend(quote! {
+            #(
+                #[::macro_magic::export_tokens_no_emit]
+                #items
+            )*
+            #(#children)*
+        });
+    }
+}
+
+/// Removes every `#[cfg(..)]` attribute from `tokens`, including those of nested items, fields,
+/// variants and statements.
+fn strip_cfg_attrs(tokens: TokenStream2) -> TokenStream2 {
+    let mut output = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = tokens.peek() {
+                    let is_cfg = group.delimiter() == Delimiter::Bracket
+                        && matches!(
+                            group.stream().into_iter().next(),
+                            Some(TokenTree::Ident(ident)) if ident == "cfg"
+                        );
+                    if is_cfg {
+                        tokens.next();
+                        continue;
+                    }
+                }
+                output.push(TokenTree::Punct(punct));
+            }
+            TokenTree::Group(group) => {
+                let mut stripped = Group::new(group.delimiter(), strip_cfg_attrs(group.stream()));
+                stripped.set_span(group.span());
+                output.push(TokenTree::Group(stripped));
+            }
+            token => output.push(token),
+        }
+    }
+    output.into_iter().collect()
+}
+
+/// Returns the source of the shim module of `shim`, whose lib target root file is `lib_root`,
+/// along with every file that was read. Items are found via `read_item_from_crate`.
+///
+/// The module is named after the dependency (i.e. `proc_macro2`) and each item is re-declared
+/// in nested `pub mod`s mirroring its path, with `#[export_tokens_no_emit]` attached, so its
+/// tokens can be imported like those of any other exported item while the item itself is
+/// never compiled as part of the crate including the shim. `#[cfg(..)]` attributes are removed
+/// from the items and everything inside them (i.e. fields and variants), as they refer to the
+/// features of the dependency, so members gated on mutually exclusive conditions are all kept.
+pub fn shim_source(shim: &Shim, lib_root: impl AsRef<Path>) -> io::Result<(String, Vec<PathBuf>)> {
+    let mut root = ShimModule::default();
+    let mut files: Vec<PathBuf> = Vec::new();
+    for item_path in &shim.items {
+        let path = syn::parse_str::<syn::Path>(item_path).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid item path `{}`: {}", item_path, err),
+            )
+        })?;
+        let (item, item_files) = read_item_from_crate(lib_root.as_ref(), &path)?;
+        for file in item_files {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        let item = syn::parse2::<Item>(strip_cfg_attrs(item.to_token_stream()))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let mut module = &mut root;
+        for segment in path.segments.iter().rev().skip(1).rev() {
+            module = module
+                .children
+                .entry(segment.ident.to_string())
+                .or_default();
+        }
+        let ident = export_tokens_item_ident(&item);
+        if !module
+            .items
+            .iter()
+            .any(|existing| export_tokens_item_ident(existing) == ident)
+        {
+            module.items.push(item);
+        }
+    }
+    let name = format_ident!("{}", shim.dependency.replace('-', "_"));
+    let tokens = quote!(pub mod #name { #root });
+    syn::parse2::<syn::File>(tokens.clone())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((pretty_print(&tokens), files))
+}
+
+/// Finds the dependencies of the package located at `manifest_dir` via
+/// [`dependency_lib_roots`] and writes the shim modules of `shims` (see [`shim_source`]) to
+/// `out_file`, returning the files that were read.
+pub fn write_shims_to(
+    manifest_dir: impl AsRef<Path>,
+    shims: &[Shim],
+    out_file: impl AsRef<Path>,
+) -> io::Result<Vec<PathBuf>> {
+    let dependencies: Vec<&str> = shims.iter().map(|shim| shim.dependency.as_str()).collect();
+    let lib_roots = dependency_lib_roots(manifest_dir, &dependencies)?;
+    let mut source = String::new();
+    let mut files = Vec::new();
+    for (shim, lib_root) in shims.iter().zip(lib_roots) {
+        let (shim_source, shim_files) = shim_source(shim, lib_root)?;
+        source.push_str(&shim_source);
+        files.extend(shim_files);
+    }
+    fs::write(out_file, source)?;
+    Ok(files)
+}
+
+/// Writes shim modules for the dependencies of the crate being built to [`SHIMS_FILE`] in
+/// `OUT_DIR`, returning the path of the file. The items listed in `shims` can then be imported
+/// by `macro_magic` macros as if their crates used `#[export_tokens]`:
+///
+/// ```ignore
+/// // build.rs
+/// macro_magic_build::write_shims(&[
+///     Shim::new("proc-macro2").item("location::LineColumn"),
+/// ])
+/// .unwrap();
+///
+/// // src/lib.rs
+/// pub mod shims {
+///     include!(concat!(env!("OUT_DIR"), "/macro_magic_shims.rs"));
+/// }
+///
+/// // elsewhere
+/// import_tokens!(let tokens = my_crate::shims::proc_macro2::location::LineColumn);
+/// ```
+///
+/// Must be called from a build script. Emits `cargo:rerun-if-changed` for every file read.
+pub fn write_shims(shims: &[Shim]) -> io::Result<PathBuf> {
+    let crate_dir = PathBuf::from(build_var("CARGO_MANIFEST_DIR")?);
+    let out_file = PathBuf::from(build_var("OUT_DIR")?).join(SHIMS_FILE);
+    let files = write_shims_to(&crate_dir, shims, &out_file)?;
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+    }
+    Ok(out_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_with_aliases() {
+        let crate_dir = env::temp_dir().join(format!("macro_magic_build_{}", std::process::id()));
+        fs::create_dir_all(crate_dir.join("src/shapes")).unwrap();
+        fs::write(
+            crate_dir.join("src/lib.rs"),
+            "pub mod shapes;\nmod private { #[export_tokens] pub struct Hidden; }\n\
+            #[custom_export_tokens] pub fn area() {}",
+        )
+        .unwrap();
+        fs::write(
+            crate_dir.join("src/shapes/mod.rs"),
+            "#[export_tokens_no_emit(unit_square)] impl Square {}\n\
+            #[macro_magic::export_tokens] pub struct Square;",
+        )
+        .unwrap();
+        let out_file = crate_dir.join(MANIFEST_FILE);
+        let files =
+            write_manifest_to(&crate_dir, "shapes", &["custom_export_tokens"], &out_file).unwrap();
+        assert_eq!(files.len(), 2);
+        let manifest = fs::read_to_string(&out_file).unwrap();
+        fs::remove_dir_all(&crate_dir).unwrap();
+        assert_eq!(
+            manifest,
+            "&[\n    ::macro_magic::manifest::ExportInfo {\n        path: \"shapes::area\",\n        \
+            macro_path: \"shapes::__export_tokens_tt_area\",\n        kind: \"fn\",\n        \
+            emit: true,\n        accessible: true,\n    },\n    \
+            ::macro_magic::manifest::ExportInfo {\n        path: \"shapes::private::Hidden\",\n        \
+            macro_path: \"shapes::private::__export_tokens_tt_hidden\",\n        \
+            kind: \"struct\",\n        emit: true,\n        accessible: false,\n    },\n    \
+            ::macro_magic::manifest::ExportInfo {\n        path: \"shapes::shapes::Square\",\n        \
+            macro_path: \"shapes::shapes::__export_tokens_tt_square\",\n        \
+            kind: \"struct\",\n        emit: true,\n        accessible: true,\n    },\n    \
+            ::macro_magic::manifest::ExportInfo {\n        path: \"shapes::shapes::unit_square\",\n        \
+            macro_path: \"shapes::shapes::__export_tokens_tt_unit_square\",\n        \
+            kind: \"impl\",\n        emit: false,\n        accessible: true,\n    },\n]"
+        );
+    }
+    #[test]
+    fn shim_with_file_modules() {
+        let crate_dir = env::temp_dir().join(format!("macro_magic_shim_{}", std::process::id()));
+        fs::create_dir_all(crate_dir.join("src/models")).unwrap();
+        fs::write(
+            crate_dir.join("src/lib.rs"),
+            "pub mod models;\n#[cfg(feature = \"std\")] pub fn version() -> u32 {\n    \
+            #[cfg(test)] return 2;\n    1\n}",
+        )
+        .unwrap();
+        fs::write(
+            crate_dir.join("src/models.rs"),
+            "mod user;\npub use user::User;\n\
+            pub mod inline { pub enum Role { Admin, #[cfg(feature = \"guests\")] Guest } }",
+        )
+        .unwrap();
+        fs::write(
+            crate_dir.join("src/models/user.rs"),
+            "/// A user\n#[derive(Clone)]\n\
+            pub struct User { pub name: String, #[cfg(feature = \"std\")] pub email: String }",
+        )
+        .unwrap();
+        let shim = Shim::new("user-models")
+            .item("models::user::User")
+            .item("models::inline::Role")
+            .item("version")
+            .item("models::user::User");
+        let (source, files) = shim_source(&shim, crate_dir.join("src/lib.rs")).unwrap();
+        let missing = shim_source(
+            &Shim::new("user-models").item("models::Missing"),
+            crate_dir.join("src/lib.rs"),
+        )
+        .err()
+        .unwrap();
+        fs::remove_dir_all(&crate_dir).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            source,
+            "pub mod user_models {\n    #[::macro_magic::export_tokens_no_emit]\n    \
+            pub fn version() -> u32 {\n        return 2;\n        1\n    }\n    pub mod models {\n        \
+            pub mod inline {\n            #[::macro_magic::export_tokens_no_emit]\n            \
+            pub enum Role {\n                Admin,\n                Guest,\n            }\n        }\n        \
+            pub mod user {\n            #[::macro_magic::export_tokens_no_emit]\n            \
+            /// A user\n            #[derive(Clone)]\n            pub struct User {\n                \
+            pub name: String,\n                pub email: String,\n            }\n        }\n    }\n}\n"
+        );
+        assert!(missing
+            .to_string()
+            .starts_with("could not find `Missing` of `models::Missing`"));
+    }
+
+    #[test]
+    fn lib_root_prefers_the_building_package() {
+        let crate_dir =
+            env::temp_dir().join(format!("macro_magic_lib_root_{}", std::process::id()));
+        let metadata = serde_json::json!({
+            "packages": [
+                {
+                    "name": "workspace-root",
+                    "id": "root 0.1.0",
+                    "manifest_path": "/ws/Cargo.toml",
+                    "targets": [],
+                },
+                {
+                    "name": "member",
+                    "id": "member 0.1.0",
+                    "manifest_path": crate_dir.join("Cargo.toml"),
+                    "targets": [],
+                },
+                {
+                    "name": "dep",
+                    "id": "dep 1.0.0",
+                    "manifest_path": "/deps/dep-1/Cargo.toml",
+                    "targets": [{ "kind": ["lib"], "src_path": "/deps/dep-1/src/lib.rs" }],
+                },
+                {
+                    "name": "dep",
+                    "id": "dep 2.0.0",
+                    "manifest_path": "/deps/dep-2/Cargo.toml",
+                    "targets": [{ "kind": ["lib"], "src_path": "/deps/dep-2/src/lib.rs" }],
+                },
+            ],
+            "resolve": {
+                "root": "root 0.1.0",
+                "nodes": [
+                    { "id": "root 0.1.0", "dependencies": ["dep 1.0.0", "member 0.1.0"] },
+                    { "id": "member 0.1.0", "dependencies": ["dep 2.0.0"] },
+                ],
+            },
+        });
+        assert_eq!(
+            lib_root(&metadata, &crate_dir, "dep").unwrap(),
+            PathBuf::from("/deps/dep-2/src/lib.rs")
+        );
+        assert!(lib_root(&metadata, Path::new("/ws/other"), "dep").is_err());
+        assert!(lib_root(&metadata, &crate_dir, "missing").is_err());
+    }
+}
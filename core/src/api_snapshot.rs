@@ -0,0 +1,871 @@
+//! Contains the API snapshot facility, which records the `#[export_tokens]` items of a crate
+//! in a baseline file and classifies the changes between a baseline and the current source as
+//! additive or breaking.
+//!
+//! Crates are read from disk via [`scan_crate`], so this works offline and doesn't require
+//! compiling the crate. Items are recorded under the ident
+//! [`export_tokens_internal`](crate::export_tokens_internal) would export them under, with
+//! their doc comments removed, since those are not part of the API.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use proc_macro2::{Delimiter, Group, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse2, Attribute, Fields, GenericParam, Generics, ImplItem, Item, TraitItem, Visibility,
+};
+
+use crate::{
+    item_attrs, item_kind, pretty_print, reflect_signature_string, reflect_type_string, same_attr,
+    scan_crate,
+};
+
+/// The first line of every snapshot written by [`api_snapshot_string`].
+pub const API_SNAPSHOT_HEADER: &str = "# macro_magic API snapshot";
+
+/// The file name used for the baseline of a crate when none is specified, relative to the
+/// crate directory.
+pub const DEFAULT_API_SNAPSHOT_FILE: &str = "macro_magic_api.txt";
+
+/// An `#[export_tokens]` item of a crate, as recorded in an API snapshot.
+#[derive(Clone)]
+pub struct ApiItem {
+    /// The path of the item relative to the crate root, i.e. `config::Timeout`
+    pub path: String,
+    /// The item, without its export attribute and doc comments
+    pub item: Item,
+}
+
+impl ApiItem {
+    /// Returns the kind of the item as it is written in the source, i.e. `struct` or `fn`.
+    pub fn kind(&self) -> &'static str {
+        item_kind(&self.item)
+    }
+}
+
+/// Whether an [`ApiChange`] can break downstream code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiChangeKind {
+    /// The change only adds to the API, i.e. a new item or a new defaulted generic param
+    Additive,
+    /// The change can break downstream code, i.e. a removed field or a changed signature
+    Breaking,
+}
+
+/// A single difference between two API snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiChange {
+    /// The path of the item that changed, relative to the crate root
+    pub path: String,
+    /// Whether the change is additive or breaking
+    pub kind: ApiChangeKind,
+    /// A human-readable description of the change, i.e. ``field `secs` was removed``
+    pub description: String,
+}
+
+impl fmt::Display for ApiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ApiChangeKind::Additive => "additive",
+            ApiChangeKind::Breaking => "breaking",
+        };
+        write!(f, "{}: {}: {}", kind, self.path, self.description)
+    }
+}
+
+/// Removes every `#[doc = ".."]` attribute (i.e. doc comment) from `tokens`, recursively.
+fn strip_docs(tokens: TokenStream2) -> TokenStream2 {
+    let mut output = Vec::new();
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(tree) = iter.next() {
+        match tree {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = iter.peek() {
+                    let is_doc = group.delimiter() == Delimiter::Bracket
+                        && matches!(
+                            group.stream().into_iter().next(),
+                            Some(TokenTree::Ident(ident)) if ident == "doc"
+                        );
+                    if is_doc {
+                        iter.next();
+                        continue;
+                    }
+                }
+                output.push(TokenTree::Punct(punct));
+            }
+            TokenTree::Group(group) => {
+                let mut stripped = Group::new(group.delimiter(), strip_docs(group.stream()));
+                stripped.set_span(group.span());
+                output.push(TokenTree::Group(stripped));
+            }
+            tree => output.push(tree),
+        }
+    }
+    output.into_iter().collect()
+}
+
+/// Removes the export attribute `attr` and all doc comments from `item`.
+fn normalize_item(mut item: Item, attr: &Attribute) -> Item {
+    let attrs = match &mut item {
+        Item::Const(item) => &mut item.attrs,
+        Item::Enum(item) => &mut item.attrs,
+        Item::ExternCrate(item) => &mut item.attrs,
+        Item::Fn(item) => &mut item.attrs,
+        Item::Impl(item) => &mut item.attrs,
+        Item::Macro(item) => &mut item.attrs,
+        Item::Mod(item) => &mut item.attrs,
+        Item::Static(item) => &mut item.attrs,
+        Item::Struct(item) => &mut item.attrs,
+        Item::Trait(item) => &mut item.attrs,
+        Item::TraitAlias(item) => &mut item.attrs,
+        Item::Type(item) => &mut item.attrs,
+        Item::Union(item) => &mut item.attrs,
+        _ => return item,
+    };
+    attrs.retain(|other| !same_attr(other, attr));
+    parse2(strip_docs(item.to_token_stream())).unwrap_or(item)
+}
+
+/// Returns every `#[export_tokens]` item of the crate located at `crate_dir`, sorted by
+/// path.
+///
+/// The crate is read via [`scan_crate`], so items exported via custom aliases created with
+/// `export_tokens_alias!` are not included.
+pub fn crate_api(crate_dir: impl AsRef<FsPath>) -> io::Result<Vec<ApiItem>> {
+    let scan = scan_crate(crate_dir, &[])?;
+    Ok(scan
+        .exports
+        .into_iter()
+        .map(|export| ApiItem {
+            path: export.path(),
+            item: normalize_item(export.item, &export.attr),
+        })
+        .collect())
+}
+
+/// Renders `items` as a snapshot that can be checked in and later read back using
+/// [`parse_api_snapshot`]. Each item is pretty-printed below a `## path` line.
+pub fn api_snapshot_string(items: &[ApiItem]) -> String {
+    let mut output = format!("{}\n", API_SNAPSHOT_HEADER);
+    for api_item in items {
+        let pretty = pretty_print(&api_item.item.to_token_stream());
+        output.push_str(&format!("\n## {}\n{}", api_item.path, pretty));
+    }
+    output
+}
+
+/// Parses a snapshot written by [`api_snapshot_string`].
+pub fn parse_api_snapshot(snapshot: &str) -> io::Result<Vec<ApiItem>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut lines = snapshot.lines();
+    if lines.next().map(str::trim) != Some(API_SNAPSHOT_HEADER) {
+        return Err(invalid(format!(
+            "API snapshots must start with `{}`",
+            API_SNAPSHOT_HEADER
+        )));
+    }
+    let mut entries: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        match (line.strip_prefix("## "), entries.last_mut()) {
+            (Some(path), _) => entries.push((path.trim().to_string(), String::new())),
+            (None, Some((_, source))) => {
+                source.push_str(line);
+                source.push('\n');
+            }
+            (None, None) if line.trim().is_empty() => (),
+            (None, None) => return Err(invalid(format!("unexpected line `{}`", line))),
+        }
+    }
+    entries
+        .into_iter()
+        .map(|(path, source)| {
+            let item = syn::parse_str::<Item>(&source)
+                .map_err(|err| invalid(format!("failed to parse `{}`: {}", path, err)))?;
+            Ok(ApiItem { path, item })
+        })
+        .collect()
+}
+
+/// Writes the snapshot of the crate at `crate_dir` to `baseline`.
+pub fn write_api_snapshot(
+    crate_dir: impl AsRef<FsPath>,
+    baseline: impl AsRef<FsPath>,
+) -> io::Result<()> {
+    let items = crate_api(crate_dir)?;
+    fs::write(baseline, api_snapshot_string(&items))
+}
+
+/// Compares the crate at `crate_dir` against the snapshot stored in `baseline`, returning
+/// every change, breaking changes first.
+pub fn check_api_snapshot(
+    crate_dir: impl AsRef<FsPath>,
+    baseline: impl AsRef<FsPath>,
+) -> io::Result<Vec<ApiChange>> {
+    let baseline = parse_api_snapshot(&fs::read_to_string(baseline)?)?;
+    let current = crate_api(crate_dir)?;
+    let mut changes = diff_api(&baseline, &current);
+    changes.sort_by_key(|change| std::cmp::Reverse(change.kind));
+    Ok(changes)
+}
+
+/// Returns the default location of the baseline of the crate at `crate_dir`.
+pub fn default_api_snapshot_path(crate_dir: impl AsRef<FsPath>) -> PathBuf {
+    crate_dir.as_ref().join(DEFAULT_API_SNAPSHOT_FILE)
+}
+
+/// Collects the changes of a single item.
+struct ItemDiff<'a> {
+    path: &'a str,
+    changes: Vec<ApiChange>,
+}
+
+impl ItemDiff<'_> {
+    fn push(&mut self, kind: ApiChangeKind, description: String) {
+        self.changes.push(ApiChange {
+            path: self.path.to_string(),
+            kind,
+            description,
+        });
+    }
+
+    fn breaking(&mut self, description: String) {
+        self.push(ApiChangeKind::Breaking, description);
+    }
+
+    fn additive(&mut self, description: String) {
+        self.push(ApiChangeKind::Additive, description);
+    }
+
+    fn compare_visibility(&mut self, old: &Visibility, new: &Visibility) {
+        match (is_public(old), is_public(new)) {
+            (true, false) => self.breaking(String::from("is no longer public")),
+            (false, true) => self.additive(String::from("became public")),
+            _ => (),
+        }
+    }
+
+    fn compare_attrs(&mut self, old: &[Attribute], new: &[Attribute]) {
+        let (old_derives, new_derives) = (derives(old), derives(new));
+        for derive in old_derives.iter().filter(|d| !new_derives.contains(d)) {
+            self.breaking(format!("no longer derives `{}`", derive));
+        }
+        for derive in new_derives.iter().filter(|d| !old_derives.contains(d)) {
+            self.additive(format!("now derives `{}`", derive));
+        }
+        match (is_non_exhaustive(old), is_non_exhaustive(new)) {
+            (false, true) => self.breaking(String::from("became `#[non_exhaustive]`")),
+            (true, false) => self.additive(String::from("is no longer `#[non_exhaustive]`")),
+            _ => (),
+        }
+        let other = |attrs: &[Attribute]| {
+            attrs
+                .iter()
+                .filter(|attr| !attr.path().is_ident("derive"))
+                .filter(|attr| !attr.path().is_ident("non_exhaustive"))
+                .map(|attr| attr.to_token_stream().to_string())
+                .collect::<Vec<_>>()
+        };
+        if other(old) != other(new) {
+            self.breaking(String::from("attributes changed"));
+        }
+    }
+
+    fn compare_generics(&mut self, old: &Generics, new: &Generics) {
+        let old_params: BTreeMap<String, &GenericParam> =
+            old.params.iter().map(|p| (param_name(p), p)).collect();
+        let new_params: BTreeMap<String, &GenericParam> =
+            new.params.iter().map(|p| (param_name(p), p)).collect();
+        for name in old_params.keys().filter(|n| !new_params.contains_key(*n)) {
+            self.breaking(format!("generic param `{}` was removed", name));
+        }
+        for (name, param) in new_params
+            .iter()
+            .filter(|(n, _)| !old_params.contains_key(*n))
+        {
+            let has_default = match param {
+                GenericParam::Type(param) => param.default.is_some(),
+                GenericParam::Const(param) => param.default.is_some(),
+                GenericParam::Lifetime(_) => false,
+            };
+            match has_default {
+                true => self.additive(format!("defaulted generic param `{}` was added", name)),
+                false => self.breaking(format!("required generic param `{}` was added", name)),
+            }
+        }
+        for (name, old_param) in &old_params {
+            let Some(new_param) = new_params.get(name) else {
+                continue;
+            };
+            let (old_param, new_param) = (param_string(old_param), param_string(new_param));
+            if old_param != new_param {
+                self.breaking(format!(
+                    "generic param `{}` changed from `{}` to `{}`",
+                    name, old_param, new_param
+                ));
+            }
+        }
+        let where_string =
+            |generics: &Generics| generics.where_clause.to_token_stream().to_string();
+        if where_string(old) != where_string(new) {
+            self.breaking(String::from("where clause changed"));
+        }
+    }
+
+    /// Compares the fields of a struct or union, or of the enum variant `variant`. Adding
+    /// fields is only additive if the old fields can't all be specified in a literal, i.e. if
+    /// some are private or the struct or variant is `#[non_exhaustive]`.
+    fn compare_fields(
+        &mut self,
+        old: &Fields,
+        new: &Fields,
+        variant: Option<&str>,
+        non_exhaustive: bool,
+    ) {
+        let owner = match variant {
+            Some(variant) => format!(" of variant `{}`", variant),
+            None => String::new(),
+        };
+        let style = |fields: &Fields| match fields {
+            Fields::Named(_) => "named fields",
+            Fields::Unnamed(_) => "tuple fields",
+            Fields::Unit => "no fields",
+        };
+        if style(old) != style(new) {
+            self.breaking(format!(
+                "changed from {} to {}{}",
+                style(old),
+                style(new),
+                owner
+            ));
+            return;
+        }
+        let fields = |fields: &Fields| {
+            fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let name = match &field.ident {
+                        Some(ident) => ident.to_string(),
+                        None => i.to_string(),
+                    };
+                    let public = variant.is_some() || is_public(&field.vis);
+                    (name, (reflect_type_string(&field.ty), public))
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+        let (old_fields, new_fields) = (fields(old), fields(new));
+        let constructible = !non_exhaustive && old_fields.values().all(|(_, public)| *public);
+        for (name, (ty, public)) in &old_fields {
+            match new_fields.get(name) {
+                None if *public => {
+                    self.breaking(format!("field `{}`{} was removed", name, owner));
+                }
+                Some((new_ty, _)) if *public && ty != new_ty => self.breaking(format!(
+                    "type of field `{}`{} changed from `{}` to `{}`",
+                    name, owner, ty, new_ty
+                )),
+                Some((_, new_public)) if *public && !new_public => {
+                    self.breaking(format!("field `{}`{} is no longer public", name, owner));
+                }
+                Some((_, new_public)) if !public && *new_public => {
+                    self.additive(format!("field `{}`{} became public", name, owner));
+                }
+                _ => (),
+            }
+        }
+        for name in new_fields.keys().filter(|n| !old_fields.contains_key(*n)) {
+            match constructible {
+                true => self.breaking(format!("field `{}`{} was added", name, owner)),
+                false => self.additive(format!("field `{}`{} was added", name, owner)),
+            }
+        }
+    }
+}
+
+/// Returns `true` if `vis` is `pub` (restricted visibilities such as `pub(crate)` are not
+/// part of the public API).
+fn is_public(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+/// Returns `true` if `attrs` contain `#[non_exhaustive]`.
+fn is_non_exhaustive(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("non_exhaustive"))
+}
+
+/// Returns the traits derived via `#[derive(..)]` in `attrs`.
+fn derives(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .filter_map(|attr| attr.meta.require_list().ok())
+        .flat_map(|list| {
+            list.tokens
+                .to_string()
+                .split(',')
+                .map(|derive| derive.replace(' ', ""))
+                .filter(|derive| !derive.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Returns the name of a generic param, i.e. `T` or `'a`.
+fn param_name(param: &GenericParam) -> String {
+    match param {
+        GenericParam::Lifetime(param) => param.lifetime.to_string(),
+        GenericParam::Type(param) => param.ident.to_string(),
+        GenericParam::Const(param) => param.ident.to_string(),
+    }
+}
+
+/// Returns the pretty-printed form of a generic param, i.e. `T: Copy`.
+fn param_string(param: &GenericParam) -> String {
+    let pretty = pretty_print(&quote!(struct __T<#param>;));
+    pretty
+        .trim()
+        .trim_start_matches("struct __T<")
+        .trim_end_matches(">;")
+        .to_string()
+}
+
+/// Returns the name and pretty-printed form of each associated item of a trait, and whether
+/// it must be implemented (i.e. has no default).
+fn trait_members(items: &[TraitItem]) -> BTreeMap<String, (String, bool)> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            TraitItem::Fn(item) => Some((
+                item.sig.ident.to_string(),
+                (reflect_signature_string(&item.sig), item.default.is_none()),
+            )),
+            TraitItem::Const(item) => Some((
+                item.ident.to_string(),
+                (
+                    format!("const {}: {}", item.ident, reflect_type_string(&item.ty)),
+                    item.default.is_none(),
+                ),
+            )),
+            TraitItem::Type(item) => {
+                let mut item = item.clone();
+                let required = item.default.take().is_none();
+                item.attrs.clear();
+                let pretty = item.to_token_stream().to_string();
+                Some((item.ident.to_string(), (pretty, required)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the name and pretty-printed form of each associated item of an impl block.
+fn impl_members(items: &[ImplItem]) -> BTreeMap<String, String> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(item) => Some((
+                item.sig.ident.to_string(),
+                reflect_signature_string(&item.sig),
+            )),
+            ImplItem::Const(item) => Some((
+                item.ident.to_string(),
+                format!("const {}: {}", item.ident, reflect_type_string(&item.ty)),
+            )),
+            ImplItem::Type(item) => Some((
+                item.ident.to_string(),
+                format!("type {} = {}", item.ident, reflect_type_string(&item.ty)),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Appends the changes between two versions of the item at `path` to `changes`.
+fn diff_item(path: &str, old: &Item, new: &Item, changes: &mut Vec<ApiChange>) {
+    let mut diff = ItemDiff {
+        path,
+        changes: Vec::new(),
+    };
+    if item_kind(old) != item_kind(new) {
+        diff.breaking(format!(
+            "changed from `{}` to `{}`",
+            item_kind(old),
+            item_kind(new)
+        ));
+        changes.append(&mut diff.changes);
+        return;
+    }
+    diff.compare_attrs(item_attrs(old), item_attrs(new));
+    match (old, new) {
+        (Item::Struct(old), Item::Struct(new)) => {
+            diff.compare_visibility(&old.vis, &new.vis);
+            diff.compare_generics(&old.generics, &new.generics);
+            let non_exhaustive = is_non_exhaustive(&old.attrs);
+            diff.compare_fields(&old.fields, &new.fields, None, non_exhaustive);
+        }
+        (Item::Union(old), Item::Union(new)) => {
+            diff.compare_visibility(&old.vis, &new.vis);
+            diff.compare_generics(&old.generics, &new.generics);
+            let old_fields = Fields::Named(old.fields.clone());
+            let new_fields = Fields::Named(new.fields.clone());
+            diff.compare_fields(&old_fields, &new_fields, None, false);
+        }
+        (Item::Enum(old), Item::Enum(new)) => {
+            diff.compare_visibility(&old.vis, &new.vis);
+            diff.compare_generics(&old.generics, &new.generics);
+            for old_variant in &old.variants {
+                let name = old_variant.ident.to_string();
+                let Some(new_variant) = new.variants.iter().find(|v| v.ident == old_variant.ident)
+                else {
+                    diff.breaking(format!("variant `{}` was removed", name));
+                    continue;
+                };
+                let non_exhaustive = is_non_exhaustive(&old_variant.attrs);
+                diff.compare_fields(
+                    &old_variant.fields,
+                    &new_variant.fields,
+                    Some(&name),
+                    non_exhaustive,
+                );
+                let discriminant = |variant: &syn::Variant| {
+                    variant
+                        .discriminant
+                        .as_ref()
+                        .map(|(_, expr)| expr.to_token_stream().to_string())
+                };
+                if discriminant(old_variant) != discriminant(new_variant) {
+                    diff.breaking(format!("discriminant of variant `{}` changed", name));
+                }
+            }
+            let added = new
+                .variants
+                .iter()
+                .filter(|new_variant| !old.variants.iter().any(|v| v.ident == new_variant.ident));
+            for variant in added {
+                match is_non_exhaustive(&old.attrs) {
+                    true => diff.additive(format!("variant `{}` was added", variant.ident)),
+                    false => diff.breaking(format!("variant `{}` was added", variant.ident)),
+                }
+            }
+        }
+        (Item::Fn(old), Item::Fn(new)) => {
+            diff.compare_visibility(&old.vis, &new.vis);
+            let (old_sig, new_sig) = (
+                reflect_signature_string(&old.sig),
+                reflect_signature_string(&new.sig),
+            );
+            if old_sig != new_sig {
+                diff.breaking(format!(
+                    "signature changed from `{}` to `{}`",
+                    old_sig, new_sig
+                ));
+            }
+        }
+        (Item::Trait(old), Item::Trait(new)) => {
+            diff.compare_visibility(&old.vis, &new.vis);
+            diff.compare_generics(&old.generics, &new.generics);
+            let bounds = |item: &syn::ItemTrait| item.supertraits.to_token_stream().to_string();
+            if bounds(old) != bounds(new) {
+                diff.breaking(String::from("supertraits changed"));
+            }
+            let (old_items, new_items) = (trait_members(&old.items), trait_members(&new.items));
+            for (name, (old_item, _)) in &old_items {
+                match new_items.get(name) {
+                    None => diff.breaking(format!("`{}` was removed", name)),
+                    Some((new_item, _)) if old_item != new_item => diff.breaking(format!(
+                        "`{}` changed from `{}` to `{}`",
+                        name, old_item, new_item
+                    )),
+                    Some((_, true)) if !old_items[name].1 => {
+                        diff.breaking(format!("`{}` no longer has a default", name));
+                    }
+                    _ => (),
+                }
+            }
+            for (name, (_, required)) in &new_items {
+                if old_items.contains_key(name) {
+                    continue;
+                }
+                match required {
+                    true => diff.breaking(format!("required item `{}` was added", name)),
+                    false => diff.additive(format!("provided item `{}` was added", name)),
+                }
+            }
+        }
+        (Item::Impl(old), Item::Impl(new)) => {
+            let header = |item: &syn::ItemImpl| {
+                let mut item = item.clone();
+                item.items.clear();
+                item.attrs.clear();
+                item.to_token_stream().to_string()
+            };
+            if header(old) != header(new) {
+                diff.breaking(String::from("impl header changed"));
+            }
+            let (old_items, new_items) = (impl_members(&old.items), impl_members(&new.items));
+            for (name, old_item) in &old_items {
+                match new_items.get(name) {
+                    None => diff.breaking(format!("`{}` was removed", name)),
+                    Some(new_item) if old_item != new_item => diff.breaking(format!(
+                        "`{}` changed from `{}` to `{}`",
+                        name, old_item, new_item
+                    )),
+                    _ => (),
+                }
+            }
+            for name in new_items.keys().filter(|n| !old_items.contains_key(*n)) {
+                diff.additive(format!("`{}` was added", name));
+            }
+        }
+        (old, new) => {
+            if old.to_token_stream().to_string() != new.to_token_stream().to_string() {
+                diff.breaking(String::from("definition changed"));
+            }
+        }
+    }
+    changes.append(&mut diff.changes);
+}
+
+/// Returns the changes between the `baseline` and `current` versions of an API, in the order
+/// of the items in `current`, followed by removed items.
+pub fn diff_api(baseline: &[ApiItem], current: &[ApiItem]) -> Vec<ApiChange> {
+    let mut changes = Vec::new();
+    for api_item in current {
+        match baseline.iter().find(|old| old.path == api_item.path) {
+            Some(old) => diff_item(&api_item.path, &old.item, &api_item.item, &mut changes),
+            None => changes.push(ApiChange {
+                path: api_item.path.clone(),
+                kind: ApiChangeKind::Additive,
+                description: format!("{} was added", api_item.kind()),
+            }),
+        }
+    }
+    for old in baseline {
+        if !current.iter().any(|api_item| api_item.path == old.path) {
+            changes.push(ApiChange {
+                path: old.path.clone(),
+                kind: ApiChangeKind::Breaking,
+                description: format!("{} was removed", old.kind()),
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn api(path: &str, item: Item) -> ApiItem {
+        ApiItem {
+            path: path.to_string(),
+            item,
+        }
+    }
+
+    fn descriptions(changes: &[ApiChange]) -> Vec<String> {
+        changes.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    #[cfg(feature = "pretty")]
+    fn api_snapshot_of_crate() {
+        let crate_dir = FsPath::new(env!("CARGO_MANIFEST_DIR")).join("../tests/external_crate");
+        let items = crate_api(&crate_dir).unwrap();
+        let paths: Vec<&str> = items.iter().map(|item| item.path.as_str()).collect();
+        assert!(paths.contains(&"config::Timeout"));
+        assert!(paths.contains(&"inherit::square_shape"));
+        assert!(paths.contains(&"some_submodule::AnExternalTraitImpl"));
+        let snapshot = api_snapshot_string(&items);
+        assert!(snapshot.starts_with("# macro_magic API snapshot\n\n## an_external_function\n"));
+        assert!(snapshot.contains("\n## units::Point\n#[derive(Debug, PartialEq)]\n#[repr(C)]\n"));
+        assert!(!snapshot.contains("A point in 2D space."));
+        let parsed = parse_api_snapshot(&snapshot).unwrap();
+        assert_eq!(parsed.len(), items.len());
+        assert!(diff_api(&items, &parsed).is_empty());
+        assert!(parse_api_snapshot("## config::Timeout\nstruct Timeout;").is_err());
+    }
+
+    #[test]
+    fn api_diff_types() {
+        let baseline = [
+            api(
+                "Config",
+                parse_quote! {
+                    pub struct Config<T> {
+                        pub host: String,
+                        pub port: u16,
+                        pub tags: T,
+                    }
+                },
+            ),
+            api(
+                "Event",
+                parse_quote! {
+                    #[derive(Debug, Clone)]
+                    pub enum Event { Started, Progress(u8), Finished }
+                },
+            ),
+            api(
+                "Handle",
+                parse_quote!(
+                    pub struct Handle {
+                        id: u32,
+                    }
+                ),
+            ),
+        ];
+        let current = [
+            api(
+                "Config",
+                parse_quote! {
+                    pub struct Config<T, U> {
+                        pub host: String,
+                        pub port: u32,
+                        pub tags: T,
+                        pub extra: U,
+                    }
+                },
+            ),
+            api(
+                "Event",
+                parse_quote! {
+                    #[derive(Debug)]
+                    pub enum Event { Started, Progress(u8, u8), Failed }
+                },
+            ),
+            api(
+                "Handle",
+                parse_quote!(
+                    pub struct Handle {
+                        id: u64,
+                        pub name: String,
+                    }
+                ),
+            ),
+            api(
+                "Retry",
+                parse_quote!(
+                    pub struct Retry;
+                ),
+            ),
+        ];
+        assert_eq!(
+            descriptions(&diff_api(&baseline, &current)),
+            [
+                "breaking: Config: required generic param `U` was added",
+                "breaking: Config: type of field `port` changed from `u16` to `u32`",
+                "breaking: Config: field `extra` was added",
+                "breaking: Event: no longer derives `Clone`",
+                "breaking: Event: field `1` of variant `Progress` was added",
+                "breaking: Event: variant `Finished` was removed",
+                "breaking: Event: variant `Failed` was added",
+                "additive: Handle: field `name` was added",
+                "additive: Retry: struct was added",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "pretty")]
+    fn api_diff_fns_and_traits() {
+        let baseline = [
+            api(
+                "Repo",
+                parse_quote! {
+                    pub trait Repo {
+                        fn get(&self, id: u32) -> Option<u8>;
+                        fn count(&self) -> usize { 0 }
+                        fn clear(&mut self);
+                    }
+                },
+            ),
+            api(
+                "parse",
+                parse_quote!(
+                    pub fn parse(input: &str) -> u32 {
+                        0
+                    }
+                ),
+            ),
+            api(
+                "Status",
+                parse_quote! {
+                    #[non_exhaustive]
+                    pub enum Status { Ok }
+                },
+            ),
+            api(
+                "Wrapper",
+                parse_quote!(
+                    pub struct Wrapper<T>(pub T);
+                ),
+            ),
+            api(
+                "LIMIT",
+                parse_quote!(
+                    pub const LIMIT: u32 = 4;
+                ),
+            ),
+        ];
+        let current = [
+            api(
+                "Repo",
+                parse_quote! {
+                    pub trait Repo {
+                        fn get(&self, id: u64) -> Option<u8>;
+                        fn count(&self) -> usize;
+                        fn len(&self) -> usize { 0 }
+                        fn put(&mut self, id: u32);
+                    }
+                },
+            ),
+            api(
+                "parse",
+                parse_quote!(
+                    pub fn parse(input: &str) -> u32 {
+                        1
+                    }
+                ),
+            ),
+            api(
+                "Status",
+                parse_quote! {
+                    #[non_exhaustive]
+                    pub enum Status { Ok, Pending }
+                },
+            ),
+            api(
+                "Wrapper",
+                parse_quote!(
+                    pub(crate) struct Wrapper<T, A = ()>(pub T, A);
+                ),
+            ),
+        ];
+        let changes = diff_api(&baseline, &current);
+        assert_eq!(
+            descriptions(&changes),
+            [
+                "breaking: Repo: `clear` was removed",
+                "breaking: Repo: `count` no longer has a default",
+                "breaking: Repo: `get` changed from `fn get(&self, id: u32) -> Option<u8>` to \
+                `fn get(&self, id: u64) -> Option<u8>`",
+                "additive: Repo: provided item `len` was added",
+                "breaking: Repo: required item `put` was added",
+                "additive: Status: variant `Pending` was added",
+                "breaking: Wrapper: is no longer public",
+                "additive: Wrapper: defaulted generic param `A` was added",
+                "breaking: Wrapper: field `1` was added",
+                "breaking: LIMIT: const was removed",
+            ]
+        );
+        assert_eq!(changes[0].kind, ApiChangeKind::Breaking);
+    }
+}
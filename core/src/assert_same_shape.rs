@@ -0,0 +1,545 @@
+//! Contains the implementation behind the `assert_same_shape!` macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    parse2,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Attribute, Error, Fields, Item, Path, Result, Token, Variant, Visibility,
+};
+
+use crate::{
+    item_kind, keywords, macro_magic_root, pretty_print, reflect_docs, reflect_type_string,
+    starts_with_keyword, trace_stage, ForwardedPipeline,
+};
+
+/// Specifies which parts of two items `assert_same_shape!` doesn't compare.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct ShapeIgnore {
+    /// Whether attributes other than doc comments are ignored, specified via `attrs`
+    pub attrs: bool,
+    /// Whether doc comments are ignored, specified via `docs`
+    pub docs: bool,
+    /// Whether the visibility of items and fields is ignored, specified via `visibility`
+    pub visibility: bool,
+}
+
+/// Used to parse the args of [`assert_same_shape_internal`], i.e. `a::Foo, b::Foo` or
+/// `a::Foo, b::Foo, ignore(attrs, docs, visibility)`.
+///
+/// You shouldn't need to use this directly.
+pub struct AssertSameShapeArgs {
+    /// The path of the first `#[export_tokens]` item
+    pub a: Path,
+    /// The path of the second `#[export_tokens]` item
+    pub b: Path,
+    /// The parts of the items that aren't compared
+    pub ignore: ShapeIgnore,
+}
+
+impl Parse for AssertSameShapeArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let a = input.parse::<Path>()?;
+        input.parse::<Token![,]>()?;
+        let b = input.parse::<Path>()?;
+        let mut ignore = ShapeIgnore::default();
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            input.parse::<keywords::ignore>()?;
+            let content;
+            parenthesized!(content in input);
+            let parts = Punctuated::<IgnoredPart, Token![,]>::parse_terminated(&content)?;
+            for part in parts {
+                match part {
+                    IgnoredPart::Attrs => ignore.attrs = true,
+                    IgnoredPart::Docs => ignore.docs = true,
+                    IgnoredPart::Visibility => ignore.visibility = true,
+                }
+            }
+            input.parse::<Option<Token![,]>>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after `ignore(..)`"));
+        }
+        Ok(AssertSameShapeArgs { a, b, ignore })
+    }
+}
+
+impl ToTokens for AssertSameShapeArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let (a, b) = (&self.a, &self.b);
+        tokens.extend(quote!(#a, #b));
+        let mut parts = Vec::new();
+        if self.ignore.attrs {
+            parts.push(quote!(attrs));
+        }
+        if self.ignore.docs {
+            parts.push(quote!(docs));
+        }
+        if self.ignore.visibility {
+            parts.push(quote!(visibility));
+        }
+        if !parts.is_empty() {
+            tokens.extend(quote!(, ignore(#(#parts),*)));
+        }
+    }
+}
+
+/// A single entry of `ignore(..)`.
+enum IgnoredPart {
+    Attrs,
+    Docs,
+    Visibility,
+}
+
+impl Parse for IgnoredPart {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lookahead = input.lookahead1();
+        if lookahead.peek(keywords::attrs) {
+            input.parse::<keywords::attrs>()?;
+            Ok(IgnoredPart::Attrs)
+        } else if lookahead.peek(keywords::docs) {
+            input.parse::<keywords::docs>()?;
+            Ok(IgnoredPart::Docs)
+        } else if lookahead.peek(keywords::visibility) {
+            input.parse::<keywords::visibility>()?;
+            Ok(IgnoredPart::Visibility)
+        } else {
+            Err(lookahead.error())
+        }
+    }
+}
+
+/// Returns the single-line form of the non-doc attributes in `attrs`, or `none`.
+fn attrs_string(attrs: &[&Attribute]) -> String {
+    if attrs.is_empty() {
+        return String::from("none");
+    }
+    attrs
+        .iter()
+        .map(|attr| {
+            let pretty = pretty_print(&quote!(#attr struct __S;));
+            format!(
+                "`{}`",
+                pretty.trim().trim_end_matches("struct __S;").trim_end()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the string form of `vis`, i.e. `pub(crate)`, or `private`.
+fn visibility_string(vis: &Visibility) -> String {
+    match vis {
+        Visibility::Inherited => String::from("private"),
+        vis => vis.to_token_stream().to_string().replace(' ', ""),
+    }
+}
+
+/// Collects the differences between two items, each of which is prefixed by the part of the
+/// items it concerns.
+struct ShapeComparer {
+    ignore: ShapeIgnore,
+    diffs: Vec<String>,
+}
+
+impl ShapeComparer {
+    fn diff(&mut self, context: &str, message: String) {
+        self.diffs.push(format!("{}: {}", context, message));
+    }
+
+    fn compare_attrs(&mut self, context: &str, a: &[Attribute], b: &[Attribute]) {
+        if !self.ignore.attrs {
+            let a: Vec<&Attribute> = a
+                .iter()
+                .filter(|attr| !attr.path().is_ident("doc"))
+                .collect();
+            let b: Vec<&Attribute> = b
+                .iter()
+                .filter(|attr| !attr.path().is_ident("doc"))
+                .collect();
+            let tokens = |attrs: &[&Attribute]| {
+                attrs
+                    .iter()
+                    .map(|attr| attr.to_token_stream().to_string())
+                    .collect::<Vec<_>>()
+            };
+            if tokens(&a) != tokens(&b) {
+                let message = format!("attributes {} != {}", attrs_string(&a), attrs_string(&b));
+                self.diff(context, message);
+            }
+        }
+        if !self.ignore.docs {
+            let (a, b) = (reflect_docs(a), reflect_docs(b));
+            if a != b {
+                self.diff(context, format!("doc comments {:?} != {:?}", a, b));
+            }
+        }
+    }
+
+    fn compare_visibility(&mut self, context: &str, a: &Visibility, b: &Visibility) {
+        let (a, b) = (visibility_string(a), visibility_string(b));
+        if !self.ignore.visibility && a != b {
+            self.diff(context, format!("visibility `{}` != `{}`", a, b));
+        }
+    }
+
+    /// Compares the fields of a struct or union (`owner` is its kind) or of an enum variant
+    /// (`owner` is the variant, which is prefixed to the context of each field).
+    fn compare_fields(&mut self, owner: &str, is_variant: bool, a: &Fields, b: &Fields) {
+        let context = match is_variant {
+            true => format!("{}, ", owner),
+            false => String::new(),
+        };
+        let style = |fields: &Fields| match fields {
+            Fields::Named(_) => "named fields",
+            Fields::Unnamed(_) => "tuple fields",
+            Fields::Unit => "no fields",
+        };
+        if style(a) != style(b) {
+            self.diff(owner, format!("{} != {}", style(a), style(b)));
+            return;
+        }
+        for (i, (field_a, field_b)) in a.iter().zip(b.iter()).enumerate() {
+            let field_context = match &field_a.ident {
+                Some(ident) => format!("{}field `{}`", context, ident),
+                None => format!("{}field {}", context, i),
+            };
+            if field_a.ident != field_b.ident {
+                let (name_a, name_b) = (field_a.ident.as_ref(), field_b.ident.as_ref());
+                self.diff(
+                    &format!("{}field {}", context, i),
+                    format!("name `{}` != `{}`", name_a.unwrap(), name_b.unwrap()),
+                );
+            }
+            let (ty_a, ty_b) = (
+                reflect_type_string(&field_a.ty),
+                reflect_type_string(&field_b.ty),
+            );
+            if ty_a != ty_b {
+                self.diff(&field_context, format!("type `{}` != `{}`", ty_a, ty_b));
+            }
+            self.compare_visibility(&field_context, &field_a.vis, &field_b.vis);
+            self.compare_attrs(&field_context, &field_a.attrs, &field_b.attrs);
+        }
+        let (len_a, len_b) = (a.len(), b.len());
+        if len_a != len_b {
+            let (side, extra) = match len_a > len_b {
+                true => ("first", a.iter().skip(len_b)),
+                false => ("second", b.iter().skip(len_a)),
+            };
+            for field in extra {
+                let ty = reflect_type_string(&field.ty);
+                let field = match &field.ident {
+                    Some(ident) => format!("`{}: {}`", ident, ty),
+                    None => format!("`{}`", ty),
+                };
+                self.diff(owner, format!("only the {} item has field {}", side, field));
+            }
+        }
+    }
+
+    fn compare_variants<'a>(
+        &mut self,
+        a: impl ExactSizeIterator<Item = &'a Variant>,
+        b: impl ExactSizeIterator<Item = &'a Variant>,
+    ) {
+        let a: Vec<&Variant> = a.collect();
+        let b: Vec<&Variant> = b.collect();
+        for (i, (variant_a, variant_b)) in a.iter().zip(b.iter()).enumerate() {
+            if variant_a.ident != variant_b.ident {
+                self.diff(
+                    &format!("variant {}", i),
+                    format!("name `{}` != `{}`", variant_a.ident, variant_b.ident),
+                );
+            }
+            let context = format!("variant `{}`", variant_a.ident);
+            let discriminant = |variant: &Variant| match &variant.discriminant {
+                Some((_, expr)) => expr.to_token_stream().to_string(),
+                None => String::from("none"),
+            };
+            let (disc_a, disc_b) = (discriminant(variant_a), discriminant(variant_b));
+            if disc_a != disc_b {
+                self.diff(
+                    &context,
+                    format!("discriminant `{}` != `{}`", disc_a, disc_b),
+                );
+            }
+            self.compare_attrs(&context, &variant_a.attrs, &variant_b.attrs);
+            self.compare_fields(&context, true, &variant_a.fields, &variant_b.fields);
+        }
+        let (side, extra) = match a.len() > b.len() {
+            true => ("first", &a[b.len()..]),
+            false => ("second", &b[a.len()..]),
+        };
+        for variant in extra {
+            self.diff(
+                "enum",
+                format!("only the {} item has variant `{}`", side, variant.ident),
+            );
+        }
+    }
+}
+
+/// Returns the differences between the shapes of `a` and `b`, which must be structs, enums or
+/// unions, as one human-readable line per difference. Item names are not compared, so items
+/// with different names can have the same shape.
+pub fn shape_differences(a: &Item, b: &Item, ignore: ShapeIgnore) -> Vec<String> {
+    let mut comparer = ShapeComparer {
+        ignore,
+        diffs: Vec::new(),
+    };
+    let generics = |generics: &syn::Generics| {
+        let params = &generics.params;
+        let where_clause = &generics.where_clause;
+        quote!(<#params> #where_clause).to_string()
+    };
+    let item = item_kind(a);
+    match (a, b) {
+        (Item::Struct(a), Item::Struct(b)) => {
+            comparer.compare_visibility(item, &a.vis, &b.vis);
+            comparer.compare_attrs(item, &a.attrs, &b.attrs);
+            if generics(&a.generics) != generics(&b.generics) {
+                comparer.diff(item, String::from("generics differ"));
+            }
+            comparer.compare_fields(item, false, &a.fields, &b.fields);
+        }
+        (Item::Union(a), Item::Union(b)) => {
+            comparer.compare_visibility(item, &a.vis, &b.vis);
+            comparer.compare_attrs(item, &a.attrs, &b.attrs);
+            if generics(&a.generics) != generics(&b.generics) {
+                comparer.diff(item, String::from("generics differ"));
+            }
+            let (a, b) = (
+                Fields::Named(a.fields.clone()),
+                Fields::Named(b.fields.clone()),
+            );
+            comparer.compare_fields(item, false, &a, &b);
+        }
+        (Item::Enum(a), Item::Enum(b)) => {
+            comparer.compare_visibility(item, &a.vis, &b.vis);
+            comparer.compare_attrs(item, &a.attrs, &b.attrs);
+            if generics(&a.generics) != generics(&b.generics) {
+                comparer.diff(item, String::from("generics differ"));
+            }
+            comparer.compare_variants(a.variants.iter(), b.variants.iter());
+        }
+        _ if item_kind(a) == item_kind(b) => comparer.diff(
+            "item",
+            format!(
+                "only structs, enums and unions can be compared, not `{}`",
+                item
+            ),
+        ),
+        _ => comparer.diff(
+            "item",
+            format!("kind `{}` != `{}`", item_kind(a), item_kind(b)),
+        ),
+    }
+    comparer.diffs
+}
+
+/// Generates nothing if the exported items `a` and `b` (imported from `args.a` and `args.b`)
+/// have the same shape, or a compile error listing every difference between them.
+pub fn assert_same_shape_tokens(
+    a: &Item,
+    b: &Item,
+    args: &AssertSameShapeArgs,
+) -> Result<TokenStream2> {
+    for (item, path) in [(a, &args.a), (b, &args.b)] {
+        if !matches!(item, Item::Struct(_) | Item::Enum(_) | Item::Union(_)) {
+            return Err(Error::new(
+                path.span(),
+                format!(
+                    "`{}` is not a struct, enum or union, so its shape can't be compared",
+                    path.to_token_stream().to_string().replace(' ', "")
+                ),
+            ));
+        }
+    }
+    let diffs = shape_differences(a, b, args.ignore);
+    if diffs.is_empty() {
+        return Ok(TokenStream2::new());
+    }
+    let lines: Vec<String> = diffs.iter().map(|diff| format!("- {}", diff)).collect();
+    Err(Error::new(
+        args.a.span(),
+        format!(
+            "`{}` and `{}` have different shapes:\n{}",
+            args.a.to_token_stream().to_string().replace(' ', ""),
+            args.b.to_token_stream().to_string().replace(' ', ""),
+            lines.join("\n")
+        ),
+    ))
+}
+
+/// Internal implementation of the `assert_same_shape!` macro.
+///
+/// When invoked by the user, `tokens` are parsed as [`AssertSameShapeArgs`] and both items are
+/// imported via `forward_pipeline!`, which passes them back to `assert_same_shape!` along with
+/// these args. When invoked with the imported items, they are compared via
+/// [`assert_same_shape_tokens`].
+pub fn assert_same_shape_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output =
+        match starts_with_keyword::<keywords::__private_macro_magic_tokens_forwarded>(&tokens) {
+            true => {
+                let pipeline = parse2::<ForwardedPipeline>(tokens.clone())?;
+                let args = parse2::<AssertSameShapeArgs>(pipeline.args)?;
+                let [(_, a), (_, b)] = &pipeline.items[..] else {
+                    return Err(Error::new(
+                        args.a.span(),
+                        "expected exactly two imported items",
+                    ));
+                };
+                assert_same_shape_tokens(a, b, &args)?
+            }
+            false => {
+                let args = parse2::<AssertSameShapeArgs>(tokens.clone())?;
+                let mm_path = macro_magic_root();
+                let (a, b) = (&args.a, &args.b);
+                match a.to_token_stream().to_string() == b.to_token_stream().to_string() {
+                    // an item trivially has the same shape as itself
+                    true => TokenStream2::new(),
+                    false => quote! {
+                        #mm_path::forward_pipeline! {
+                            #a, #b => #mm_path::__private::assert_same_shape;
+                            args = { #args }
+                        }
+                    },
+                }
+            }
+        };
+    trace_stage("assert_same_shape", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    #[cfg(feature = "pretty")]
+    fn same_shape_structs() {
+        let a: Item = parse_quote! {
+            /// A header.
+            #[repr(C)]
+            pub struct Header<T> {
+                pub id: u32,
+                pub len: u16,
+                data: T,
+            }
+        };
+        let b: Item = parse_quote! {
+            /// A header.
+            #[repr(C)]
+            pub struct WireHeader<T> {
+                pub id: u32,
+                pub len: u16,
+                data: T,
+            }
+        };
+        assert!(shape_differences(&a, &b, ShapeIgnore::default()).is_empty());
+        let c: Item = parse_quote! {
+            #[repr(C, packed)]
+            pub(crate) struct Header<T> {
+                pub id: u64,
+                pub length: u16,
+            }
+        };
+        assert_eq!(
+            shape_differences(&a, &c, ShapeIgnore::default()),
+            [
+                "struct: visibility `pub` != `pub(crate)`",
+                "struct: attributes `#[repr(C)]` != `#[repr(C, packed)]`",
+                "struct: doc comments \"A header.\" != \"\"",
+                "field `id`: type `u32` != `u64`",
+                "field 1: name `len` != `length`",
+                "struct: only the first item has field `data: T`",
+            ]
+        );
+        let ignore = ShapeIgnore {
+            attrs: true,
+            docs: true,
+            visibility: true,
+        };
+        assert_eq!(shape_differences(&a, &c, ignore).len(), 3);
+    }
+
+    #[test]
+    fn same_shape_enums() {
+        let a: Item = parse_quote! {
+            enum Shape {
+                Circle(u32),
+                Rect { w: u32, h: u32 },
+                Empty = 4,
+            }
+        };
+        let b: Item = parse_quote! {
+            enum Shape {
+                Circle(u64),
+                Rect { w: u32 },
+                Empty = 5,
+                Line,
+            }
+        };
+        assert_eq!(
+            shape_differences(&a, &b, ShapeIgnore::default()),
+            [
+                "variant `Circle`, field 0: type `u32` != `u64`",
+                "variant `Rect`: only the first item has field `h: u32`",
+                "variant `Empty`: discriminant `4` != `5`",
+                "enum: only the second item has variant `Line`",
+            ]
+        );
+        let c: Item = parse_quote!(
+            struct Shape;
+        );
+        assert_eq!(
+            shape_differences(&a, &c, ShapeIgnore::default()),
+            ["item: kind `enum` != `struct`"]
+        );
+    }
+
+    #[test]
+    fn same_shape_args() {
+        let args =
+            parse2::<AssertSameShapeArgs>(quote!(a::Foo, b::Foo, ignore(docs, attrs))).unwrap();
+        assert!(args.ignore.attrs && args.ignore.docs && !args.ignore.visibility);
+        assert_eq!(
+            args.to_token_stream().to_string(),
+            "a :: Foo , b :: Foo , ignore (attrs , docs)"
+        );
+        assert!(parse2::<AssertSameShapeArgs>(quote!(a::Foo, b::Foo, ignore(names))).is_err());
+        assert!(assert_same_shape_internal(quote!(a::Foo, a::Foo))
+            .unwrap()
+            .is_empty());
+        let err = assert_same_shape_tokens(
+            &parse_quote!(
+                struct Foo(u8);
+            ),
+            &parse_quote!(
+                struct Foo(u16);
+            ),
+            &args,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`a::Foo` and `b::Foo` have different shapes:\n- field 0: type `u8` != `u16`"
+        );
+        assert!(assert_same_shape_tokens(
+            &parse_quote!(
+                fn foo() {}
+            ),
+            &parse_quote!(
+                fn foo() {}
+            ),
+            &args
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,461 @@
+//! Contains the implementation behind the `builder_for!` macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    parse2,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Attribute, Error, Expr, Fields, Ident, Item, ItemStruct, Meta, Path, Result, Token, Visibility,
+};
+
+use crate::{macro_magic_root, parse_forwarded_proc, trace_stage};
+
+/// The per-field configuration passed to `builder_for!`, i.e. `#[default(30)] timeout`.
+///
+/// You shouldn't need to use this directly.
+pub struct BuilderField {
+    /// The `#[default]` or `#[default(..)]` attribute of the field
+    pub attr: Attribute,
+    /// The field being configured
+    pub ident: Ident,
+}
+
+impl BuilderField {
+    /// Returns the expression that produces the default value of the field, i.e.
+    /// `::core::default::Default::default()` for `#[default]` and `30` for `#[default(30)]`.
+    pub fn default_expr(&self) -> Result<TokenStream2> {
+        match &self.attr.meta {
+            Meta::Path(_) => Ok(quote!(::core::default::Default::default())),
+            Meta::List(list) => Ok(parse2::<Expr>(list.tokens.clone())?.to_token_stream()),
+            Meta::NameValue(_) => Err(Error::new(
+                self.attr.span(),
+                "expected `#[default]` or `#[default(value)]`",
+            )),
+        }
+    }
+}
+
+impl Parse for BuilderField {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse::<Ident>()?;
+        let attr = match (attrs.pop(), attrs.is_empty()) {
+            (Some(attr), true) if attr.path().is_ident("default") => attr,
+            _ => {
+                return Err(Error::new(
+                    ident.span(),
+                    format!(
+                        "expected a single `#[default]` or `#[default(value)]` attribute on `{}`",
+                        ident
+                    ),
+                ))
+            }
+        };
+        let field = BuilderField { attr, ident };
+        field.default_expr()?;
+        Ok(field)
+    }
+}
+
+impl ToTokens for BuilderField {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.attr.to_tokens(tokens);
+        self.ident.to_tokens(tokens);
+    }
+}
+
+/// Used to parse the args of [`builder_for_internal`], i.e. `upstream::Config`,
+/// `upstream::Config as ConfigBuilder` or `upstream::Config { #[default(30)] timeout }`.
+///
+/// You shouldn't need to use this directly.
+pub struct BuilderForArgs {
+    /// The path of the `#[export_tokens]` struct that will be built
+    pub source: Path,
+    /// The ident of the builder, if specified via `as ..`. Defaults to `<Struct>Builder`.
+    pub ident: Option<Ident>,
+    /// The fields that have a default value, and are therefore optional
+    pub fields: Punctuated<BuilderField, Token![,]>,
+}
+
+impl Parse for BuilderForArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse::<Path>()?;
+        let ident = match input.parse::<Option<Token![as]>>()? {
+            Some(_) => Some(input.parse::<Ident>()?),
+            None => None,
+        };
+        let fields = match input.is_empty() {
+            true => Punctuated::new(),
+            false => {
+                let content;
+                braced!(content in input);
+                content.parse_terminated(BuilderField::parse, Token![,])?
+            }
+        };
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after the field defaults"));
+        }
+        Ok(BuilderForArgs {
+            source,
+            ident,
+            fields,
+        })
+    }
+}
+
+impl ToTokens for BuilderForArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.source.to_tokens(tokens);
+        if let Some(ident) = &self.ident {
+            tokens.extend(quote!(as #ident));
+        }
+        let fields = &self.fields;
+        tokens.extend(quote!({ #fields }));
+    }
+}
+
+/// Generates a builder for the `foreign` struct as specified by `args`, with one setter per
+/// field and a `build()` method returning an error that names the missing required fields.
+///
+/// Returns an error if `foreign` doesn't have named fields, if any of its fields are private,
+/// or if `args` configures a field that doesn't exist.
+pub fn builder_for_tokens(args: &BuilderForArgs, foreign: &ItemStruct) -> Result<TokenStream2> {
+    let source = &args.source;
+    let source_str = source.to_token_stream().to_string().replace(' ', "");
+    let Fields::Named(named) = &foreign.fields else {
+        return Err(Error::new(
+            source.span(),
+            format!(
+                "`{}` doesn't have named fields, so a builder can't be generated for it",
+                source_str
+            ),
+        ));
+    };
+    let mut errors: Option<Error> = None;
+    let mut push_error = |err: Error| match &mut errors {
+        Some(errors) => errors.combine(err),
+        None => errors = Some(err),
+    };
+    for field in &named.named {
+        // restricted fields (i.e. `pub(crate)`) can't be accessed from other crates either
+        let visibility = match &field.vis {
+            Visibility::Public(_) => continue,
+            Visibility::Inherited => "private".to_string(),
+            Visibility::Restricted(restricted) => format!(
+                "`pub({}{})`",
+                if restricted.in_token.is_some() {
+                    "in "
+                } else {
+                    ""
+                },
+                restricted
+                    .path
+                    .to_token_stream()
+                    .to_string()
+                    .replace(' ', "")
+            ),
+        };
+        push_error(Error::new(
+            source.span(),
+            format!(
+                "field `{}` of `{}` is {}, so it can't be set by a builder",
+                field
+                    .ident
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                source_str,
+                visibility
+            ),
+        ));
+    }
+    for config in &args.fields {
+        if !named
+            .named
+            .iter()
+            .any(|field| field.ident.as_ref() == Some(&config.ident))
+        {
+            push_error(Error::new(
+                config.ident.span(),
+                format!("`{}` has no field named `{}`", source_str, config.ident),
+            ));
+        }
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+    let builder_ident = args
+        .ident
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}Builder", foreign.ident));
+    let error_ident = format_ident!("{}Error", builder_ident);
+    let generics = &foreign.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let foreign_ty = quote!(#source #ty_generics);
+
+    let mut builder_fields = Vec::new();
+    let mut setters = Vec::new();
+    let mut required = Vec::new();
+    let mut required_bindings = Vec::new();
+    let mut required_names = Vec::new();
+    let mut defaulted = Vec::new();
+    let mut default_exprs = Vec::new();
+    for (i, field) in named.named.iter().enumerate() {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        let ty = &field.ty;
+        let doc = format!("Sets the `{}` field.", ident);
+        builder_fields.push(quote!(#ident: ::core::option::Option<#ty>));
+        setters.push(quote! {
+            #[doc = #doc]
+            pub fn #ident(mut self, value: #ty) -> Self {
+                self.#ident = ::core::option::Option::Some(value);
+                self
+            }
+        });
+        match args.fields.iter().find(|config| &config.ident == ident) {
+            Some(config) => {
+                defaulted.push(ident);
+                default_exprs.push(config.default_expr()?);
+            }
+            None => {
+                required.push(ident);
+                required_bindings.push(format_ident!("__field{}", i));
+                required_names.push(ident.to_string());
+            }
+        }
+    }
+    let value = quote! {
+        #source {
+            #(#required: #required_bindings,)*
+            #(#defaulted: self.#defaulted.unwrap_or_else(|| #default_exprs),)*
+        }
+    };
+    let build_body = match required.is_empty() {
+        true => quote!(::core::result::Result::Ok(#value)),
+        false => quote! {
+            match (#(self.#required,)*) {
+                (#(::core::option::Option::Some(#required_bindings),)*) => {
+                    ::core::result::Result::Ok(#value)
+                }
+                (#(#required_bindings,)*) => {
+                    let mut missing = ::std::vec::Vec::new();
+                    #(
+                        if #required_bindings.is_none() {
+                            missing.push(#required_names);
+                        }
+                    )*
+                    ::core::result::Result::Err(#error_ident { missing })
+                }
+            }
+        },
+    };
+    let builder_doc = format!(
+        "A builder for `{}` generated by `builder_for!`.",
+        source_str
+    );
+    let error_doc = format!(
+        "The error returned by [`{}::build`] when required fields are missing.",
+        builder_ident
+    );
+    let field_idents: Vec<&Ident> = named
+        .named
+        .iter()
+        .filter_map(|f| f.ident.as_ref())
+        .collect();
+    Ok(quote! {
+        #[doc = #error_doc]
+        #[derive(::core::fmt::Debug, ::core::clone::Clone, ::core::cmp::PartialEq)]
+        pub struct #error_ident {
+            /// The names of the required fields that were not set
+            pub missing: ::std::vec::Vec<&'static str>,
+        }
+
+        impl ::core::fmt::Display for #error_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::write!(f, "missing required fields: {}", self.missing.join(", "))
+            }
+        }
+
+        impl ::std::error::Error for #error_ident {}
+
+        #[doc = #builder_doc]
+        pub struct #builder_ident #generics #where_clause {
+            #(#builder_fields,)*
+        }
+
+        impl #impl_generics ::core::default::Default for #builder_ident #ty_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#field_idents: ::core::option::Option::None,)*
+                }
+            }
+        }
+
+        impl #impl_generics #builder_ident #ty_generics #where_clause {
+            /// Creates a builder with none of the fields set.
+            pub fn new() -> Self {
+                ::core::default::Default::default()
+            }
+
+            #(#setters)*
+
+            /// Builds the value, returning an error naming the required fields that were not
+            /// set. Fields that were not set but have a default use their default.
+            pub fn build(self) -> ::core::result::Result<#foreign_ty, #error_ident> {
+                #build_body
+            }
+        }
+    })
+}
+
+/// Internal implementation of the `builder_for!` macro.
+///
+/// When invoked by the user, `tokens` are parsed as [`BuilderForArgs`] and the tokens of the
+/// `#[export_tokens]` struct are forwarded back to `builder_for!` along with these args. When
+/// invoked with forwarded tokens, the builder is generated via [`builder_for_tokens`].
+pub fn builder_for_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output = match parse_forwarded_proc(&tokens) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<BuilderForArgs>(forwarded.args)?;
+            let Item::Struct(foreign) = forwarded.item else {
+                return Err(Error::new(
+                    args.source.span(),
+                    format!(
+                        "`{}` is not a struct, so a builder can't be generated for it",
+                        args.source.to_token_stream()
+                    ),
+                ));
+            };
+            builder_for_tokens(&args, &foreign)?
+        }
+        None => {
+            let args = parse2::<BuilderForArgs>(tokens.clone())?;
+            let mm_path = macro_magic_root();
+            let source = &args.source;
+            quote! {
+                #mm_path::forward_tokens! {
+                    #source,
+                    #mm_path::__private::builder_for,
+                    #mm_path,
+                    args = { #args }
+                }
+            }
+        }
+    };
+    trace_stage("builder_for", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn config() -> ItemStruct {
+        parse_quote! {
+            pub struct Config<T> {
+                pub host: String,
+                pub port: u16,
+                pub extra: T,
+            }
+        }
+    }
+
+    #[test]
+    fn builder_for_required_and_defaulted() {
+        let output = builder_for_tokens(
+            &parse_quote!(upstream::Config {
+                #[default(8080)]
+                port,
+                #[default]
+                extra
+            }),
+            &config(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains(
+            "pub struct ConfigBuilder < T > { host : :: core :: option :: Option < String > ,"
+        ));
+        assert!(output.contains("pub fn port (mut self , value : u16) -> Self"));
+        assert!(output.contains(
+            "match (self . host ,) { (:: core :: option :: Option :: Some (__field0) ,) =>"
+        ));
+        assert!(output.contains("port : self . port . unwrap_or_else (|| 8080)"));
+        assert!(output.contains(
+            "extra : self . extra . unwrap_or_else (|| :: core :: default :: Default :: default ())"
+        ));
+        assert!(output.contains("missing . push (\"host\")"));
+        assert!(output.contains(
+            ":: core :: result :: Result < upstream :: Config < T > , ConfigBuilderError >"
+        ));
+    }
+
+    #[test]
+    fn builder_for_all_defaulted() {
+        let output = builder_for_tokens(
+            &parse_quote!(upstream::Config as Cfg { #[default] host, #[default] port, #[default] extra }),
+            &config(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("pub struct CfgError"));
+        assert!(!output.contains("match"));
+    }
+
+    #[test]
+    fn builder_for_errors() {
+        let Err(err) = builder_for_tokens(
+            &parse_quote!(upstream::Secret {
+                #[default]
+                missing
+            }),
+            &parse_quote! {
+                pub struct Secret {
+                    key: String,
+                    pub name: String,
+                    pub(crate) token: String,
+                    pub(in crate::auth) salt: String,
+                }
+            },
+        ) else {
+            panic!("expected an error");
+        };
+        let messages: Vec<String> = err.into_iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "field `key` of `upstream::Secret` is private, so it can't be set by a builder",
+                "field `token` of `upstream::Secret` is `pub(crate)`, so it can't be set by a \
+                builder",
+                "field `salt` of `upstream::Secret` is `pub(in crate::auth)`, so it can't be \
+                set by a builder",
+                "`upstream::Secret` has no field named `missing`"
+            ]
+        );
+        assert!(builder_for_tokens(
+            &parse_quote!(upstream::Pair),
+            &parse_quote!(
+                pub struct Pair(pub u8, pub u8);
+            )
+        )
+        .is_err());
+        assert!(parse2::<BuilderForArgs>(quote!(a::B {
+            #[other]
+            c
+        }))
+        .is_err());
+        assert!(parse2::<BuilderForArgs>(quote!(a::B {
+            #[default = 1]
+            c
+        }))
+        .is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! Contains helpers shared by the attribute macros that are built into `macro_magic` (such as
+//! `#[mixin]`), which follow the same forwarding protocol as macros defined using
+//! `#[import_tokens_attr]`, but can't be defined using it because they live in the same crate.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse2, Path, Result};
+
+use crate::{
+    keywords, macro_magic_root, starts_with_keyword, AttrItemWithExtra, ForwardedItemWithArgs,
+};
+
+/// Returns the tokens that forward the tokens of the `#[export_tokens]` item at `source` to
+/// the built-in attribute macro `macro_ident` (re-exported under `macro_magic::__private`),
+/// along with the tokens of the item the attribute is `attached` to and some `custom` tokens
+/// that are made available as [`AttrItemWithExtra::custom_tokens`] in the forwarded
+/// invocation.
+///
+/// This mirrors what the outer half of an `#[import_tokens_attr]` macro does.
+pub fn forward_to_builtin_attr(
+    macro_ident: &str,
+    source: &Path,
+    attached: &TokenStream2,
+    custom: &TokenStream2,
+) -> TokenStream2 {
+    let mm_path = macro_magic_root();
+    let macro_ident = format_ident!("{}", macro_ident);
+    quote! {
+        #mm_path::forward_tokens! {
+            #source,
+            #mm_path::__private::#macro_ident,
+            #mm_path,
+            {
+                { #attached },
+                { #source },
+                { #custom }
+            }
+        }
+    }
+}
+
+/// Parses the `attr` tokens of a built-in attribute macro that was invoked via
+/// [`forward_to_builtin_attr`], returning [`None`] if the tokens were not forwarded (i.e. this
+/// is the initial invocation written by the user).
+pub fn parse_forwarded_attr(attr: &TokenStream2) -> Option<Result<AttrItemWithExtra>> {
+    if !starts_with_keyword::<keywords::__private_macro_magic_tokens_forwarded>(attr) {
+        return None;
+    }
+    let tokens = attr.clone().into_iter().skip(1).collect::<TokenStream2>();
+    Some(parse2::<AttrItemWithExtra>(tokens))
+}
+
+/// Parses the tokens of a built-in function-like macro (such as `mirror_type!`) that were
+/// forwarded back to it via `forward_tokens!`, returning [`None`] if the tokens were not
+/// forwarded (i.e. this is the initial invocation written by the user).
+pub fn parse_forwarded_proc(tokens: &TokenStream2) -> Option<Result<ForwardedItemWithArgs>> {
+    if !starts_with_keyword::<keywords::__private_macro_magic_tokens_forwarded>(tokens) {
+        return None;
+    }
+    let tokens = tokens.clone().into_iter().skip(1).collect::<TokenStream2>();
+    Some(parse2::<ForwardedItemWithArgs>(tokens))
+}
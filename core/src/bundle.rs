@@ -0,0 +1,428 @@
+//! Contains the implementation behind `#[export_tokens(with_deps(..))]` and `forward_bundle!`,
+//! which import an exported item along with the closure of the exported items it depends on.
+
+use std::collections::VecDeque;
+
+use proc_macro2::{Punct, Spacing, Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    parse2,
+    token::Comma,
+    visit::{self, Visit},
+    Error, GenericParam, Generics, Ident, Item, Path, PathArguments, Result, Token, TypePath,
+};
+
+use crate::{
+    keywords, macro_magic_root, trace_stage, ForwardTokensExtraArg, ForwardedPipeline,
+    PipelineState, PipelineStep, DEFAULT_PIPELINE_MAX_DEPTH,
+};
+
+/// Type names that are never suggested as dependencies by [`referenced_type_paths`].
+const IGNORED_TYPE_NAMES: &[&str] = &[
+    "bool",
+    "char",
+    "str",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "f32",
+    "f64",
+    "Option",
+    "Result",
+    "Vec",
+    "String",
+    "Box",
+    "Rc",
+    "Arc",
+    "Cell",
+    "RefCell",
+    "Mutex",
+    "RwLock",
+    "Cow",
+    "PhantomData",
+    "HashMap",
+    "HashSet",
+    "BTreeMap",
+    "BTreeSet",
+    "VecDeque",
+];
+
+/// Crate names whose paths are never suggested as dependencies by [`referenced_type_paths`].
+const IGNORED_CRATE_NAMES: &[&str] = &["std", "core", "alloc"];
+
+/// Collects the type paths referenced by an item.
+struct TypePathCollector {
+    generic_params: Vec<Ident>,
+    paths: Vec<Path>,
+}
+
+impl<'ast> Visit<'ast> for TypePathCollector {
+    fn visit_type_path(&mut self, node: &'ast TypePath) {
+        visit::visit_type_path(self, node);
+        if node.qself.is_some() {
+            return;
+        }
+        let mut path = node.path.clone();
+        for segment in path.segments.iter_mut() {
+            segment.arguments = PathArguments::None;
+        }
+        let first = path.segments[0].ident.to_string();
+        // generic params and `Self` are skipped along with their associated types, i.e.
+        // `T::Output` or `Self::Item`
+        if path.leading_colon.is_none()
+            && (first == "Self"
+                || self.generic_params.iter().any(|param| param == &first)
+                || IGNORED_CRATE_NAMES.contains(&first.as_str())
+                || (path.segments.len() == 1 && IGNORED_TYPE_NAMES.contains(&first.as_str())))
+        {
+            return;
+        }
+        let path_str = path.to_token_stream().to_string();
+        if !self
+            .paths
+            .iter()
+            .any(|existing| existing.to_token_stream().to_string() == path_str)
+        {
+            self.paths.push(path);
+        }
+    }
+}
+
+/// Returns the paths of all the (non-primitive, non-`std`) types referenced by the specified
+/// item, in order of appearance and without generic arguments. Generic parameters of the item
+/// itself and `Self` (including their associated types) are excluded, and for functions only
+/// the signature is considered.
+///
+/// There is no way to tell whether a path refers to another `#[export_tokens]` item, so the
+/// dependencies recorded by a bare `#[export_tokens(with_deps)]` are only a best-effort list.
+pub fn referenced_type_paths(item: &Item) -> Vec<Path> {
+    let generics: Option<&Generics> = match item {
+        Item::Enum(item_enum) => Some(&item_enum.generics),
+        Item::Fn(item_fn) => Some(&item_fn.sig.generics),
+        Item::Impl(item_impl) => Some(&item_impl.generics),
+        Item::Struct(item_struct) => Some(&item_struct.generics),
+        Item::Trait(item_trait) => Some(&item_trait.generics),
+        Item::Type(item_type) => Some(&item_type.generics),
+        Item::Union(item_union) => Some(&item_union.generics),
+        _ => None,
+    };
+    let generic_params = generics
+        .map(|generics| {
+            generics
+                .params
+                .iter()
+                .filter_map(|param| match param {
+                    GenericParam::Type(type_param) => Some(type_param.ident.clone()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut collector = TypePathCollector {
+        generic_params,
+        paths: Vec::new(),
+    };
+    match item {
+        Item::Fn(item_fn) => collector.visit_signature(&item_fn.sig),
+        item => collector.visit_item(item),
+    }
+    collector.paths
+}
+
+/// Converts a dependency path into the tokens recorded in an `#[export_tokens]` macro,
+/// replacing a leading `crate` with `$crate` so the path remains valid in other crates.
+pub(crate) fn dep_path_tokens(dep: &Path) -> TokenStream2 {
+    let mut segments = dep.segments.iter();
+    match dep.segments.first() {
+        Some(first) if dep.leading_colon.is_none() && first.ident == "crate" => {
+            segments.next();
+            let dollar = Punct::new('$', Spacing::Alone);
+            quote!(#dollar crate #(::#segments)*)
+        }
+        _ => dep.to_token_stream(),
+    }
+}
+
+/// Resolves the path of a dependency recorded by the `#[export_tokens]` item at `source`.
+///
+/// Absolute paths (including `$crate::` paths) are returned as-is, while relative paths are
+/// resolved relative to the parent module of `source`, taking leading `self` and `super`
+/// segments into account.
+pub fn resolve_dep_path(source: &Path, dep: &Path) -> Path {
+    if dep.leading_colon.is_some() {
+        return dep.clone();
+    }
+    let first = dep.segments[0].ident.to_string();
+    if first == "$crate" || first == "crate" {
+        return dep.clone();
+    }
+    let mut resolved = source.clone();
+    resolved.segments.pop();
+    let mut segments = dep.segments.iter().peekable();
+    while let Some(segment) = segments.peek() {
+        if segment.ident == "self" {
+            segments.next();
+        } else if segment.ident == "super" && !resolved.segments.is_empty() {
+            resolved.segments.pop();
+            segments.next();
+        } else {
+            break;
+        }
+    }
+    for segment in segments {
+        resolved.segments.push(segment.clone());
+    }
+    // `pop` leaves a trailing separator behind, so rebuild the punctuation
+    resolved.segments = resolved.segments.into_iter().collect();
+    resolved
+}
+
+/// Parses the payload produced by the dependency arm of an `#[export_tokens]` macro, i.e.
+/// `__private_macro_magic_deps { item } { dep1, dep2 } { extra }`.
+pub(crate) struct DepsForwardPayload {
+    pub(crate) item: Item,
+    pub(crate) deps: Vec<Path>,
+    pub(crate) extra: TokenStream2,
+}
+
+impl Parse for DepsForwardPayload {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<keywords::__private_macro_magic_deps>()?;
+        let content;
+        braced!(content in input);
+        let item = content.parse()?;
+        let content;
+        braced!(content in input);
+        let deps = content
+            .parse_terminated(Path::parse, Comma)?
+            .into_iter()
+            .collect();
+        let extra = input.parse::<ForwardTokensExtraArg>()?.stream;
+        Ok(DepsForwardPayload { item, deps, extra })
+    }
+}
+
+/// Parses the args of `forward_bundle!`, i.e. `source, target` optionally followed by
+/// `args = { .. }` and/or `max_depth = N`.
+///
+/// You shouldn't need to use this directly.
+pub struct ForwardBundleArgs {
+    /// The path of the root `#[export_tokens]` item
+    pub source: Path,
+    /// The path of the macro that will receive the [`ImportedBundle`]
+    pub target: Path,
+    /// User-supplied args that will be passed along to the target
+    pub args: TokenStream2,
+    /// The maximum number of hops that may be performed while resolving the bundle
+    pub max_depth: usize,
+}
+
+impl Parse for ForwardBundleArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse()?;
+        input.parse::<Comma>()?;
+        let target = input.parse()?;
+        let mut args = TokenStream2::new();
+        let mut max_depth = DEFAULT_PIPELINE_MAX_DEPTH;
+        while input.parse::<Option<Comma>>()?.is_some() && !input.is_empty() {
+            let ident = input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+            if ident == "args" {
+                args = input.parse::<ForwardTokensExtraArg>()?.stream;
+            } else if ident == "max_depth" {
+                max_depth = input.parse::<syn::LitInt>()?.base10_parse()?;
+            } else {
+                return Err(Error::new(
+                    ident.span(),
+                    "expected `args = { .. }` or `max_depth = N`",
+                ));
+            }
+        }
+        Ok(ForwardBundleArgs {
+            source,
+            target,
+            args,
+            max_depth,
+        })
+    }
+}
+
+/// The input received by the target of a `forward_bundle!`, containing the root item along
+/// with the full closure of the `#[export_tokens]` items it depends on.
+///
+/// ## Example
+///
+/// ```ignore
+/// #[proc_macro]
+/// pub fn my_macro(tokens: TokenStream) -> TokenStream {
+///     let bundle = parse_macro_input!(tokens as ImportedBundle);
+///     for (path, dep) in bundle.deps {
+///         // ...
+///     }
+///     // ...
+/// }
+/// ```
+pub struct ImportedBundle {
+    /// The item that was passed to `forward_bundle!`
+    pub root: Item,
+    /// The transitive dependencies of the root item, along with the (resolved) paths they were
+    /// imported from, in the order they were resolved. Each dependency appears only once.
+    pub deps: Vec<(Path, Item)>,
+    /// The user-supplied args that were passed via `args = { .. }`, or an empty
+    /// [`TokenStream2`] if none were specified
+    pub args: TokenStream2,
+}
+
+impl Parse for ImportedBundle {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pipeline = input.parse::<ForwardedPipeline>()?;
+        let mut items = pipeline.items.into_iter();
+        let Some((_, root)) = items.next() else {
+            return Err(Error::new(
+                Span::call_site(),
+                "bundle is missing its root item",
+            ));
+        };
+        Ok(ImportedBundle {
+            root,
+            deps: items.collect(),
+            args: pipeline.args,
+        })
+    }
+}
+
+/// Internal implementation of `forward_bundle!`.
+pub fn forward_bundle_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let args = parse2::<ForwardBundleArgs>(tokens.clone())?;
+    let output = PipelineState {
+        depth: 0,
+        max_depth: args.max_depth,
+        visited: Vec::new(),
+        items: Vec::new(),
+        steps: VecDeque::from([
+            PipelineStep::ImportDeps(args.source.clone()),
+            PipelineStep::Stage(args.target.clone()),
+        ]),
+        args: args.args,
+        mm_path: macro_magic_root(),
+    }
+    .run()?;
+    trace_stage(
+        "forward_bundle",
+        Some(&args.source),
+        Some(&args.target),
+        &tokens,
+        &output,
+    );
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn path_strings(paths: &[Path]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.to_token_stream().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn referenced_type_paths_struct() {
+        let item: Item = parse_quote! {
+            struct Config<T> {
+                timeout: Timeout,
+                retry: Option<retry::Retry<T>>,
+                name: String,
+                map: std::collections::HashMap<u32, Timeout>,
+                generic: T,
+            }
+        };
+        assert_eq!(
+            path_strings(&referenced_type_paths(&item)),
+            ["Timeout", "retry :: Retry"]
+        );
+    }
+
+    #[test]
+    fn referenced_type_paths_fn_signature_only() {
+        let item: Item = parse_quote! {
+            fn make(timeout: Timeout) -> Config {
+                let _x: Unrelated = Unrelated;
+                Config { timeout }
+            }
+        };
+        assert_eq!(
+            path_strings(&referenced_type_paths(&item)),
+            ["Timeout", "Config"]
+        );
+    }
+
+    #[test]
+    fn referenced_type_paths_skip_assoc_and_std_types() {
+        let item: Item = parse_quote! {
+            impl<T: Transform, I: Iterator> Transform for Chain<T, I> {
+                type Output = T::Output;
+                fn apply(&self, item: I::Item, timeout: std::time::Duration) -> Self::Output {
+                    todo!()
+                }
+            }
+        };
+        assert_eq!(path_strings(&referenced_type_paths(&item)), ["Chain"]);
+    }
+
+    #[test]
+    fn resolve_dep_paths() {
+        let source: Path = parse_quote!(external_crate::config::Config);
+        let resolve = |dep: Path| {
+            resolve_dep_path(&source, &dep)
+                .to_token_stream()
+                .to_string()
+        };
+        assert_eq!(
+            resolve(parse_quote!(Timeout)),
+            "external_crate :: config :: Timeout"
+        );
+        assert_eq!(
+            resolve(parse_quote!(self::inner::Retry)),
+            "external_crate :: config :: inner :: Retry"
+        );
+        assert_eq!(
+            resolve(parse_quote!(super::Retry)),
+            "external_crate :: Retry"
+        );
+        assert_eq!(resolve(parse_quote!(::other::Retry)), ":: other :: Retry");
+        assert_eq!(
+            resolve_dep_path(&parse_quote!(Config), &parse_quote!(Timeout))
+                .to_token_stream()
+                .to_string(),
+            "Timeout"
+        );
+    }
+
+    #[test]
+    fn dep_paths_use_dollar_crate() {
+        assert_eq!(
+            dep_path_tokens(&parse_quote!(crate::a::Timeout)).to_string(),
+            "$ crate :: a :: Timeout"
+        );
+        assert_eq!(
+            dep_path_tokens(&parse_quote!(a::Timeout)).to_string(),
+            "a :: Timeout"
+        );
+    }
+}
@@ -0,0 +1,477 @@
+//! Contains the implementation behind the `#[delegate]` attribute macro.
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2,
+    spanned::Spanned,
+    visit::{self, Visit},
+    visit_mut::{self, VisitMut},
+    Error, FnArg, GenericArgument, Ident, ImplItem, ItemImpl, ItemTrait, Member, Pat, PatIdent,
+    Path, PathArguments, Result, Signature, Token, TraitItem, Type, TypePath,
+};
+
+use crate::{forward_to_builtin_attr, keywords, parse_forwarded_attr, trace_stage};
+
+/// Used to parse the args of [`delegate_internal`], i.e.
+/// `field = inner, to = upstream::Storage` or `field = 0, ty = MemStore, to = upstream::Storage`.
+///
+/// You shouldn't need to use this directly.
+pub struct DelegateArgs {
+    /// The field of `Self` that method calls are delegated to
+    pub field: Member,
+    /// The type of [`DelegateArgs::field`], if specified via `ty = ..`. This is required for
+    /// delegating associated types and consts.
+    pub ty: Option<Type>,
+    /// The path of the `#[export_tokens]` trait whose methods are delegated
+    pub to: Path,
+}
+
+impl Parse for DelegateArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut field = None;
+        let mut ty = None;
+        let mut to = None;
+        while !input.is_empty() {
+            let lookahead = input.lookahead1();
+            if lookahead.peek(keywords::field) {
+                input.parse::<keywords::field>()?;
+                input.parse::<Token![=]>()?;
+                field = Some(input.parse::<Member>()?);
+            } else if lookahead.peek(keywords::ty) {
+                input.parse::<keywords::ty>()?;
+                input.parse::<Token![=]>()?;
+                ty = Some(input.parse::<Type>()?);
+            } else if lookahead.peek(keywords::to) {
+                input.parse::<keywords::to>()?;
+                input.parse::<Token![=]>()?;
+                to = Some(input.parse::<Path>()?);
+            } else {
+                return Err(lookahead.error());
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        let (Some(field), Some(to)) = (field, to) else {
+            return Err(input.error(
+                "expected `field = ..` and `to = ..`, i.e. \
+                `#[delegate(field = inner, to = upstream::Storage)]`",
+            ));
+        };
+        Ok(DelegateArgs { field, ty, to })
+    }
+}
+
+impl ToTokens for DelegateArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let field = &self.field;
+        let to = &self.to;
+        tokens.extend(quote!(field = #field, ));
+        if let Some(ty) = &self.ty {
+            tokens.extend(quote!(ty = #ty, ));
+        }
+        tokens.extend(quote!(to = #to));
+    }
+}
+
+/// Finds bare `Self` types, which can't be forwarded to the delegate since it has a different
+/// type than `Self`.
+#[derive(Default)]
+struct SelfTypeFinder {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for SelfTypeFinder {
+    fn visit_type_path(&mut self, type_path: &'ast TypePath) {
+        if type_path.qself.is_none() && type_path.path.is_ident("Self") {
+            self.found = true;
+        }
+        visit::visit_type_path(self, type_path);
+    }
+}
+
+/// Substitutes the generic type params of a trait with the generic args of an impl of it.
+struct TraitParamReplacer {
+    params: HashMap<Ident, Type>,
+}
+
+impl VisitMut for TraitParamReplacer {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(type_path) = ty {
+            if let Some(replacement) = type_path
+                .path
+                .get_ident()
+                .filter(|_| type_path.qself.is_none())
+                .and_then(|ident| self.params.get(ident))
+            {
+                *ty = replacement.clone();
+                return;
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+}
+
+/// Returns the expression form of `path`, i.e. `Storage::<u32>` for `Storage<u32>`.
+fn expr_path(path: &Path) -> Path {
+    let mut path = path.clone();
+    for segment in path.segments.iter_mut() {
+        if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+            args.colon2_token = Some(Default::default());
+        }
+    }
+    path
+}
+
+/// Returns a forwarding implementation of the trait method `sig`, which calls the same method
+/// on `field` via `callee` (i.e. `Storage::get` or `<MemStore as Storage>::get`).
+///
+/// Returns an error if the method has no `self`, `&self` or `&mut self` receiver, or if its
+/// signature refers to `Self` outside of its receiver.
+fn delegate_fn(sig: &Signature, field: &Member, callee: &TokenStream2) -> Result<TokenStream2> {
+    let mut sig = sig.clone();
+    let ident = sig.ident.clone();
+    let mut finder = SelfTypeFinder::default();
+    for input in sig.inputs.iter().skip(1) {
+        finder.visit_fn_arg(input);
+    }
+    finder.visit_return_type(&sig.output);
+    if finder.found {
+        return Err(Error::new(
+            ident.span(),
+            format!(
+                "cannot delegate `{}` because its signature refers to `Self`, so it must be \
+                implemented by hand",
+                ident
+            ),
+        ));
+    }
+    let receiver = match sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) if receiver.colon_token.is_none() => {
+            match (&receiver.reference, &receiver.mutability) {
+                (Some(_), Some(_)) => quote!(&mut self.#field),
+                (Some(_), None) => quote!(&self.#field),
+                (None, _) => quote!(self.#field),
+            }
+        }
+        _ => {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "cannot delegate `{}` because it doesn't take `self`, `&self` or \
+                    `&mut self`, so it must be implemented by hand",
+                    ident
+                ),
+            ))
+        }
+    };
+    let mut args = Vec::new();
+    for (i, input) in sig.inputs.iter_mut().skip(1).enumerate() {
+        let FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let arg = format_ident!("__arg{}", i);
+        *pat_type.pat = Pat::Ident(PatIdent {
+            attrs: Vec::new(),
+            by_ref: None,
+            mutability: None,
+            ident: arg.clone(),
+            subpat: None,
+        });
+        args.push(arg);
+    }
+    let call = quote!(#callee::#ident(#receiver #(, #args)*));
+    let body = match sig.asyncness {
+        Some(_) => quote!(#call.await),
+        None => call,
+    };
+    Ok(quote! {
+        #sig {
+            #body
+        }
+    })
+}
+
+/// Adds a forwarding implementation of every method (and, when the type of the field is
+/// specified, every associated type and const) of the `foreign` trait (imported from
+/// `args.to`) that `local` does not already define to `local`.
+///
+/// Returns an error if `local` isn't an impl of the trait, or for each missing item that
+/// can't be delegated.
+pub fn delegate_impl_items(
+    mut local: ItemImpl,
+    foreign: &ItemTrait,
+    args: &DelegateArgs,
+) -> Result<ItemImpl> {
+    let source = &args.to;
+    let Some((_, trait_path, _)) = &local.trait_ else {
+        return Err(Error::new(
+            local.self_ty.span(),
+            "`#[delegate]` can only be attached to a trait impl, i.e. `impl Storage for Wrapper`",
+        ));
+    };
+    let Some(trait_segment) = trait_path.segments.last() else {
+        return Err(Error::new(trait_path.span(), "expected a trait path"));
+    };
+    if trait_segment.ident != foreign.ident {
+        return Err(Error::new(
+            trait_path.span(),
+            format!(
+                "`{}` is an impl of `{}`, but `{}` is the trait `{}`",
+                local.self_ty.to_token_stream(),
+                trait_path.to_token_stream(),
+                source.to_token_stream(),
+                foreign.ident
+            ),
+        ));
+    }
+    let mut replacer = TraitParamReplacer {
+        params: HashMap::new(),
+    };
+    if let PathArguments::AngleBracketed(generic_args) = &trait_segment.arguments {
+        let type_args = generic_args.args.iter().filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        });
+        replacer.params = foreign
+            .generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .zip(type_args)
+            .collect();
+    }
+    let callee = match &args.ty {
+        Some(ty) => quote!(<#ty as #trait_path>),
+        None => expr_path(trait_path).to_token_stream(),
+    };
+    let field = &args.field;
+    let defined = |ident: &Ident| {
+        local.items.iter().any(|item| match item {
+            ImplItem::Const(item_const) => &item_const.ident == ident,
+            ImplItem::Fn(item_fn) => &item_fn.sig.ident == ident,
+            ImplItem::Type(item_type) => &item_type.ident == ident,
+            _ => false,
+        })
+    };
+    let mut delegated = Vec::new();
+    let mut errors: Option<Error> = None;
+    for item in &foreign.items {
+        let result = match item {
+            TraitItem::Fn(item_fn) if !defined(&item_fn.sig.ident) => {
+                let mut sig = item_fn.sig.clone();
+                replacer.visit_signature_mut(&mut sig);
+                delegate_fn(&sig, field, &callee)
+            }
+            TraitItem::Type(item_type) if !defined(&item_type.ident) => {
+                let ident = &item_type.ident;
+                let generics = &item_type.generics;
+                let (_, ty_generics, where_clause) = generics.split_for_impl();
+                match &args.ty {
+                    Some(ty) => Ok(quote! {
+                        type #ident #generics = <#ty as #trait_path>::#ident #ty_generics
+                        #where_clause;
+                    }),
+                    None => Err(Error::new(
+                        ident.span(),
+                        format!(
+                            "cannot delegate associated type `{}` without the type of the \
+                            field, so specify it via `ty = ..` or implement it by hand",
+                            ident
+                        ),
+                    )),
+                }
+            }
+            TraitItem::Const(item_const) if !defined(&item_const.ident) => {
+                let ident = &item_const.ident;
+                let mut const_ty = item_const.ty.clone();
+                replacer.visit_type_mut(&mut const_ty);
+                match &args.ty {
+                    Some(ty) => Ok(quote! {
+                        const #ident: #const_ty = <#ty as #trait_path>::#ident;
+                    }),
+                    None => Err(Error::new(
+                        ident.span(),
+                        format!(
+                            "cannot delegate associated const `{}` without the type of the \
+                            field, so specify it via `ty = ..` or implement it by hand",
+                            ident
+                        ),
+                    )),
+                }
+            }
+            _ => continue,
+        };
+        match result {
+            Ok(tokens) => delegated.push(parse2::<ImplItem>(tokens)?),
+            Err(err) => {
+                // point at the attribute, since the trait lives in another crate
+                let err = Error::new(source.span(), err.to_string());
+                match &mut errors {
+                    Some(errors) => errors.combine(err),
+                    None => errors = Some(err),
+                }
+            }
+        }
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+    local.items.extend(delegated);
+    Ok(local)
+}
+
+/// Internal implementation of the `#[delegate]` attribute macro.
+///
+/// When invoked by the user, `attr` is parsed as [`DelegateArgs`] and the tokens of the
+/// `#[export_tokens]` trait are forwarded back to `#[delegate]` along with these args. When
+/// invoked with forwarded tokens, forwarding implementations are added to the attached impl
+/// via [`delegate_impl_items`].
+pub fn delegate_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    let tokens = tokens.into();
+    let output = match parse_forwarded_attr(&attr) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<DelegateArgs>(forwarded.custom_tokens)?;
+            let Ok(foreign) = parse2::<ItemTrait>(forwarded.imported_item.to_token_stream()) else {
+                return Err(Error::new(
+                    args.to.span(),
+                    format!(
+                        "`{}` is not a trait, so its methods cannot be delegated",
+                        args.to.to_token_stream()
+                    ),
+                ));
+            };
+            let local = parse2::<ItemImpl>(forwarded.tokens_ident)?;
+            delegate_impl_items(local, &foreign, &args)?.to_token_stream()
+        }
+        None => {
+            let args = parse2::<DelegateArgs>(attr.clone())?;
+            let Ok(local) = parse2::<ItemImpl>(tokens.clone()) else {
+                return Err(Error::new(
+                    tokens.span(),
+                    "`#[delegate]` can only be attached to an impl block",
+                ));
+            };
+            forward_to_builtin_attr(
+                "delegate",
+                &args.to,
+                &local.to_token_stream(),
+                &args.to_token_stream(),
+            )
+        }
+    };
+    trace_stage("delegate", None, None, &quote!(#attr #tokens), &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn storage() -> ItemTrait {
+        parse_quote! {
+            pub trait Storage<K> {
+                type Value;
+                const NAME: &'static str;
+                fn get(&self, key: K) -> Option<&Self::Value>;
+                fn put<V: Into<Self::Value>>(&mut self, (a, b): (K, K), value: V);
+                async fn flush(self) -> bool { true }
+            }
+        }
+    }
+
+    #[test]
+    fn delegate_methods() {
+        let output = delegate_impl_items(
+            parse_quote! {
+                impl Storage<u32> for Wrapper {
+                    type Value = u8;
+                    const NAME: &'static str = "wrapper";
+                    fn put<V: Into<u8>>(&mut self, _: (u32, u32), _: V) {}
+                }
+            },
+            &storage(),
+            &parse_quote!(field = inner, to = upstream::Storage),
+        )
+        .unwrap()
+        .to_token_stream()
+        .to_string();
+        assert!(output.contains(
+            "fn get (& self , __arg0 : u32) -> Option < & Self :: Value > { Storage :: < u32 > :: \
+            get (& self . inner , __arg0) }"
+        ));
+        assert!(output.contains(
+            "async fn flush (self) -> bool { Storage :: < u32 > :: flush (self . inner) . await }"
+        ));
+        assert_eq!(output.matches("fn put").count(), 1);
+    }
+
+    #[test]
+    fn delegate_associated_items() {
+        let output = delegate_impl_items(
+            parse_quote!(impl Storage<u32> for Wrapper {}),
+            &storage(),
+            &parse_quote!(field = 0, ty = MemStore, to = upstream::Storage),
+        )
+        .unwrap()
+        .to_token_stream()
+        .to_string();
+        assert!(output.contains("type Value = < MemStore as Storage < u32 > > :: Value ;"));
+        assert!(output
+            .contains("const NAME : & 'static str = < MemStore as Storage < u32 > > :: NAME ;"));
+        assert!(output
+            .contains("< MemStore as Storage < u32 > > :: put (& mut self . 0 , __arg0 , __arg1)"));
+    }
+
+    #[test]
+    fn delegate_errors() {
+        let Err(err) = delegate_impl_items(
+            parse_quote!(impl Storage<u32> for Wrapper {}),
+            &storage(),
+            &parse_quote!(field = inner, to = upstream::Storage),
+        ) else {
+            panic!("expected an error");
+        };
+        assert_eq!(err.into_iter().count(), 2);
+        let Err(err) = delegate_impl_items(
+            parse_quote!(impl Wrapper {}),
+            &storage(),
+            &parse_quote!(field = inner, to = upstream::Storage),
+        ) else {
+            panic!("expected an error");
+        };
+        assert!(err.to_string().contains("trait impl"));
+        let Err(err) = delegate_impl_items(
+            parse_quote!(impl Cloner for Wrapper {}),
+            &parse_quote! {
+                trait Cloner {
+                    fn dup(&self) -> Self;
+                    fn new() -> u8;
+                }
+            },
+            &parse_quote!(field = inner, to = upstream::Cloner),
+        ) else {
+            panic!("expected an error");
+        };
+        let messages: Vec<String> = err.into_iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot delegate `dup` because its signature refers to `Self`, so it must be \
+                implemented by hand",
+                "cannot delegate `new` because it doesn't take `self`, `&self` or `&mut self`, \
+                so it must be implemented by hand"
+            ]
+        );
+        assert!(delegate_internal(quote!(field = inner), quote!(impl A for B {})).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+//! Contains the implementation behind the `#[doc_item]` attribute macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream, Parser},
+    parse2,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Attribute, Error, Fields, Ident, ImplItem, Item, Path, Result, Signature, Token, TraitItem,
+    Visibility,
+};
+
+use crate::{
+    forward_to_builtin_attr, impl_item_ident, keywords, parse_forwarded_attr, pretty_print,
+    pretty_type, trace_stage,
+};
+
+/// Limits which part of an item `#[doc_item]` renders.
+pub enum DocItemSelector {
+    /// The whole item, which is the default
+    All,
+    /// Only the signature of the item, specified via `signature`. For fns this is the
+    /// signature itself, for traits and impl blocks these are the signatures of their items and
+    /// for other items this is the definition without any attributes or doc comments.
+    Signature,
+    /// Only the specified fields, variants or associated items, specified via
+    /// `members(a, b)`.
+    Members(Vec<Ident>),
+}
+
+/// Used to parse the args of [`doc_item_internal`], i.e. `upstream::Thing`,
+/// `upstream::Thing, signature` or `upstream::Thing, members(a, b)`.
+///
+/// You shouldn't need to use this directly.
+pub struct DocItemArgs {
+    /// The path of the `#[export_tokens]` item that will be rendered
+    pub source: Path,
+    /// Which part of the item will be rendered
+    pub selector: DocItemSelector,
+}
+
+impl Parse for DocItemArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse::<Path>()?;
+        let mut selector = DocItemSelector::All;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let lookahead = input.lookahead1();
+            if lookahead.peek(keywords::signature) {
+                input.parse::<keywords::signature>()?;
+                selector = DocItemSelector::Signature;
+            } else if lookahead.peek(keywords::members) {
+                input.parse::<keywords::members>()?;
+                let content;
+                parenthesized!(content in input);
+                let members = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                selector = DocItemSelector::Members(members.into_iter().collect());
+            } else {
+                return Err(lookahead.error());
+            }
+        }
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after the selector"));
+        }
+        Ok(DocItemArgs { source, selector })
+    }
+}
+
+impl ToTokens for DocItemArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.source.to_tokens(tokens);
+        match &self.selector {
+            DocItemSelector::All => (),
+            DocItemSelector::Signature => tokens.extend(quote!(, signature)),
+            DocItemSelector::Members(members) => tokens.extend(quote!(, members(#(#members),*))),
+        }
+    }
+}
+
+/// Returns the pretty-printed form of `sig` (with visibility `vis`) followed by a `;`, which
+/// may span multiple lines.
+fn render_signature(vis: &Visibility, sig: &Signature) -> String {
+    let pretty = pretty_print(&quote!(#vis #sig {}));
+    format!("{};", pretty.trim_end().trim_end_matches("{}").trim_end())
+}
+
+/// Returns `true` if `attr` is a doc comment.
+fn is_doc(attr: &Attribute) -> bool {
+    attr.path().is_ident("doc")
+}
+
+/// Returns the ident that the specified member of an item is referred to by.
+fn trait_item_ident(item: &TraitItem) -> Option<&Ident> {
+    match item {
+        TraitItem::Const(item) => Some(&item.ident),
+        TraitItem::Fn(item) => Some(&item.sig.ident),
+        TraitItem::Type(item) => Some(&item.ident),
+        _ => None,
+    }
+}
+
+/// Only keeps the members of `item` named in `members`, returning an error naming each member
+/// that doesn't exist.
+fn select_members(item: &mut Item, members: &[Ident], source: &Path) -> Result<()> {
+    let mut found: Vec<&Ident> = Vec::new();
+    let mut keep =
+        |ident: Option<&Ident>| match ident.and_then(|i| members.iter().find(|m| *m == i)) {
+            Some(member) => {
+                found.push(member);
+                true
+            }
+            None => false,
+        };
+    match item {
+        Item::Struct(item) => {
+            if let Fields::Named(fields) = &mut item.fields {
+                fields.named = std::mem::take(&mut fields.named)
+                    .into_pairs()
+                    .filter(|pair| keep(pair.value().ident.as_ref()))
+                    .collect();
+            }
+        }
+        Item::Enum(item) => {
+            item.variants = std::mem::take(&mut item.variants)
+                .into_pairs()
+                .filter(|pair| keep(Some(&pair.value().ident)))
+                .collect();
+        }
+        Item::Trait(item) => item.items.retain(|item| keep(trait_item_ident(item))),
+        Item::Impl(item) => item.items.retain(|item| keep(impl_item_ident(item))),
+        _ => {
+            return Err(Error::new(
+                source.span(),
+                format!(
+                    "`{}` has no members, so `members(..)` can't be used",
+                    source.to_token_stream()
+                ),
+            ))
+        }
+    }
+    let mut errors: Option<Error> = None;
+    for member in members.iter().filter(|member| !found.contains(member)) {
+        let err = Error::new(
+            member.span(),
+            format!(
+                "`{}` has no member named `{}`",
+                source.to_token_stream(),
+                member
+            ),
+        );
+        match &mut errors {
+            Some(errors) => errors.combine(err),
+            None => errors = Some(err),
+        }
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+/// Strips the attributes and doc comments of `item` and its members, and the bodies of its
+/// methods where they can be removed, leaving only its signature.
+fn render_item_signature(mut item: Item) -> String {
+    match &mut item {
+        Item::Fn(item_fn) => return render_signature(&item_fn.vis, &item_fn.sig),
+        Item::Impl(item_impl) => {
+            let items = std::mem::take(&mut item_impl.items);
+            item_impl.attrs.clear();
+            let header = pretty_print(&item_impl.to_token_stream());
+            let header = header.trim_end().trim_end_matches("{}").trim_end();
+            let mut lines = Vec::new();
+            for impl_item in items {
+                let rendered = match impl_item {
+                    ImplItem::Fn(item_fn) => render_signature(&item_fn.vis, &item_fn.sig),
+                    ImplItem::Const(item_const) => {
+                        let (ident, ty) = (&item_const.ident, &item_const.ty);
+                        format!("const {}: {};", ident, pretty_type(&quote!(#ty)))
+                    }
+                    ImplItem::Type(item_type) => {
+                        let (ident, ty) = (&item_type.ident, &item_type.ty);
+                        format!("type {} = {};", ident, pretty_type(&quote!(#ty)))
+                    }
+                    _ => continue,
+                };
+                lines.extend(rendered.lines().map(|line| format!("    {}", line)));
+            }
+            return format!("{} {{\n{}\n}}", header, lines.join("\n"));
+        }
+        Item::Trait(item_trait) => {
+            item_trait.attrs.clear();
+            for trait_item in item_trait.items.iter_mut() {
+                match trait_item {
+                    TraitItem::Fn(item_fn) => {
+                        item_fn.attrs.clear();
+                        item_fn.default = None;
+                        item_fn.semi_token = Some(Default::default());
+                    }
+                    TraitItem::Const(item_const) => item_const.attrs.clear(),
+                    TraitItem::Type(item_type) => item_type.attrs.clear(),
+                    _ => (),
+                }
+            }
+        }
+        Item::Struct(item_struct) => {
+            item_struct.attrs.clear();
+            for field in item_struct.fields.iter_mut() {
+                field.attrs.clear();
+            }
+        }
+        Item::Enum(item_enum) => {
+            item_enum.attrs.clear();
+            for variant in item_enum.variants.iter_mut() {
+                variant.attrs.clear();
+                for field in variant.fields.iter_mut() {
+                    field.attrs.clear();
+                }
+            }
+        }
+        _ => (),
+    }
+    pretty_print(&item.to_token_stream()).trim_end().to_string()
+}
+
+/// Returns the pretty-printed source of the part of `item` (imported from `source`) that is
+/// selected by `selector`.
+pub fn doc_item_source(
+    mut item: Item,
+    selector: &DocItemSelector,
+    source: &Path,
+) -> Result<String> {
+    match selector {
+        DocItemSelector::All => Ok(pretty_print(&item.to_token_stream()).trim_end().to_string()),
+        DocItemSelector::Signature => Ok(render_item_signature(item)),
+        DocItemSelector::Members(members) => {
+            select_members(&mut item, members, source)?;
+            Ok(pretty_print(&item.to_token_stream()).trim_end().to_string())
+        }
+    }
+}
+
+/// Appends the rendered source of `foreign` to the doc comments of the item `local`, whose
+/// tokens start with its outer attributes, as a fenced code block.
+///
+/// The code block is marked `rust,ignore`, since the rendered source is not expected to
+/// compile as a doctest of the local crate.
+pub fn doc_item_tokens(
+    local: TokenStream2,
+    foreign: Item,
+    args: &DocItemArgs,
+) -> Result<TokenStream2> {
+    let rendered = doc_item_source(foreign, &args.selector, &args.source)?;
+    let code_block = format!("```rust,ignore\n{}\n```", rendered);
+    let (attrs, rest) = (|input: ParseStream| {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let rest = input.parse::<TokenStream2>()?;
+        Ok((attrs, rest))
+    })
+    .parse2(local)?;
+    let separator = match attrs.iter().any(is_doc) {
+        true => quote!(#[doc = ""]),
+        false => TokenStream2::new(),
+    };
+    Ok(quote! {
+        #(#attrs)*
+        #separator
+        #[doc = #code_block]
+        #rest
+    })
+}
+
+/// Internal implementation of the `#[doc_item]` attribute macro.
+///
+/// When invoked by the user, `attr` is parsed as [`DocItemArgs`] and the tokens of the
+/// `#[export_tokens]` item are forwarded back to `#[doc_item]` along with these args. When
+/// invoked with forwarded tokens, the rendered item is appended to the docs of the attached
+/// item via [`doc_item_tokens`].
+pub fn doc_item_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    let tokens = tokens.into();
+    let output = match parse_forwarded_attr(&attr) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<DocItemArgs>(forwarded.custom_tokens)?;
+            doc_item_tokens(forwarded.tokens_ident, forwarded.imported_item, &args)?
+        }
+        None => {
+            let args = parse2::<DocItemArgs>(attr.clone())?;
+            forward_to_builtin_attr("doc_item", &args.source, &tokens, &args.to_token_stream())
+        }
+    };
+    trace_stage("doc_item", None, None, &quote!(#attr #tokens), &output);
+    Ok(output)
+}
+
+#[cfg(all(test, feature = "pretty"))]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn render(item: Item, args: DocItemArgs) -> Result<String> {
+        doc_item_source(item, &args.selector, &args.source)
+    }
+
+    fn shape_impl() -> Item {
+        parse_quote! {
+            /// The shape of a square.
+            impl Shape for Square {
+                const SIDES: u32 = 4;
+                /// Returns the name.
+                fn name(&self) -> &'static str {
+                    "square"
+                }
+                fn double(&self) -> Square {
+                    Square(self.0 * 2)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn doc_item_all() {
+        let rendered = render(shape_impl(), parse_quote!(a::square_shape)).unwrap();
+        assert!(rendered.starts_with("/// The shape of a square.\nimpl Shape for Square {\n"));
+        assert!(rendered.contains("    fn double(&self) -> Square {\n        Square(self.0 * 2)\n"));
+    }
+
+    #[test]
+    fn doc_item_signature() {
+        let rendered = render(shape_impl(), parse_quote!(a::square_shape, signature)).unwrap();
+        assert_eq!(
+            rendered,
+            "impl Shape for Square {\n    const SIDES: u32;\n    fn name(&self) -> &'static str;\n    \
+            fn double(&self) -> Square;\n}"
+        );
+        let rendered = render(
+            parse_quote! {
+                /// Does things.
+                pub fn do_things<T: Copy>(value: T) -> T { value }
+            },
+            parse_quote!(a::do_things, signature),
+        )
+        .unwrap();
+        assert_eq!(rendered, "pub fn do_things<T: Copy>(value: T) -> T;");
+        let rendered = render(
+            parse_quote! {
+                /// A trait.
+                pub trait Repo {
+                    /// Finds a thing.
+                    fn find(&self) -> u32 { 0 }
+                }
+            },
+            parse_quote!(a::Repo, signature),
+        )
+        .unwrap();
+        assert_eq!(rendered, "pub trait Repo {\n    fn find(&self) -> u32;\n}");
+    }
+
+    #[test]
+    fn doc_item_members() {
+        let rendered = render(
+            parse_quote! {
+                pub struct Config {
+                    pub host: String,
+                    /// The port.
+                    pub port: u16,
+                    pub workers: u8,
+                }
+            },
+            parse_quote!(a::Config, members(port)),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "pub struct Config {\n    /// The port.\n    pub port: u16,\n}"
+        );
+        let Err(err) = render(
+            shape_impl(),
+            parse_quote!(a::square_shape, members(name, area)),
+        ) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "`a :: square_shape` has no member named `area`"
+        );
+        assert!(render(
+            parse_quote!(
+                fn f() {}
+            ),
+            parse_quote!(a::f, members(x))
+        )
+        .is_err());
+        assert!(parse2::<DocItemArgs>(quote!(a::b, other)).is_err());
+    }
+
+    #[test]
+    fn doc_item_appends_docs() {
+        let output = doc_item_tokens(
+            quote! {
+                /// Local docs.
+                #[derive(Debug)]
+                pub struct Local;
+            },
+            parse_quote!(
+                pub struct Foreign;
+            ),
+            &parse_quote!(a::Foreign),
+        )
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            output,
+            "# [doc = r\" Local docs.\"] # [derive (Debug)] # [doc = \"\"] # [doc = \
+            \"```rust,ignore\\npub struct Foreign;\\n```\"] pub struct Local ;"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Contains an offline explorer that lists the exported items and the
+//! `#[import_tokens_attr]` / `#[import_tokens_proc]` definitions of a crate, and flags common
+//! problems with them, using the same naming and parsing as the macros themselves.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use quote::{quote, ToTokens};
+use syn::{parse2, Attribute, Item, ItemFn, Path, Visibility};
+
+use crate::{
+    export_attr, export_ident, flatten_ident, item_attrs, item_kind, parse_proc_macro_variant,
+    pretty_print, scan_crate, CrateScan, OverridePath, ProcMacro, ProcMacroType, ScannedExport,
+    MACRO_MAGIC_ROOT,
+};
+
+/// An `#[import_tokens_attr]` or `#[import_tokens_proc]` definition found by
+/// [`explore_crate`].
+pub struct ScannedImportMacro {
+    /// The path of the module containing the definition, relative to the crate root
+    pub module: Vec<String>,
+    /// The parsed proc macro function, with [`ProcMacro::macro_type`] set to
+    /// [`ProcMacroType::Attribute`] for `#[import_tokens_attr]` and
+    /// [`ProcMacroType::Normal`] for `#[import_tokens_proc]`
+    pub proc_macro: ProcMacro,
+    /// The `macro_magic` path override passed to the attribute, i.e. `my_crate::macro_magic`,
+    /// or [`None`] if the default path is used
+    pub override_path: Option<String>,
+    /// The path of the struct passed to `#[with_custom_parsing(..)]`, if one was specified
+    pub custom_parsing: Option<Path>,
+    /// The file the definition is located in
+    pub file: PathBuf,
+}
+
+impl ScannedImportMacro {
+    /// Returns the name of the attribute the definition uses, i.e. `import_tokens_attr`.
+    pub fn attr_name(&self) -> &'static str {
+        match self.proc_macro.macro_type {
+            ProcMacroType::Attribute => "import_tokens_attr",
+            _ => "import_tokens_proc",
+        }
+    }
+
+    /// Returns the path of the defined macro, relative to the crate root.
+    pub fn path(&self) -> String {
+        let mut path = self.module.clone();
+        path.push(self.proc_macro.proc_fn.sig.ident.to_string());
+        path.join("::")
+    }
+}
+
+/// A problem found by [`explore_crate`].
+pub struct ExploreProblem {
+    /// The path of the item the problem concerns, relative to the crate root
+    pub path: String,
+    /// The file the item is located in
+    pub file: PathBuf,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl Display for ExploreProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.path,
+            self.message,
+            self.file.display()
+        )
+    }
+}
+
+/// The result of [`explore_crate`].
+pub struct CrateExploration {
+    /// The scanned crate, including every exported item
+    pub scan: CrateScan,
+    /// Every `#[import_tokens_attr]` and `#[import_tokens_proc]` definition of the crate
+    pub import_macros: Vec<ScannedImportMacro>,
+    /// Every problem that was found
+    pub problems: Vec<ExploreProblem>,
+}
+
+/// Returns the visibility of `item` as it is written in the source, i.e. `pub(crate)`, or
+/// [`None`] for items that have no visibility of their own (such as impl blocks).
+pub fn item_visibility(item: &Item) -> Option<String> {
+    let vis = match item {
+        Item::Const(item) => &item.vis,
+        Item::Enum(item) => &item.vis,
+        Item::ExternCrate(item) => &item.vis,
+        Item::Fn(item) => &item.vis,
+        Item::Mod(item) => &item.vis,
+        Item::Static(item) => &item.vis,
+        Item::Struct(item) => &item.vis,
+        Item::Trait(item) => &item.vis,
+        Item::TraitAlias(item) => &item.vis,
+        Item::Type(item) => &item.vis,
+        Item::Union(item) => &item.vis,
+        _ => return None,
+    };
+    Some(match vis {
+        Visibility::Inherited => String::from("private"),
+        vis => pretty_print(&quote!(#vis struct __S;))
+            .trim()
+            .trim_end_matches("struct __S;")
+            .trim()
+            .to_string(),
+    })
+}
+
+/// Renders an [`OverridePath`] the way it is written in the source.
+fn override_path_string(override_path: &OverridePath) -> String {
+    match override_path {
+        OverridePath::Path(path) => {
+            let segments: Vec<String> = path
+                .segments
+                .iter()
+                .map(|seg| seg.ident.to_string())
+                .collect();
+            let prefix = if path.leading_colon.is_some() {
+                "::"
+            } else {
+                ""
+            };
+            format!("{}{}", prefix, segments.join("::"))
+        }
+        OverridePath::Expr(expr) => pretty_print(&quote!(const __P: () = #expr;))
+            .trim()
+            .trim_start_matches("const __P: () = ")
+            .trim_end_matches(';')
+            .to_string(),
+    }
+}
+
+/// Returns the `#[import_tokens_attr]` or `#[import_tokens_proc]` attribute in `attrs` along
+/// with the type of proc macro it requires, if there is one.
+fn import_attr(attrs: &[Attribute]) -> Option<(&Attribute, ProcMacroType)> {
+    attrs.iter().find_map(
+        |attr| match attr.path().segments.last()?.ident.to_string().as_str() {
+            "import_tokens_attr" => Some((attr, ProcMacroType::Attribute)),
+            "import_tokens_proc" => Some((attr, ProcMacroType::Normal)),
+            _ => None,
+        },
+    )
+}
+
+/// Parses an `#[import_tokens_attr]` or `#[import_tokens_proc]` definition the same way the
+/// attributes do, returning a description of the problem if it would be rejected.
+fn parse_import_macro(
+    item_fn: &ItemFn,
+    attr: &Attribute,
+    macro_type: ProcMacroType,
+    module: &[String],
+    file: &FsPath,
+) -> std::result::Result<ScannedImportMacro, String> {
+    let override_path = match &attr.meta {
+        syn::Meta::List(list) => Some(
+            parse2::<OverridePath>(list.tokens.clone())
+                .map_err(|err| format!("invalid override path: {}", err))?,
+        ),
+        _ => None,
+    };
+    let custom_parsing = match item_fn
+        .attrs
+        .iter()
+        .find(|attr| attr.path().segments.last().unwrap().ident == "with_custom_parsing")
+    {
+        Some(attr) => Some(
+            attr.parse_args::<Path>()
+                .map_err(|err| format!("invalid `#[with_custom_parsing]`: {}", err))?,
+        ),
+        None => None,
+    };
+    let expected_inputs = match macro_type {
+        ProcMacroType::Attribute => 2,
+        _ => 1,
+    };
+    if item_fn.sig.inputs.len() != expected_inputs {
+        return Err(format!(
+            "a function with {} must take {} argument(s)",
+            macro_type.to_str(),
+            expected_inputs
+        ));
+    }
+    let proc_macro = parse_proc_macro_variant(item_fn.to_token_stream(), macro_type)
+        .map_err(|err| err.to_string())?;
+    Ok(ScannedImportMacro {
+        module: module.to_vec(),
+        proc_macro,
+        override_path: override_path.as_ref().map(override_path_string),
+        custom_parsing,
+        file: file.to_path_buf(),
+    })
+}
+
+/// Reads the crate located at `crate_dir` via [`scan_crate`] (treating the attributes named
+/// in `aliases` like `#[export_tokens]`), finds its `#[import_tokens_attr]` and
+/// `#[import_tokens_proc]` definitions, and flags:
+///
+/// - exports whose hidden macros collide because their idents flatten to the same name
+/// - exports in modules that cannot be reached from other crates
+/// - exports of items without an ident that don't specify one
+/// - import macro definitions that would be rejected by their attribute
+pub fn explore_crate(
+    crate_dir: impl AsRef<FsPath>,
+    aliases: &[&str],
+) -> io::Result<CrateExploration> {
+    let scan = scan_crate(crate_dir, aliases)?;
+    let mut import_macros = Vec::new();
+    let mut problems = Vec::new();
+    for module in &scan.modules {
+        let item_path = |name: &str| {
+            let mut path = module.path.clone();
+            path.push(name.to_string());
+            path.join("::")
+        };
+        for item in &module.items {
+            if let Item::Fn(item_fn) = item {
+                if let Some((attr, macro_type)) = import_attr(&item_fn.attrs) {
+                    match parse_import_macro(item_fn, attr, macro_type, &module.path, &module.file)
+                    {
+                        Ok(import_macro) => import_macros.push(import_macro),
+                        Err(message) => problems.push(ExploreProblem {
+                            path: item_path(&item_fn.sig.ident.to_string()),
+                            file: module.file.clone(),
+                            message,
+                        }),
+                    }
+                }
+            }
+            let Some((attr, _)) = export_attr(item_attrs(item), aliases) else {
+                continue;
+            };
+            if export_ident(item, attr).is_none() {
+                problems.push(ExploreProblem {
+                    path: item_path(&format!("<{}>", item_kind(item))),
+                    file: module.file.clone(),
+                    message: format!(
+                        "`{}` item has no ident, so one must be specified, i.e. \
+                        `#[{}(MyIdent)]`",
+                        item_kind(item),
+                        attr.path().segments.last().unwrap().ident
+                    ),
+                });
+            }
+        }
+    }
+    for (i, export) in scan.exports.iter().enumerate() {
+        let macro_ident = export.macro_ident();
+        let colliding: Vec<&ScannedExport> = scan.exports[..i]
+            .iter()
+            .filter(|other| other.module == export.module && other.macro_ident() == macro_ident)
+            .collect();
+        if let Some(other) = colliding.first() {
+            problems.push(ExploreProblem {
+                path: export.path(),
+                file: export.file.clone(),
+                message: format!(
+                    "collides with `{}`, as both export the hidden macro `{}`",
+                    other.path(),
+                    macro_ident
+                ),
+            });
+        }
+        if !export.accessible {
+            problems.push(ExploreProblem {
+                path: export.path(),
+                file: export.file.clone(),
+                message: String::from(
+                    "is exported from a module that is not `pub`, so it can only be imported \
+                    from within this crate",
+                ),
+            });
+        }
+    }
+    import_macros.sort_by_key(|import_macro| import_macro.path());
+    Ok(CrateExploration {
+        scan,
+        import_macros,
+        problems,
+    })
+}
+
+/// Renders `exploration` as the human-readable report printed by `cargo macro-magic`.
+pub fn exploration_report(exploration: &CrateExploration) -> String {
+    let mut report = format!("exports ({}):\n", exploration.scan.exports.len());
+    for export in &exploration.scan.exports {
+        let mut notes = vec![
+            export.kind().to_string(),
+            item_visibility(&export.item).unwrap_or_else(|| String::from("no visibility")),
+        ];
+        if !export.accessible {
+            notes.push(String::from("inaccessible module"));
+        }
+        if !export.emit {
+            notes.push(String::from("no emit"));
+        }
+        report.push_str(&format!(
+            "  {} [{}]\n    flattened: {}\n    macro: {}\n",
+            export.path(),
+            notes.join(", "),
+            flatten_ident(&export.ident),
+            export.macro_path(),
+        ));
+    }
+    report.push_str(&format!(
+        "\nimport macros ({}):\n",
+        exploration.import_macros.len()
+    ));
+    for import_macro in &exploration.import_macros {
+        report.push_str(&format!(
+            "  {} [#[{}]]\n    override path: {}\n",
+            import_macro.path(),
+            import_macro.attr_name(),
+            import_macro
+                .override_path
+                .clone()
+                .unwrap_or_else(|| format!("{} (default)", MACRO_MAGIC_ROOT)),
+        ));
+        if let Some(custom_parsing) = &import_macro.custom_parsing {
+            report.push_str(&format!(
+                "    custom parsing: {}\n",
+                custom_parsing
+                    .to_token_stream()
+                    .to_string()
+                    .replace(' ', "")
+            ));
+        }
+    }
+    report.push_str(&format!("\nproblems ({}):\n", exploration.problems.len()));
+    for problem in &exploration.problems {
+        report.push_str(&format!("  {}\n", problem));
+    }
+    report
+}
+
+#[cfg(all(test, feature = "pretty"))]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn explore_test_macros() {
+        let crate_dir = FsPath::new(env!("CARGO_MANIFEST_DIR")).join("../tests/test_macros");
+        let exploration = explore_crate(&crate_dir, &[]).unwrap();
+        let find = |name: &str| {
+            exploration
+                .import_macros
+                .iter()
+                .find(|import_macro| import_macro.path() == name)
+                .unwrap()
+        };
+        let distant = find("distant_re_export_attr");
+        assert_eq!(distant.attr_name(), "import_tokens_attr");
+        assert_eq!(
+            distant.override_path.as_deref(),
+            Some("format!(\"{}::export_mod::sub_mod::macro_magic\", \"middle_crate\")")
+        );
+        let item_level = find("item_level_proc");
+        assert_eq!(item_level.attr_name(), "import_tokens_proc");
+        assert_eq!(
+            item_level.override_path.as_deref(),
+            Some("example_export::subpath")
+        );
+        let custom = find("import_tokens_attr_with_custom_parsing_b");
+        assert!(custom.override_path.is_none());
+        assert!(custom
+            .custom_parsing
+            .as_ref()
+            .unwrap()
+            .is_ident("CustomParsingA"));
+    }
+
+    #[test]
+    fn explore_problems() {
+        let crate_dir = env::temp_dir().join(format!("macro_magic_explore_{}", std::process::id()));
+        fs::create_dir_all(crate_dir.join("src")).unwrap();
+        fs::write(
+            crate_dir.join("src/lib.rs"),
+            "#[export_tokens] pub struct FooBar;\n#[export_tokens] pub(crate) fn foo_bar() {}\n\
+            #[export_tokens] impl FooBar {}\nmod private { #[export_tokens] pub struct Hidden; }\n\
+            #[import_tokens_attr] #[proc_macro_attribute] fn my_attr(attr: TokenStream, \
+            tokens: TokenStream) -> TokenStream { tokens }",
+        )
+        .unwrap();
+        let exploration = explore_crate(&crate_dir, &[]).unwrap();
+        fs::remove_dir_all(&crate_dir).unwrap();
+        let problems: Vec<String> = exploration
+            .problems
+            .iter()
+            .map(|problem| format!("{}: {}", problem.path, problem.message))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "<impl>: `impl` item has no ident, so one must be specified, i.e. \
+                `#[export_tokens(MyIdent)]`",
+                "my_attr: Visibility must be public",
+                "foo_bar: collides with `FooBar`, as both export the hidden macro \
+                `__export_tokens_tt_foo_bar`",
+                "private::Hidden: is exported from a module that is not `pub`, so it can only be \
+                imported from within this crate",
+            ]
+        );
+        let report = exploration_report(&exploration);
+        assert!(report.contains(
+            "  foo_bar [fn, pub(crate)]\n    flattened: foo_bar\n    \
+            macro: __export_tokens_tt_foo_bar\n"
+        ));
+        assert!(report.contains("  private::Hidden [struct, pub, inaccessible module]\n"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Contains the implementation behind the `#[extend_enum]` attribute macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse2, punctuated::Punctuated, spanned::Spanned, Error, Fields, ItemEnum, Path, Result,
+    Variant,
+};
+
+use crate::{forward_to_builtin_attr, merge_generics, parse_forwarded_attr, trace_stage};
+
+/// Returns the pattern (and equivalently, the constructor expression) that binds every field
+/// of a struct or variant with the specified fields, i.e. `path { code }` or
+/// `path(__0, __1)`.
+pub(crate) fn fields_binding(path: &TokenStream2, fields: &Fields) -> TokenStream2 {
+    match fields {
+        Fields::Named(fields) => {
+            let idents = fields.named.iter().map(|field| &field.ident);
+            quote!(#path { #(#idents),* })
+        }
+        Fields::Unnamed(fields) => {
+            let idents = (0..fields.unnamed.len()).map(|i| format_ident!("__{}", i));
+            quote!(#path(#(#idents),*))
+        }
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// Returns the pattern (and equivalently, the constructor expression) that binds every field
+/// of the specified variant, i.e. `Finished { code }` or `Progress(__0, __1)`, prefixed by
+/// `prefix`.
+pub(crate) fn variant_binding(prefix: &TokenStream2, variant: &Variant) -> TokenStream2 {
+    let ident = &variant.ident;
+    fields_binding(&quote!(#prefix::#ident), &variant.fields)
+}
+
+/// Prepends the variants of the `foreign` enum (imported from `source`) to the `local` enum,
+/// merging generics and where-clauses, and generates a `From<source>` impl along with a
+/// `TryFrom<local>` impl for `source` whose error type is the local enum.
+///
+/// Returns an error pointing at both definitions for each variant of `local` whose name
+/// conflicts with a variant of `foreign`.
+pub fn extend_enum_tokens(
+    mut local: ItemEnum,
+    foreign: &ItemEnum,
+    source: &Path,
+) -> Result<TokenStream2> {
+    let source_str = source.to_token_stream().to_string();
+    let mut errors: Option<Error> = None;
+    for variant in &local.variants {
+        let Some(foreign_variant) = foreign
+            .variants
+            .iter()
+            .find(|foreign_variant| foreign_variant.ident == variant.ident)
+        else {
+            continue;
+        };
+        let mut err = Error::new(
+            variant.ident.span(),
+            format!(
+                "variant `{}` conflicts with a variant of the same name in extended enum `{}`",
+                variant.ident, source_str
+            ),
+        );
+        err.combine(Error::new(
+            foreign_variant.ident.span(),
+            format!("`{}` is also defined here", foreign_variant.ident),
+        ));
+        match &mut errors {
+            Some(errors) => errors.combine(err),
+            None => errors = Some(err),
+        }
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+    let mut variants: Punctuated<Variant, syn::token::Comma> = foreign.variants.clone();
+    variants.extend(local.variants.iter().cloned());
+    local.variants = variants;
+    merge_generics(&mut local.generics, &foreign.generics);
+
+    let local_ident = &local.ident;
+    let (impl_generics, local_ty_generics, where_clause) = local.generics.split_for_impl();
+    let (_, foreign_ty_generics, _) = foreign.generics.split_for_impl();
+    let foreign_ty = quote!(#source #foreign_ty_generics);
+    let local_prefix = quote!(#local_ident);
+    let foreign_prefix = quote!(#source);
+    let into_local = foreign.variants.iter().map(|variant| {
+        let foreign_binding = variant_binding(&foreign_prefix, variant);
+        let local_binding = variant_binding(&local_prefix, variant);
+        quote!(#foreign_binding => #local_binding)
+    });
+    let into_foreign = foreign.variants.iter().map(|variant| {
+        let foreign_binding = variant_binding(&foreign_prefix, variant);
+        let local_binding = variant_binding(&local_prefix, variant);
+        quote!(#local_binding => ::core::result::Result::Ok(#foreign_binding))
+    });
+    Ok(quote! {
+        #local
+
+        impl #impl_generics ::core::convert::From<#foreign_ty> for #local_ident #local_ty_generics
+        #where_clause
+        {
+            fn from(value: #foreign_ty) -> Self {
+                match value {
+                    #(#into_local),*
+                }
+            }
+        }
+
+        impl #impl_generics ::core::convert::TryFrom<#local_ident #local_ty_generics> for #foreign_ty
+        #where_clause
+        {
+            type Error = #local_ident #local_ty_generics;
+
+            #[allow(unreachable_patterns)]
+            fn try_from(
+                value: #local_ident #local_ty_generics,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                match value {
+                    #(#into_foreign,)*
+                    other => ::core::result::Result::Err(other),
+                }
+            }
+        }
+    })
+}
+
+/// Internal implementation of the `#[extend_enum]` attribute macro.
+///
+/// When invoked by the user, `attr` contains the path of an `#[export_tokens]` enum, whose
+/// tokens are forwarded back to `#[extend_enum]`. When invoked with forwarded tokens, the
+/// attached enum is extended via [`extend_enum_tokens`].
+pub fn extend_enum_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    let tokens = tokens.into();
+    let output = match parse_forwarded_attr(&attr) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let source = parse2::<Path>(forwarded.source_path)?;
+            let Ok(foreign) = parse2::<ItemEnum>(forwarded.imported_item.to_token_stream()) else {
+                return Err(Error::new(
+                    source.span(),
+                    format!(
+                        "`{}` is not an enum, so it cannot be extended",
+                        source.to_token_stream()
+                    ),
+                ));
+            };
+            let local = parse2::<ItemEnum>(forwarded.tokens_ident)?;
+            extend_enum_tokens(local, &foreign, &source)?
+        }
+        None => {
+            let source = parse2::<Path>(attr.clone())?;
+            let Ok(local) = parse2::<ItemEnum>(tokens.clone()) else {
+                return Err(Error::new(
+                    tokens.span(),
+                    "`#[extend_enum]` can only be attached to an enum",
+                ));
+            };
+            forward_to_builtin_attr(
+                "extend_enum",
+                &source,
+                &local.to_token_stream(),
+                &TokenStream2::new(),
+            )
+        }
+    };
+    trace_stage("extend_enum", None, None, &quote!(#attr #tokens), &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn extend_enum_prepends_variants() {
+        let output = extend_enum_tokens(
+            parse_quote!(
+                enum AppEvent {
+                    Custom(&'static str),
+                }
+            ),
+            &parse_quote!(
+                enum Event {
+                    Started,
+                    Progress(u8),
+                    Finished { code: u32 },
+                }
+            ),
+            &parse_quote!(upstream::Event),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.starts_with(
+            "enum AppEvent { Started , Progress (u8) , Finished { code : u32 } , Custom (& 'static str) }"
+        ));
+        assert!(
+            output.contains("upstream :: Event :: Progress (__0) => AppEvent :: Progress (__0)")
+        );
+        assert!(output.contains(
+            "AppEvent :: Finished { code } => :: core :: result :: Result :: Ok (upstream :: Event :: Finished { code })"
+        ));
+        assert!(output.contains("type Error = AppEvent ;"));
+    }
+
+    #[test]
+    fn extend_enum_generics() {
+        let output = extend_enum_tokens(
+            parse_quote!(
+                enum Local<T> {
+                    Other(T),
+                }
+            ),
+            &parse_quote!(
+                enum Event<'a> {
+                    Named(&'a str),
+                }
+            ),
+            &parse_quote!(upstream::Event),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains(
+            "impl < 'a , T > :: core :: convert :: From < upstream :: Event < 'a > > for Local < 'a , T >"
+        ));
+    }
+
+    #[test]
+    fn extend_enum_duplicate_variants() {
+        let Err(err) = extend_enum_tokens(
+            parse_quote!(
+                enum Local {
+                    Started,
+                }
+            ),
+            &parse_quote!(
+                enum Event {
+                    Started,
+                }
+            ),
+            &parse_quote!(upstream::Event),
+        ) else {
+            panic!("expected an error");
+        };
+        let messages: Vec<String> = err.into_iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "variant `Started` conflicts with a variant of the same name in extended enum \
+                `upstream :: Event`",
+                "`Started` is also defined here"
+            ]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Contains the implementation behind `#[export_group]`, `#[export_tokens(group = ..)]` and
+//! `collect_group!`, which forward every item of a named group to a target macro at once.
+
+use std::{collections::VecDeque, slice, sync::atomic::Ordering};
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    parse2,
+    token::Comma,
+    Error, Ident, Item, ItemMod, Meta, Path, Result, Token,
+};
+
+use crate::{
+    export_attr, export_tokens_item_ident, flatten_ident, item_attrs_mut, keywords,
+    macro_magic_root, parse_path_items, path_items_to_tokens, private_path, trace_stage,
+    ExportTokensArgs, ForwardTokensExtraArg, PipelineState, PipelineStep, COMPILATION_TAG, COUNTER,
+};
+
+/// Produces the ident of the hidden macro that provides the items of the specified export
+/// group, i.e. `__export_group_tt_plugins`.
+pub fn export_group_macro_ident(group: &Ident) -> Ident {
+    format_ident!("__export_group_tt_{}", flatten_ident(group))
+}
+
+/// Resolves to the path of the hidden macro of the export group at the specified path, i.e.
+/// `crate::plugins` resolves to `crate::__export_group_tt_plugins`.
+pub fn export_group_macro_path(group_path: &Path) -> Path {
+    let mut macro_path = group_path.clone();
+    let Some(last_seg) = macro_path.segments.pop() else {
+        unreachable!("must have at least one segment")
+    };
+    let last_seg = export_group_macro_ident(&last_seg.into_value().ident);
+    macro_path.segments.push(last_seg.into());
+    macro_path
+}
+
+/// Parses the args of `#[export_group]`, i.e. nothing (in which case the group is named after
+/// the module) or a comma-separated list of group names, i.e. `#[export_group(plugins, hooks)]`.
+///
+/// You shouldn't need to use this directly.
+pub struct ExportGroupArgs {
+    /// The names of the groups declared by the module, or an empty [`Vec`] if the group is
+    /// named after the module
+    pub groups: Vec<Ident>,
+}
+
+impl Parse for ExportGroupArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(ExportGroupArgs {
+            groups: input
+                .parse_terminated(Ident::parse, Comma)?
+                .into_iter()
+                .collect(),
+        })
+    }
+}
+
+/// Parses the args of `collect_group!`, i.e. `crate::plugins, my_macro`, optionally followed
+/// by `args = { .. }`.
+///
+/// You shouldn't need to use this directly.
+pub struct CollectGroupArgs {
+    /// The path of the group, i.e. the path of the module declaring it with its last segment
+    /// replaced by the name of the group
+    pub group: Path,
+    /// The path of the macro that will receive the items of the group
+    pub target: Path,
+    /// User-supplied args that will be passed along to the target
+    pub args: TokenStream2,
+}
+
+impl Parse for CollectGroupArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let group = input.parse()?;
+        input.parse::<Comma>()?;
+        let target = input.parse()?;
+        let mut args = TokenStream2::new();
+        if input.parse::<Option<Comma>>()?.is_some() && !input.is_empty() {
+            input.parse::<keywords::args>()?;
+            input.parse::<Token![=]>()?;
+            args = input.parse::<ForwardTokensExtraArg>()?.stream;
+            input.parse::<Option<Comma>>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `args = { .. }`"));
+        }
+        Ok(CollectGroupArgs {
+            group,
+            target,
+            args,
+        })
+    }
+}
+
+/// The tokens a group macro passes to `collect_group_inner!`: the module declaring the group,
+/// the exported idents and tokens of its items, and the `collect_group!` call being resolved.
+struct GroupPayload {
+    module: Ident,
+    items: Vec<(Path, Item)>,
+    group: Path,
+    target: Path,
+    args: TokenStream2,
+}
+
+impl Parse for GroupPayload {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<keywords::__private_macro_magic_group>()?;
+        let content;
+        braced!(content in input);
+        let module = content.parse()?;
+        let content;
+        braced!(content in input);
+        let items = parse_path_items(&content)?;
+        let content;
+        braced!(content in input);
+        let group = content.parse()?;
+        let content;
+        braced!(content in input);
+        let target = content.parse()?;
+        let args = input.parse::<ForwardTokensExtraArg>()?.stream;
+        Ok(GroupPayload {
+            module,
+            items,
+            group,
+            target,
+            args,
+        })
+    }
+}
+
+/// Internal implementation of `#[export_group]`.
+///
+/// Collects the items of the (inline) module `tokens` marked with
+/// `#[export_tokens(group = name)]` (or `#[export_tokens_no_emit(group = name)]`) for each of
+/// the groups declared in `attr`, and emits a hidden macro for each group next to the module
+/// that provides all of their tokens at once. The `group = name` args are then removed, so the
+/// items are exported as usual.
+pub fn export_group_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let mut item_mod = parse2::<ItemMod>(tokens.clone())?;
+    let args = parse2::<ExportGroupArgs>(attr.into())?;
+    let groups = match args.groups.is_empty() {
+        true => vec![item_mod.ident.clone()],
+        false => args.groups,
+    };
+    let Some((_, items)) = &mut item_mod.content else {
+        return Err(Error::new(
+            item_mod.ident.span(),
+            "`#[export_group]` can only be attached to inline modules, i.e. `mod plugins { .. }`",
+        ));
+    };
+    let mut group_items: Vec<Vec<(Path, Item)>> = vec![Vec::new(); groups.len()];
+    for item in items.iter_mut() {
+        let Some(attrs) = item_attrs_mut(item) else {
+            continue;
+        };
+        let Some(index) = attrs
+            .iter()
+            .position(|attr| export_attr(slice::from_ref(attr), &[]).is_some())
+        else {
+            continue;
+        };
+        // malformed args (and `#[export_template]` args) are left for the attribute to handle
+        let Meta::List(list) = &attrs[index].meta else {
+            continue;
+        };
+        let Ok(mut export_args) = parse2::<ExportTokensArgs>(list.tokens.clone()) else {
+            continue;
+        };
+        let Some(group) = export_args.group.take() else {
+            continue;
+        };
+        let Some(group_index) = groups.iter().position(|name| name == &group) else {
+            return Err(Error::new(
+                group.span(),
+                format!(
+                    "group `{}` is not declared by the enclosing `#[export_group]`",
+                    group
+                ),
+            ));
+        };
+        let path = attrs[index].path().clone();
+        attrs[index] = match export_args.to_token_stream().is_empty() {
+            true => syn::parse_quote!(#[#path]),
+            false => syn::parse_quote!(#[#path(#export_args)]),
+        };
+        // the item is provided as `#[export_tokens]` would forward it, without the attribute
+        let mut grouped = item.clone();
+        if let Some(attrs) = item_attrs_mut(&mut grouped) {
+            attrs.remove(index);
+        }
+        // items without an ident are rejected by `#[export_tokens]` itself
+        if let Some(ident) = export_args.ident.or_else(|| export_tokens_item_ident(item)) {
+            group_items[group_index].push((ident.into(), grouped));
+        }
+    }
+    let group_keyword = keywords::__private_macro_magic_group::default();
+    let module = &item_mod.ident;
+    let group_macros = groups.iter().zip(&group_items).map(|(group, items)| {
+        let unique_id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let unique_ident = format_ident!(
+            "__export_group_tt_{}_{}_{}",
+            COMPILATION_TAG,
+            flatten_ident(group),
+            unique_id
+        );
+        let group_ident = export_group_macro_ident(group);
+        let items = path_items_to_tokens(items);
+        quote! {
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! #unique_ident {
+                ($(::)?$($callback:ident)::*, { $( $extra:tt )* }) => {
+                    $($callback)::*! {
+                        #group_keyword
+                        { #module }
+                        { #items }
+                        $( $extra )*
+                    }
+                };
+            }
+            pub use #unique_ident as #group_ident;
+        }
+    });
+    let output = quote! {
+        #(#group_macros)*
+        #item_mod
+    };
+    trace_stage("export_group", None, None, &tokens, &output);
+    Ok(output)
+}
+
+/// Internal implementation of `collect_group!`.
+pub fn collect_group_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let args = parse2::<CollectGroupArgs>(tokens.clone())?;
+    let group_macro = export_group_macro_path(&args.group);
+    let inner_macro = private_path(&quote!(collect_group_inner));
+    let group = &args.group;
+    let target = &args.target;
+    let user_args = &args.args;
+    let output = quote! {
+        #group_macro! {
+            #inner_macro,
+            { { #group } { #target } { #user_args } }
+        }
+    };
+    trace_stage("collect_group", Some(group), Some(target), &tokens, &output);
+    Ok(output)
+}
+
+/// Internal implementation of the hidden `collect_group_inner!` macro, which receives the
+/// items of a group from its group macro and invokes the target of the `collect_group!` with
+/// them, along with their full paths.
+pub fn collect_group_inner_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let payload = parse2::<GroupPayload>(tokens.clone())?;
+    let mut module_path = payload.group.clone();
+    if module_path.segments.pop().is_none() {
+        return Err(Error::new(
+            Span::call_site(),
+            "group path must not be empty",
+        ));
+    }
+    let items = payload
+        .items
+        .into_iter()
+        .map(|(ident, item)| {
+            let mut path = module_path.clone();
+            path.segments.push(payload.module.clone().into());
+            path.segments.extend(ident.segments);
+            (path, item)
+        })
+        .collect();
+    let output = PipelineState {
+        depth: 0,
+        max_depth: 1,
+        visited: Vec::new(),
+        items,
+        steps: VecDeque::from([PipelineStep::Stage(payload.target.clone())]),
+        args: payload.args,
+        mm_path: macro_magic_root(),
+    }
+    .run()?;
+    trace_stage(
+        "collect_group_inner",
+        Some(&payload.group),
+        Some(&payload.target),
+        &tokens,
+        &output,
+    );
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_group_strips_group_args() {
+        let output = export_group_internal(
+            quote!(),
+            quote! {
+                pub mod plugins {
+                    #[export_tokens(group = plugins)]
+                    pub struct Logger;
+                    #[export_tokens(TheCache, group = plugins)]
+                    pub struct Cache;
+                    #[export_tokens]
+                    pub struct Unrelated;
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("pub use __export_group_tt_"));
+        assert!(output.contains("as __export_group_tt_plugins ;"));
+        assert!(output.contains(
+            "{ plugins } { [Logger] { pub struct Logger ; } [TheCache] { pub struct Cache ; } }"
+        ));
+        assert!(output.contains("# [export_tokens] pub struct Logger ;"));
+        assert!(output.contains("# [export_tokens (TheCache)] pub struct Cache ;"));
+        assert!(!output.contains("group ="));
+    }
+
+    #[test]
+    fn export_group_rejects_undeclared_groups() {
+        let err = export_group_internal(
+            quote!(hooks),
+            quote! {
+                mod plugins {
+                    #[export_tokens(group = plugins)]
+                    pub struct Logger;
+                }
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "group `plugins` is not declared by the enclosing `#[export_group]`"
+        );
+        let err = crate::export_tokens_internal(
+            quote!(group = plugins),
+            quote!(
+                pub struct Logger;
+            ),
+            true,
+            true,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("`group = plugins` can only be used"));
+    }
+}
@@ -0,0 +1,259 @@
+//! Contains the implementation behind `import_tokens_from_file!` and
+//! `#[import_tokens_from_file_attr]`, which import the tokens of an item directly from a source
+//! file, for crates that don't mark their items with `#[export_tokens]`.
+
+use std::{
+    env, fs, io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2, Error, Ident, Item, ItemFn, LitStr, Path, Result, Stmt, Token,
+};
+
+use crate::{
+    child_module_dir, export_tokens_item_ident, module_file, parse_source_file, private_path,
+    trace_stage,
+};
+
+/// Used to parse the args of [`import_tokens_from_file_internal`] and
+/// [`import_tokens_from_file_attr_internal`], i.e. `let tokens = "vendor/lib.rs" :: Config`
+/// or `let tokens = "vendor/lib.rs" :: codec::encode`.
+///
+/// You shouldn't need to use this directly.
+pub struct ImportTokensFromFileArgs {
+    /// The ident of the variable the tokens will be assigned to
+    pub tokens_var_ident: Ident,
+    /// The path of the source file, relative to `CARGO_MANIFEST_DIR`
+    pub file: LitStr,
+    /// The path of the item within the file, where each segment but the last is an inline
+    /// module
+    pub item_path: Path,
+}
+
+impl Parse for ImportTokensFromFileArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![let]>()?;
+        let tokens_var_ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let file = input.parse()?;
+        input.parse::<Token![::]>()?;
+        let item_path = input.parse()?;
+        Ok(ImportTokensFromFileArgs {
+            tokens_var_ident,
+            file,
+            item_path,
+        })
+    }
+}
+
+/// Finds the item at `item_path` in `items`, descending into inline modules for each segment
+/// but the last. Items are matched by the ident `#[export_tokens]` would export them under (see
+/// [`export_tokens_item_ident`]), so items without an ident (such as impl blocks) can't be
+/// selected.
+pub fn select_item(items: &[Item], item_path: &Path) -> Option<Item> {
+    let segments: Vec<_> = item_path.segments.iter().collect();
+    let (last, modules) = segments.split_last()?;
+    let mut items = items;
+    for segment in modules {
+        items = items.iter().find_map(|item| match item {
+            Item::Mod(item_mod) if item_mod.ident == segment.ident => {
+                item_mod.content.as_ref().map(|(_, items)| items.as_slice())
+            }
+            _ => None,
+        })?;
+    }
+    items
+        .iter()
+        .find(|item| export_tokens_item_ident(item).is_some_and(|ident| ident == last.ident))
+        .cloned()
+}
+
+/// Reads and parses the source file `file` (relative to `CARGO_MANIFEST_DIR`) and selects the
+/// item at `item_path` via [`select_item`], returning it along with the absolute path of the
+/// file.
+pub fn read_item_from_file(file: &LitStr, item_path: &Path) -> Result<(Item, PathBuf)> {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| Error::new(file.span(), "`CARGO_MANIFEST_DIR` is not set"))?;
+    let file_path = PathBuf::from(manifest_dir).join(file.value());
+    let source = fs::read_to_string(&file_path).map_err(|err| {
+        Error::new(
+            file.span(),
+            format!("failed to read {}: {}", file_path.display(), err),
+        )
+    })?;
+    let parsed = syn::parse_file(&source).map_err(|err| {
+        Error::new(
+            file.span(),
+            format!("failed to parse {}: {}", file_path.display(), err),
+        )
+    })?;
+    let Some(item) = select_item(&parsed.items, item_path) else {
+        return Err(Error::new_spanned(
+            item_path,
+            format!(
+                "could not find `{}` in {}",
+                item_path.to_token_stream().to_string().replace(' ', ""),
+                file.value()
+            ),
+        ));
+    };
+    Ok((item, file_path))
+}
+
+/// Reads the crate whose root file is `root_file` (i.e. `src/lib.rs`) and finds the item at
+/// `item_path`, following `mod` declarations (including `#[path = ".."]`) into their files for
+/// each segment but the last. Returns the item along with every file that was read.
+///
+/// Unlike [`scan_crate`](`crate::scan_crate`), only the modules along `item_path` are read, so
+/// unrelated modules of large crates are never parsed. `#[cfg(..)]` conditions are not
+/// evaluated, so the first module with a matching name whose file exists is used.
+pub fn read_item_from_crate(
+    root_file: impl AsRef<FsPath>,
+    item_path: &Path,
+) -> io::Result<(Item, Vec<PathBuf>)> {
+    let root_file = root_file.as_ref();
+    let path_string = item_path.to_token_stream().to_string().replace(' ', "");
+    let not_found = |what: String| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "could not find {} of `{}` in {}",
+                what,
+                path_string,
+                root_file.display()
+            ),
+        )
+    };
+    let segments: Vec<_> = item_path.segments.iter().collect();
+    let Some((last, modules)) = segments.split_last() else {
+        return Err(not_found("the item".into()));
+    };
+    let mut files = vec![root_file.to_path_buf()];
+    let mut items = parse_source_file(root_file)?.items;
+    let mut dir = child_module_dir(root_file, true);
+    for segment in modules {
+        let Some(item_mod) = items.into_iter().find_map(|item| match item {
+            Item::Mod(item_mod) if item_mod.ident == segment.ident => Some(item_mod),
+            _ => None,
+        }) else {
+            return Err(not_found(format!("module `{}`", segment.ident)));
+        };
+        match item_mod.content {
+            Some((_, content)) => {
+                items = content;
+                dir = dir.join(segment.ident.to_string());
+            }
+            None => {
+                let Some(file) = module_file(&item_mod, &dir) else {
+                    return Err(not_found(format!("the file of module `{}`", segment.ident)));
+                };
+                items = parse_source_file(&file)?.items;
+                dir = child_module_dir(&file, false);
+                files.push(file);
+            }
+        }
+    }
+    let item = select_item(&items, &last.ident.clone().into())
+        .ok_or_else(|| not_found(format!("`{}`", last.ident)))?;
+    Ok((item, files))
+}
+
+/// Returns a `let` statement assigning the tokens of the item selected by `args` to a
+/// `TokenStream2` variable. The file is passed to `include_bytes!` so the compiler tracks it,
+/// and edits to the file trigger a rebuild.
+fn import_tokens_from_file_stmt(args: &ImportTokensFromFileArgs) -> Result<TokenStream2> {
+    let (item, file_path) = read_item_from_file(&args.file, &args.item_path)?;
+    let tokens_string = item.to_token_stream().to_string();
+    let file_path = file_path.to_string_lossy().to_string();
+    let ident = &args.tokens_var_ident;
+    let token_stream_2 = private_path(&quote!(TokenStream2));
+    Ok(quote! {
+        let #ident = {
+            const _: &[u8] = include_bytes!(#file_path);
+            #tokens_string.parse::<#token_stream_2>().expect("failed to parse quoted tokens")
+        };
+    })
+}
+
+/// Internal implementation of `import_tokens_from_file!`.
+///
+/// Like [`import_tokens_internal`](`crate::import_tokens_internal`), but reads the item from a
+/// source file via [`read_item_from_file`] rather than importing it from an `#[export_tokens]`
+/// item, so no callback is involved.
+pub fn import_tokens_from_file_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let args = parse2::<ImportTokensFromFileArgs>(tokens.clone())?;
+    let output = import_tokens_from_file_stmt(&args)?;
+    trace_stage("import_tokens_from_file", None, None, &tokens, &output);
+    Ok(output)
+}
+
+/// Internal implementation of `#[import_tokens_from_file_attr]`, which prepends the statement
+/// generated by [`import_tokens_from_file_internal`] to the body of the attached function.
+pub fn import_tokens_from_file_attr_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let args = parse2::<ImportTokensFromFileArgs>(attr.into())?;
+    let mut item_fn = parse2::<ItemFn>(tokens.clone())?;
+    let stmt = parse2::<Stmt>(import_tokens_from_file_stmt(&args)?)?;
+    item_fn.block.stmts.insert(0, stmt);
+    let output = item_fn.to_token_stream();
+    trace_stage("import_tokens_from_file_attr", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn select_items() {
+        let file: syn::File = parse_quote! {
+            pub struct Config;
+            impl Config {}
+            mod codec {
+                pub fn encode() {}
+                mod nested;
+            }
+        };
+        let select = |path: Path| {
+            select_item(&file.items, &path).map(|item| item.to_token_stream().to_string())
+        };
+        assert_eq!(select(parse_quote!(Config)).unwrap(), "pub struct Config ;");
+        assert_eq!(
+            select(parse_quote!(codec::encode)).unwrap(),
+            "pub fn encode () { }"
+        );
+        assert!(select(parse_quote!(codec::decode)).is_none());
+        assert!(select(parse_quote!(codec::nested::Thing)).is_none());
+        assert!(select(parse_quote!(missing::Config)).is_none());
+    }
+
+    #[test]
+    fn read_items_from_crate() {
+        let root =
+            FsPath::new(env!("CARGO_MANIFEST_DIR")).join("../tests/external_crate/src/lib.rs");
+        let (item, files) = read_item_from_crate(&root, &parse_quote!(config::Timeout)).unwrap();
+        assert_eq!(export_tokens_item_ident(&item).unwrap(), "Timeout");
+        assert_eq!(files, [root.as_path()]);
+        let err = read_item_from_crate(&root, &parse_quote!(config::Missing))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("could not find `Missing` of `config::Missing`"));
+        let err = read_item_from_crate(&root, &parse_quote!(missing::Timeout))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("could not find module `missing` of `missing::Timeout`"));
+    }
+}
@@ -0,0 +1,277 @@
+//! Contains the implementation behind the `#[inherit_impl]` attribute macro.
+
+use proc_macro2::{Group, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse2,
+    spanned::Spanned,
+    visit_mut::{self, VisitMut},
+    Error, Ident, ImplItem, ItemImpl, Macro, Path, PathSegment, Result, Type,
+};
+
+use crate::{forward_to_builtin_attr, parse_forwarded_attr, trace_stage};
+
+/// Returns the ident of the specified associated item, if it has one.
+pub(crate) fn impl_item_ident(item: &ImplItem) -> Option<&Ident> {
+    match item {
+        ImplItem::Const(item_const) => Some(&item_const.ident),
+        ImplItem::Fn(item_fn) => Some(&item_fn.sig.ident),
+        ImplItem::Type(item_type) => Some(&item_type.ident),
+        _ => None,
+    }
+}
+
+/// Returns the last path segment of the specified type, if it is a path type.
+fn type_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            type_path.path.segments.last().map(|segment| &segment.ident)
+        }
+        _ => None,
+    }
+}
+
+/// Replaces the self type of a foreign impl with the self type of a local impl.
+struct SelfTypeReplacer {
+    foreign_ty: String,
+    foreign_ident: Option<Ident>,
+    local_ty: Type,
+}
+
+impl VisitMut for SelfTypeReplacer {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if ty.to_token_stream().to_string() == self.foreign_ty {
+            *ty = self.local_ty.clone();
+            return;
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        // expression paths such as `Foreign::new()` or `Foreign { .. }` become `Self`
+        if let (None, Some(ident), Some(first)) = (
+            path.leading_colon,
+            &self.foreign_ident,
+            path.segments.first_mut(),
+        ) {
+            if &first.ident == ident {
+                *first = PathSegment::from(Ident::new("Self", first.ident.span()));
+            }
+        }
+        visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        // macro bodies are opaque, so fall back to replacing the bare ident with `Self`
+        if let Some(ident) = &self.foreign_ident {
+            mac.tokens = replace_ident_with_self(mac.tokens.clone(), ident);
+        }
+        visit_mut::visit_macro_mut(self, mac);
+    }
+}
+
+/// Replaces every occurrence of `ident` within `tokens` (recursively) with `Self`.
+fn replace_ident_with_self(tokens: TokenStream2, ident: &Ident) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(token_ident) if &token_ident == ident => {
+                TokenTree::Ident(Ident::new("Self", token_ident.span()))
+            }
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(
+                    group.delimiter(),
+                    replace_ident_with_self(group.stream(), ident),
+                );
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group)
+            }
+            token => token,
+        })
+        .collect()
+}
+
+/// Copies every method, associated const and associated type of `foreign` that `local` does
+/// not already define into `local`, replacing the self type of `foreign` with the self type
+/// of `local` within the copied items.
+///
+/// Returns an error if the two impls implement different traits (compared by the last
+/// segment of the trait path), or if only one of them is a trait impl.
+pub fn inherit_impl_items(
+    mut local: ItemImpl,
+    foreign: &ItemImpl,
+    source: &Path,
+) -> Result<ItemImpl> {
+    let trait_ident = |item_impl: &ItemImpl| {
+        item_impl
+            .trait_
+            .as_ref()
+            .and_then(|(_, path, _)| path.segments.last())
+            .map(|segment| segment.ident.to_string())
+    };
+    if trait_ident(&local) != trait_ident(foreign) {
+        let describe = |item_impl: &ItemImpl| match &item_impl.trait_ {
+            Some((_, path, _)) => format!("an impl of `{}`", path.to_token_stream()),
+            None => String::from("an inherent impl"),
+        };
+        return Err(Error::new(
+            source.span(),
+            format!(
+                "`{}` is {}, but the local impl is {}",
+                source.to_token_stream(),
+                describe(foreign),
+                describe(&local)
+            ),
+        ));
+    }
+    let mut replacer = SelfTypeReplacer {
+        foreign_ty: foreign.self_ty.to_token_stream().to_string(),
+        foreign_ident: type_ident(&foreign.self_ty).cloned(),
+        local_ty: (*local.self_ty).clone(),
+    };
+    let mut inherited = Vec::new();
+    for item in &foreign.items {
+        let Some(ident) = impl_item_ident(item) else {
+            continue;
+        };
+        if local
+            .items
+            .iter()
+            .any(|local_item| impl_item_ident(local_item) == Some(ident))
+        {
+            continue;
+        }
+        let mut item = item.clone();
+        replacer.visit_impl_item_mut(&mut item);
+        inherited.push(item);
+    }
+    local.items.extend(inherited);
+    Ok(local)
+}
+
+/// Internal implementation of the `#[inherit_impl]` attribute macro.
+///
+/// When invoked by the user, `attr` contains the path of an `#[export_tokens]` impl, whose
+/// tokens are forwarded back to `#[inherit_impl]`. When invoked with forwarded tokens, the
+/// missing items of the imported impl are copied into the attached impl via
+/// [`inherit_impl_items`].
+pub fn inherit_impl_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    let tokens = tokens.into();
+    let output = match parse_forwarded_attr(&attr) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let source = parse2::<Path>(forwarded.source_path)?;
+            let Ok(foreign) = parse2::<ItemImpl>(forwarded.imported_item.to_token_stream()) else {
+                return Err(Error::new(
+                    source.span(),
+                    format!(
+                        "`{}` is not an impl block, so its items cannot be inherited",
+                        source.to_token_stream()
+                    ),
+                ));
+            };
+            let local = parse2::<ItemImpl>(forwarded.tokens_ident)?;
+            inherit_impl_items(local, &foreign, &source)?.to_token_stream()
+        }
+        None => {
+            let source = parse2::<Path>(attr.clone())?;
+            let Ok(local) = parse2::<ItemImpl>(tokens.clone()) else {
+                return Err(Error::new(
+                    tokens.span(),
+                    "`#[inherit_impl]` can only be attached to an impl block",
+                ));
+            };
+            forward_to_builtin_attr(
+                "inherit_impl",
+                &source,
+                &local.to_token_stream(),
+                &TokenStream2::new(),
+            )
+        }
+    };
+    trace_stage("inherit_impl", None, None, &quote!(#attr #tokens), &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn inherit(local: ItemImpl, foreign: ItemImpl) -> Result<ItemImpl> {
+        inherit_impl_items(local, &foreign, &parse_quote!(a::square_shape))
+    }
+
+    #[test]
+    fn inherit_impl_skips_overrides() {
+        let merged = inherit(
+            parse_quote! {
+                impl Shape for Tri {
+                    const SIDES: u32 = 3;
+                    fn name(&self) -> &'static str { "tri" }
+                }
+            },
+            parse_quote! {
+                impl Shape for Square {
+                    const SIDES: u32 = 4;
+                    type Unit = u32;
+                    fn name(&self) -> &'static str { "square" }
+                    fn sides(&self) -> u32 { Self::SIDES }
+                }
+            },
+        )
+        .unwrap();
+        let idents: Vec<String> = merged
+            .items
+            .iter()
+            .filter_map(impl_item_ident)
+            .map(|ident| ident.to_string())
+            .collect();
+        assert_eq!(idents, ["SIDES", "name", "Unit", "sides"]);
+        assert!(merged.to_token_stream().to_string().contains("\"tri\""));
+        assert!(!merged.to_token_stream().to_string().contains("\"square\""));
+    }
+
+    #[test]
+    fn inherit_impl_substitutes_self_type() {
+        let merged = inherit(
+            parse_quote!(impl Shape for Tri {}),
+            parse_quote! {
+                impl Shape for Square {
+                    fn double(&self) -> Square { Square(self.0 * 2) }
+                    fn all(&self) -> Vec<Square> { vec![Square::new()] }
+                }
+            },
+        )
+        .unwrap()
+        .to_token_stream()
+        .to_string();
+        assert!(merged.contains("fn double (& self) -> Tri { Self (self . 0 * 2) }"));
+        assert!(merged.contains("fn all (& self) -> Vec < Tri >"));
+        assert!(!merged.contains("Square"));
+    }
+
+    #[test]
+    fn inherit_impl_trait_mismatch() {
+        let Err(err) = inherit(
+            parse_quote!(impl Other for Tri {}),
+            parse_quote!(impl Shape for Square {}),
+        ) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "`a :: square_shape` is an impl of `Shape`, but the local impl is an impl of `Other`"
+        );
+        assert!(inherit(
+            parse_quote!(impl Tri {}),
+            parse_quote!(impl Shape for Square {})
+        )
+        .is_err());
+        assert!(inherit(parse_quote!(impl Tri {}), parse_quote!(impl Square {})).is_ok());
+    }
+}
@@ -11,13 +11,62 @@ use macro_magic_core_macros::*;
 use proc_macro2::{Delimiter, Group, Punct, Spacing, Span, TokenStream as TokenStream2, TokenTree};
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
 use syn::{
-    parse::{Nothing, ParseStream},
+    parse::ParseStream,
     parse2, parse_quote,
     spanned::Spanned,
     token::{Brace, Comma},
     Attribute, Error, Expr, FnArg, Ident, Item, ItemFn, Pat, Path, Result, Token, Visibility,
 };
 
+mod api_snapshot;
+pub use api_snapshot::*;
+mod assert_same_shape;
+pub use assert_same_shape::*;
+mod builder_for;
+pub use builder_for::*;
+mod builtin;
+pub use builtin::*;
+mod bundle;
+pub use bundle::*;
+mod delegate;
+pub use delegate::*;
+mod doc_item;
+pub use doc_item::*;
+mod explore;
+pub use explore::*;
+mod extend_enum;
+pub use extend_enum::*;
+mod group;
+pub use group::*;
+mod import_file;
+pub use import_file::*;
+mod inherit_impl;
+pub use inherit_impl::*;
+mod mirror_type;
+pub use mirror_type::*;
+mod mixin;
+pub use mixin::*;
+mod mock_trait;
+pub use mock_trait::*;
+mod parse_cache;
+pub use parse_cache::*;
+mod pipeline;
+pub use pipeline::*;
+mod pretty;
+pub use pretty::*;
+mod reflect;
+pub use reflect::*;
+mod remote_mirror;
+pub use remote_mirror::*;
+mod scan;
+pub use scan::*;
+mod stringify_item;
+pub use stringify_item::*;
+mod template;
+pub use template::*;
+mod trace;
+pub use trace::*;
+
 /// Constant used to load the configured location for `macro_magic` that will be used in
 /// generated macro code.
 ///
@@ -41,8 +90,31 @@ mod keywords {
     custom_keyword!(proc_macro);
     custom_keyword!(proc_macro_derive);
 
+    custom_keyword!(item);
+    custom_keyword!(source);
+    custom_keyword!(extra);
+    custom_keyword!(args);
+
     // WARNING: Must be kept same as in macro expansions
     custom_keyword!(__private_macro_magic_tokens_forwarded);
+    custom_keyword!(__private_macro_magic_decl_forward);
+    custom_keyword!(__private_macro_magic_args_forward);
+    custom_keyword!(__private_macro_magic_pipeline);
+    custom_keyword!(__private_macro_magic_deps);
+    custom_keyword!(__private_macro_magic_group);
+    custom_keyword!(with_deps);
+    custom_keyword!(deref);
+    custom_keyword!(field);
+    custom_keyword!(ty);
+    custom_keyword!(to);
+    custom_keyword!(signature);
+    custom_keyword!(members);
+    custom_keyword!(pretty);
+    custom_keyword!(ignore);
+    custom_keyword!(attrs);
+    custom_keyword!(docs);
+    custom_keyword!(visibility);
+    custom_keyword!(group);
 }
 
 /// Used to parse args that were passed to [`forward_tokens_internal`] and
@@ -65,26 +137,164 @@ impl ToTokens for ForwardTokensExtraArg {
     }
 }
 
+/// Specifies which other `#[export_tokens]` items an exported item depends on, as recorded
+/// by `#[export_tokens(with_deps)]`.
+#[derive(Clone)]
+pub enum ExportDeps {
+    /// No dependencies are recorded (the default)
+    None,
+    /// `with_deps` was specified without a list, so the (non-`std`) types referenced by the
+    /// item are recorded on a best-effort basis (see [`referenced_type_paths`])
+    Auto,
+    /// The dependencies were listed explicitly via `with_deps(A, b::B, ..)`
+    Explicit(Vec<Path>),
+}
+
+/// Used to parse the args of [`export_tokens_internal`], i.e. `#[export_tokens]`,
+/// `#[export_tokens(SomeIdent)]`, `#[export_tokens(with_deps(Timeout, Retry))]`,
+/// `#[export_tokens(SomeIdent, with_deps(Timeout, Retry))]` or
+/// `#[export_tokens(group = plugins)]`.
+///
+/// You shouldn't need to use this directly.
+pub struct ExportTokensArgs {
+    /// The optional override ident the item will be exported under
+    pub ident: Option<Ident>,
+    /// The dependencies that will be recorded for the item
+    pub deps: ExportDeps,
+    /// The export group the item belongs to, as declared by an enclosing
+    /// `#[export_group(..)]` module (see [`export_group_internal`])
+    pub group: Option<Ident>,
+}
+
+impl syn::parse::Parse for ExportTokensArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident = match input.peek(keywords::with_deps)
+            || (input.peek(keywords::group) && input.peek2(Token![=]))
+        {
+            true => None,
+            false => input.parse::<Option<Ident>>()?,
+        };
+        let mut deps = ExportDeps::None;
+        let mut group = None;
+        let mut first = ident.is_none();
+        while !input.is_empty() {
+            if !first {
+                input.parse::<Comma>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+            first = false;
+            if input.parse::<Option<keywords::with_deps>>()?.is_some() {
+                deps = ExportDeps::Auto;
+                if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    deps = ExportDeps::Explicit(
+                        content
+                            .parse_terminated(Path::parse, Comma)?
+                            .into_iter()
+                            .collect(),
+                    );
+                }
+            } else if input.parse::<Option<keywords::group>>()?.is_some() {
+                input.parse::<Token![=]>()?;
+                group = Some(input.parse()?);
+            } else {
+                return Err(input.error("expected `with_deps` or `group = ..`"));
+            }
+        }
+        Ok(ExportTokensArgs { ident, deps, group })
+    }
+}
+
+impl ToTokens for ExportTokensArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let mut args = Vec::new();
+        if let Some(ident) = &self.ident {
+            args.push(quote!(#ident));
+        }
+        match &self.deps {
+            ExportDeps::None => (),
+            ExportDeps::Auto => args.push(quote!(with_deps)),
+            ExportDeps::Explicit(deps) => args.push(quote!(with_deps(#(#deps),*))),
+        }
+        if let Some(group) = &self.group {
+            args.push(quote!(group = #group));
+        }
+        tokens.extend(quote!(#(#args),*));
+    }
+}
+
 /// Used to parse args that were passed to [`forward_tokens_internal`].
 ///
+/// The accepted forms are `source, target`, optionally followed by an override `mm_path`,
+/// optionally followed by _one_ of `{ extra }` (attribute mode), `extra = { .. }`
+/// (declarative mode) or `args = { .. }` (function-like mode with user-supplied arguments).
+///
 /// You shouldn't need to use this directly.
-#[derive(Parse)]
 pub struct ForwardTokensArgs {
     /// The path of the item whose tokens are being forwarded
     pub source: Path,
-    _comma1: Comma,
     /// The path of the macro that will receive the forwarded tokens
     pub target: Path,
-    _comma2: Option<Comma>,
     /// Contains the override path that will be used instead of `::macro_magic`, if specified.
-    #[parse_if(_comma2.is_some())]
     pub mm_path: Option<Path>,
-    _comma3: Option<Comma>,
     /// Optional extra data. This is how [`import_tokens_attr_internal`] passes the item the
     /// attribute macro is attached to, but this can be repurposed for other things potentially as
     /// it wraps a token stream.
-    #[parse_if(_comma3.is_some())]
+    ///
+    /// Note that specifying this causes the target to be invoked as an attribute macro.
     pub extra: Option<ForwardTokensExtraArg>,
+    /// Specified via `extra = { .. }`. When present, the target is treated as a
+    /// `macro_rules!` macro and receives the structured input described in
+    /// [`DeclForwardedTokens`], with these tokens in its `extra` fragment.
+    pub decl_extra: Option<ForwardTokensExtraArg>,
+    /// Specified via `args = { .. }`. When present, the target is invoked as a function-like
+    /// macro that receives these tokens alongside the forwarded item, as described in
+    /// [`ForwardedItemWithArgs`]. Unlike [`ForwardTokensArgs::extra`], this does not switch
+    /// the target into attribute mode.
+    pub args: Option<ForwardTokensExtraArg>,
+}
+
+impl syn::parse::Parse for ForwardTokensArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse::<Path>()?;
+        input.parse::<Comma>()?;
+        let target = input.parse::<Path>()?;
+        let mut args = ForwardTokensArgs {
+            source,
+            target,
+            mm_path: None,
+            extra: None,
+            decl_extra: None,
+            args: None,
+        };
+        while input.parse::<Option<Comma>>()?.is_some() && !input.is_empty() {
+            if args.extra.is_some() || args.decl_extra.is_some() || args.args.is_some() {
+                return Err(input.error("unexpected tokens after forwarded extra data"));
+            }
+            if input.peek(keywords::extra) && input.peek2(Token![=]) {
+                input.parse::<keywords::extra>()?;
+                input.parse::<Token![=]>()?;
+                args.decl_extra = Some(input.parse()?);
+            } else if input.peek(keywords::args) && input.peek2(Token![=]) {
+                input.parse::<keywords::args>()?;
+                input.parse::<Token![=]>()?;
+                args.args = Some(input.parse()?);
+            } else if input.peek(Brace) {
+                args.extra = Some(input.parse()?);
+            } else if args.mm_path.is_none() {
+                args.mm_path = Some(input.parse()?);
+            } else {
+                return Err(input.error("expected `{ .. }`, `extra = { .. }` or `args = { .. }`"));
+            }
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `,`"));
+        }
+        Ok(args)
+    }
 }
 
 /// Used to parse args that were passed to [`forward_tokens_inner_internal`].
@@ -105,6 +315,145 @@ pub struct ForwardedTokens {
     pub extra: Option<ForwardTokensExtraArg>,
 }
 
+/// Describes the input that a `macro_rules!` target receives when tokens are forwarded to it
+/// via `forward_tokens!(path, target, extra = { .. })`.
+///
+/// The target is invoked with the following stable shape:
+///
+/// ```ignore
+/// target! {
+///     item = { /* the tokens of the exported item */ },
+///     source = { /* the path that was passed to forward_tokens! */ },
+///     extra = { /* the tokens passed via `extra = { .. }` */ }
+/// }
+/// ```
+///
+/// which can be matched by a declarative macro like so:
+///
+/// ```ignore
+/// macro_rules! my_decl_macro {
+///     (item = { $item:item }, source = { $source:path }, extra = { $($extra:tt)* }) => {
+///         // ..
+///     };
+/// }
+/// ```
+///
+/// This struct can also be used to parse that same input from within a proc macro.
+#[derive(Parse)]
+pub struct DeclForwardedTokens {
+    _item_kw: keywords::item,
+    _eq1: Token![=],
+    #[brace]
+    _item_brace: Brace,
+    /// The item whose tokens are being forwarded
+    #[inside(_item_brace)]
+    pub item: Item,
+    _comma1: Comma,
+    _source_kw: keywords::source,
+    _eq2: Token![=],
+    #[brace]
+    _source_brace: Brace,
+    /// The path that was used to refer to the item whose tokens are being forwarded
+    #[inside(_source_brace)]
+    pub source: Path,
+    _comma2: Comma,
+    _extra_kw: keywords::extra,
+    _eq3: Token![=],
+    #[brace]
+    _extra_brace: Brace,
+    /// The extra tokens that were passed via `extra = { .. }`
+    #[inside(_extra_brace)]
+    pub extra: TokenStream2,
+}
+
+/// The payload that [`forward_tokens_internal`] passes through the `extra` arm of an
+/// `#[export_tokens]` macro when forwarding to a `macro_rules!` target.
+#[derive(Parse)]
+struct DeclForwardPayload {
+    _keyword: keywords::__private_macro_magic_decl_forward,
+    #[brace]
+    _source_brace: Brace,
+    #[inside(_source_brace)]
+    source: TokenStream2,
+    #[brace]
+    _extra_brace: Brace,
+    #[inside(_extra_brace)]
+    extra: TokenStream2,
+}
+
+/// Describes the input that a function-like target receives when tokens are forwarded to it
+/// via `forward_tokens!(path, target, args = { .. })`.
+///
+/// The target is invoked as `target! { __private_macro_magic_tokens_forwarded <item>, { <args>
+/// } }`. Macros defined with `#[import_tokens_proc]` split the args off automatically (see
+/// [`split_forwarded_args`]), exposing them via the `__forwarded_args` variable, while
+/// hand-written proc macros can parse the
+/// tokens following the `__private_macro_magic_tokens_forwarded` keyword using this struct.
+pub struct ForwardedItemWithArgs {
+    /// The item whose tokens are being forwarded
+    pub item: Item,
+    /// The user-supplied args, or an empty [`TokenStream2`] if no args were forwarded
+    pub args: TokenStream2,
+}
+
+impl syn::parse::Parse for ForwardedItemWithArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let item = input.parse::<Item>()?;
+        let args = match input.parse::<Option<Comma>>()? {
+            Some(_) => input.parse::<ForwardTokensExtraArg>()?.stream,
+            None => TokenStream2::new(),
+        };
+        Ok(ForwardedItemWithArgs { item, args })
+    }
+}
+
+/// Splits the `, { <args> }` appended by `forward_tokens!(path, target, args = { .. })` off
+/// of the forwarded item `tokens`, returning the item tokens along with the args, or the
+/// tokens untouched and an empty [`TokenStream2`] if no args were forwarded.
+///
+/// Unlike parsing [`ForwardedItemWithArgs`], the item itself is never parsed. Items always
+/// end in `;` or a braced block, which tells the args apart from a trailing where clause
+/// comma such as `where T: Copy, { .. }`.
+pub fn split_forwarded_args(tokens: TokenStream2) -> (TokenStream2, TokenStream2) {
+    let token_trees: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    match token_trees.as_slice() {
+        [item @ .., last, TokenTree::Punct(comma), TokenTree::Group(args)]
+            if comma.as_char() == ','
+                && args.delimiter() == Delimiter::Brace
+                && match last {
+                    TokenTree::Punct(punct) => punct.as_char() == ';',
+                    TokenTree::Group(group) => group.delimiter() == Delimiter::Brace,
+                    _ => false,
+                } =>
+        {
+            let mut item = TokenStream2::from_iter(item.iter().cloned());
+            item.extend([last.clone()]);
+            (item, args.stream())
+        }
+        _ => (tokens, TokenStream2::new()),
+    }
+}
+
+/// The payload that [`forward_tokens_internal`] passes through the `extra` arm of an
+/// `#[export_tokens]` macro when forwarding user-supplied args to a function-like target.
+#[derive(Parse)]
+struct ArgsForwardPayload {
+    _keyword: keywords::__private_macro_magic_args_forward,
+    #[brace]
+    _args_brace: Brace,
+    #[inside(_args_brace)]
+    args: TokenStream2,
+}
+
+/// Returns whether the first token of the specified [`TokenStream2`] is the keyword `K`.
+fn starts_with_keyword<K: syn::parse::Parse>(tokens: &TokenStream2) -> bool {
+    tokens
+        .clone()
+        .into_iter()
+        .next()
+        .is_some_and(|token| parse2::<K>(token.into()).is_ok())
+}
+
 /// Used to parse args passed to the inner pro macro auto-generated by
 /// [`import_tokens_attr_internal`].
 ///
@@ -449,6 +798,45 @@ fn new_unique_export_tokens_ident(ident: &Ident) -> Ident {
     Ident::new(ident_string.as_str(), Span::call_site())
 }
 
+/// Returns the kind of `item` as it is written in the source (i.e. `struct` or `fn`) along with
+/// the ident it is exported under by [`export_tokens_internal`] when no override ident is
+/// specified, which is [`None`] for items that have no ident of their own (such as impl
+/// blocks).
+pub(crate) fn item_kind_and_ident(item: &Item) -> (&'static str, Option<&Ident>) {
+    match item {
+        Item::Const(item_const) => ("const", Some(&item_const.ident)),
+        Item::Enum(item_enum) => ("enum", Some(&item_enum.ident)),
+        Item::ExternCrate(item_extern_crate) => ("extern crate", Some(&item_extern_crate.ident)),
+        Item::Fn(item_fn) => ("fn", Some(&item_fn.sig.ident)),
+        // note this one might not have an Ident as well
+        Item::Macro(item_macro) => ("macro", item_macro.ident.as_ref()),
+        Item::Mod(item_mod) => ("mod", Some(&item_mod.ident)),
+        Item::Static(item_static) => ("static", Some(&item_static.ident)),
+        Item::Struct(item_struct) => ("struct", Some(&item_struct.ident)),
+        Item::Trait(item_trait) => ("trait", Some(&item_trait.ident)),
+        Item::TraitAlias(item_trait_alias) => ("trait alias", Some(&item_trait_alias.ident)),
+        Item::Type(item_type) => ("type", Some(&item_type.ident)),
+        Item::Union(item_union) => ("union", Some(&item_union.ident)),
+        Item::Impl(_) => ("impl", None),
+        // Item::ForeignMod(item_foreign_mod) => None,
+        // Item::Use(item_use) => None,
+        // Item::Verbatim(_) => None,
+        _ => ("item", None),
+    }
+}
+
+/// Returns the ident an item is exported under by [`export_tokens_internal`] when no override
+/// ident is specified, or [`None`] for items that have no ident of their own (such as impl
+/// blocks), which must be given one, i.e. `#[export_tokens(MyIdent)]`.
+pub fn export_tokens_item_ident(item: &Item) -> Option<Ident> {
+    item_kind_and_ident(item).1.cloned()
+}
+
+/// Returns the kind of `item` as it is written in the source, i.e. `struct` or `fn`.
+pub(crate) fn item_kind(item: &Item) -> &'static str {
+    item_kind_and_ident(item).0
+}
+
 /// The internal code behind the `#[export_tokens]` attribute macro.
 ///
 /// The `attr` variable contains the tokens for the optional naming [`struct@Ident`] (necessary
@@ -469,36 +857,45 @@ pub fn export_tokens_internal<T: Into<TokenStream2>, E: Into<TokenStream2>>(
     hide_exported_ident: bool,
 ) -> Result<TokenStream2> {
     let attr = attr.into();
-    let item: Item = parse2(tokens.into())?;
-    let ident = match item.clone() {
-        Item::Const(item_const) => Some(item_const.ident),
-        Item::Enum(item_enum) => Some(item_enum.ident),
-        Item::ExternCrate(item_extern_crate) => Some(item_extern_crate.ident),
-        Item::Fn(item_fn) => Some(item_fn.sig.ident),
-        Item::Macro(item_macro) => item_macro.ident, // note this one might not have an Ident as well
-        Item::Mod(item_mod) => Some(item_mod.ident),
-        Item::Static(item_static) => Some(item_static.ident),
-        Item::Struct(item_struct) => Some(item_struct.ident),
-        Item::Trait(item_trait) => Some(item_trait.ident),
-        Item::TraitAlias(item_trait_alias) => Some(item_trait_alias.ident),
-        Item::Type(item_type) => Some(item_type.ident),
-        Item::Union(item_union) => Some(item_union.ident),
-        // Item::ForeignMod(item_foreign_mod) => None,
-        // Item::Use(item_use) => None,
-        // Item::Impl(item_impl) => None,
-        // Item::Verbatim(_) => None,
-        _ => None,
+    let tokens = tokens.into();
+    let item: Item = parse2(tokens.clone())?;
+    let ident = export_tokens_item_ident(&item);
+    let args = parse2::<ExportTokensArgs>(attr)?;
+    if let Some(group) = args.group {
+        return Err(Error::new(
+            group.span(),
+            format!(
+                "`group = {}` can only be used on items directly inside an inline module \
+                marked with `#[export_group({})]`",
+                group, group
+            ),
+        ));
+    }
+    let ident = match (args.ident, ident) {
+        (Some(ident), _) | (None, Some(ident)) => ident,
+        (None, None) => return Err(Error::new(
+            item.span(),
+            "this item type requires an ident to be specified, i.e. `#[export_tokens(MyIdent)]`",
+        )),
     };
-    let ident = match ident {
-        Some(ident) => {
-            if parse2::<Nothing>(attr.clone()).is_ok() {
-                ident
-            } else {
-                parse2::<Ident>(attr)?
+    let deps = match args.deps {
+        ExportDeps::None => Vec::new(),
+        ExportDeps::Auto => {
+            let deps = referenced_type_paths(&item);
+            if deps.is_empty() {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "no referenced `#[export_tokens]` paths were found in this item, \
+                    dependencies can be listed explicitly via `with_deps(path, ..)`",
+                ));
             }
+            deps
         }
-        None => parse2::<Ident>(attr)?,
-    };
+        ExportDeps::Explicit(deps) => deps,
+    }
+    .into_iter()
+    .map(|dep| dep_path_tokens(&dep));
+    let deps_keyword = keywords::__private_macro_magic_deps::default();
     let macro_ident = new_unique_export_tokens_ident(&ident);
     let ident = if hide_exported_ident {
         export_tokens_macro_ident(&ident)
@@ -516,6 +913,19 @@ pub fn export_tokens_internal<T: Into<TokenStream2>, E: Into<TokenStream2>>(
         #[doc(hidden)]
         #[macro_export]
         macro_rules! #macro_ident {
+            // arm that also provides the recorded dependencies (used by forward_bundle)
+            (
+                #deps_keyword,
+                $(::)?$($callback:ident)::*,
+                { $( $extra:tt )* }
+            ) => {
+                $($callback)::*! {
+                    #deps_keyword
+                    { #item }
+                    { #(#deps),* }
+                    { $( $extra )* }
+                }
+            };
             // arm with extra support (used by attr)
             (
                 $(::)?$($tokens_var:ident)::*,
@@ -539,6 +949,7 @@ pub fn export_tokens_internal<T: Into<TokenStream2>, E: Into<TokenStream2>>(
         pub use #macro_ident as #ident;
         #item_emit
     };
+    trace_stage("export_tokens", Some(&ident.into()), None, &tokens, &output);
     Ok(output)
 }
 
@@ -590,26 +1001,38 @@ pub fn export_tokens_alias_internal<T: Into<TokenStream2>>(
 /// ```
 /// where `my_tokens` contains the tokens of `ExportedItem`.
 pub fn import_tokens_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
-    let args = parse2::<ImportTokensArgs>(tokens.into())?;
+    let tokens = tokens.into();
+    let args = parse2::<ImportTokensArgs>(tokens.clone())?;
     let source_path = export_tokens_macro_path(&args.source_path);
     let inner_macro_path = private_path(&quote!(import_tokens_inner));
     let tokens_var_ident = args.tokens_var_ident;
-    Ok(quote! {
+    let output = quote! {
         #source_path! { #tokens_var_ident, #inner_macro_path }
-    })
+    };
+    trace_stage(
+        "import_tokens",
+        Some(&args.source_path),
+        Some(&inner_macro_path),
+        &tokens,
+        &output,
+    );
+    Ok(output)
 }
 
 /// The internal implementation for the `import_tokens_inner` macro.
 ///
 /// You shouldn't need to call this in any circumstances but it is provided just in case.
 pub fn import_tokens_inner_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
-    let parsed = parse2::<ImportedTokens>(tokens.into())?;
+    let tokens = tokens.into();
+    let parsed = parse2::<ImportedTokens>(tokens.clone())?;
     let tokens_string = parsed.item.to_token_stream().to_string();
     let ident = parsed.tokens_var_ident;
     let token_stream_2 = private_path(&quote!(TokenStream2));
-    Ok(quote! {
+    let output = quote! {
         let #ident = #tokens_string.parse::<#token_stream_2>().expect("failed to parse quoted tokens");
-    })
+    };
+    trace_stage("import_tokens_inner", None, None, &tokens, &output);
+    Ok(output)
 }
 
 /// The internal implementation for the `forward_tokens` macro.
@@ -619,7 +1042,8 @@ pub fn forward_tokens_internal<T: Into<TokenStream2>>(
     tokens: T,
     hidden_source_path: bool,
 ) -> Result<TokenStream2> {
-    let args = parse2::<ForwardTokensArgs>(tokens.into())?;
+    let tokens = tokens.into();
+    let args = parse2::<ForwardTokensArgs>(tokens.clone())?;
     let mm_path = match args.mm_path {
         Some(path) => path,
         None => macro_magic_root(),
@@ -627,48 +1051,119 @@ pub fn forward_tokens_internal<T: Into<TokenStream2>>(
     let source_path = if hidden_source_path {
         export_tokens_macro_path(&args.source)
     } else {
-        args.source
+        args.source.clone()
     };
     let target_path = args.target;
-    if let Some(extra) = args.extra {
-        Ok(quote! {
+    let output = if let Some(decl_extra) = args.decl_extra {
+        let decl_keyword = keywords::__private_macro_magic_decl_forward::default();
+        let source = &args.source;
+        let extra = decl_extra.stream;
+        quote! {
+            #source_path! {
+                #target_path,
+                #mm_path::__private::forward_tokens_inner,
+                { #decl_keyword { #source } { #extra } }
+            }
+        }
+    } else if let Some(user_args) = args.args {
+        let args_keyword = keywords::__private_macro_magic_args_forward::default();
+        let user_args = user_args.stream;
+        quote! {
+            #source_path! {
+                #target_path,
+                #mm_path::__private::forward_tokens_inner,
+                { #args_keyword { #user_args } }
+            }
+        }
+    } else if let Some(extra) = args.extra {
+        quote! {
             #source_path! {
                 #target_path,
                 #mm_path::__private::forward_tokens_inner,
                 #extra
             }
-        })
+        }
     } else {
-        Ok(quote! {
+        quote! {
             #source_path! { #target_path, #mm_path::__private::forward_tokens_inner }
-        })
-    }
+        }
+    };
+    trace_stage(
+        "forward_tokens",
+        Some(&args.source),
+        Some(&target_path),
+        &tokens,
+        &output,
+    );
+    Ok(output)
 }
 
 /// Used by [`forward_tokens_internal`].
 pub fn forward_tokens_inner_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
-    let parsed = parse2::<ForwardedTokens>(tokens.into())?;
+    let tokens = tokens.into();
+    let parsed = parse2::<ForwardedTokens>(tokens.clone())?;
     let target_path = parsed.target_path;
     let imported_tokens = parsed.item;
     let tokens_forwarded_keyword = keywords::__private_macro_magic_tokens_forwarded::default();
     let pound = Punct::new('#', Spacing::Alone);
-    match parsed.extra {
+    let output = match parsed.extra {
+        // declarative payload, so expand to the structured `macro_rules!` input
+        Some(extra)
+            if starts_with_keyword::<keywords::__private_macro_magic_decl_forward>(
+                &extra.stream,
+            ) =>
+        {
+            let payload = parse2::<DeclForwardPayload>(extra.stream)?;
+            let source = payload.source;
+            let extra = payload.extra;
+            quote! {
+                #target_path! {
+                    item = { #imported_tokens },
+                    source = { #source },
+                    extra = { #extra }
+                }
+            }
+        }
+        // user-supplied args, so expand to proc macro with the args alongside the item
+        Some(extra)
+            if starts_with_keyword::<keywords::__private_macro_magic_args_forward>(
+                &extra.stream,
+            ) =>
+        {
+            let payload = parse2::<ArgsForwardPayload>(extra.stream)?;
+            let user_args = payload.args;
+            quote! {
+                #target_path! {
+                    #tokens_forwarded_keyword
+                    #imported_tokens,
+                    { #user_args }
+                }
+            }
+        }
         // some extra, used by attr, so expand to attribute macro
-        Some(extra) => Ok(quote! {
+        Some(extra) => quote! {
             #pound [#target_path(
                 #tokens_forwarded_keyword
                 #imported_tokens,
                 #extra
             )] type __Discarded = ();
-        }),
+        },
         // no extra, used by proc, import_tokens, etc, so expand to proc macro
-        None => Ok(quote! {
+        None => quote! {
             #target_path! {
                 #tokens_forwarded_keyword
                 #imported_tokens
             }
-        }),
-    }
+        },
+    };
+    trace_stage(
+        "forward_tokens_inner",
+        None,
+        Some(&target_path),
+        &tokens,
+        &output,
+    );
+    Ok(output)
 }
 
 /// The internal implementation for the `#[with_custom_parsing(..)` attribute macro.
@@ -742,6 +1237,64 @@ pub fn with_custom_parsing_internal<T1: Into<TokenStream2>, T2: Into<TokenStream
     Ok(quote!(#item_fn))
 }
 
+/// The internal implementation for the `#[with_parse_cache]` attribute macro.
+///
+/// Like [`with_custom_parsing_internal`], this only validates the attached proc macro
+/// attribute definition and re-orders its attributes. The real implementation can be found in
+/// [`import_tokens_attr_internal`], which makes the generated macro parse the imported item
+/// with [`parse_attr_item_cached`] instead of [`AttrItemWithExtra`].
+///
+/// The `import_tokens_att_name` argument is used when generating error messages and matching
+/// against the `#[import_tokens_attr]` macro this is to be used with.
+pub fn with_parse_cache_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+    import_tokens_attr_name: &'static str,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    if !attr.is_empty() {
+        return Err(Error::new(
+            attr.span(),
+            "#[with_parse_cache] does not take any arguments",
+        ));
+    }
+
+    // verify that we are attached to a valid #[import_tokens_attr] proc macro def
+    let proc_macro = parse_proc_macro_variant(tokens, ProcMacroType::Attribute)?;
+    let has_attr = |name: &str| {
+        proc_macro.proc_fn.attrs.iter().any(|attr| {
+            attr.meta
+                .path()
+                .segments
+                .last()
+                .is_some_and(|seg| seg.ident == name)
+        })
+    };
+    if !has_attr(import_tokens_attr_name) {
+        return Err(Error::new(
+            Span::call_site(),
+            format!(
+                "Can only be attached to an attribute proc macro marked with `#[{}]`",
+                import_tokens_attr_name
+            ),
+        ));
+    }
+
+    // ensure there is only one `#[with_parse_cache]`
+    if has_attr("with_parse_cache") {
+        return Err(Error::new(
+            Span::call_site(),
+            "Only one instance of #[with_parse_cache] can be attached at a time.",
+        ));
+    }
+
+    // emit original item unchanged now that parsing has passed
+    let mut item_fn = proc_macro.proc_fn;
+    item_fn.attrs.push(parse_quote!(#[with_parse_cache]));
+
+    Ok(quote!(#item_fn))
+}
+
 /// Parses the (attribute) args of [`import_tokens_attr_internal`] and
 /// [`import_tokens_proc_internal`], which can now evaluate to either a `Path` or an `Expr`
 /// that is expected to be able to be placed in a `String::from(x)`.
@@ -828,6 +1381,30 @@ pub fn import_tokens_attr_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2
         }
     };
 
+    // handle the parse cache, if applicable
+    let combined_args = if let Some(index) = proc_macro.proc_fn.attrs.iter().position(|attr| {
+        if let Some(seg) = attr.meta.path().segments.last() {
+            return seg.ident == "with_parse_cache";
+        }
+        false
+    }) {
+        proc_macro.proc_fn.attrs.remove(index);
+        quote! {
+            let __combined_args = match #mm_path::mm_core::parse_attr_item_cached(#attr_ident) {
+                Ok(args) => args,
+                Err(err) => return err.to_compile_error().into(),
+            };
+
+            let #attr_ident: proc_macro::TokenStream = __combined_args.imported_item.into();
+        }
+    } else {
+        quote! {
+            let __combined_args = #mm_path::__private::syn::parse_macro_input!(#attr_ident as #mm_path::mm_core::AttrItemWithExtra);
+
+            let #attr_ident: proc_macro::TokenStream = __combined_args.imported_item.to_token_stream().into();
+        }
+    };
+
     // outer macro
     let orig_sig = proc_macro.proc_fn.sig;
     let orig_stmts = proc_macro.proc_fn.block.stmts;
@@ -848,9 +1425,7 @@ pub fn import_tokens_attr_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2
         *
         pub #orig_sig {
             pub #inner_sig {
-                let __combined_args = #mm_path::__private::syn::parse_macro_input!(#attr_ident as #mm_path::mm_core::AttrItemWithExtra);
-
-                let #attr_ident: proc_macro::TokenStream = __combined_args.imported_item.to_token_stream().into();
+                #combined_args
                 let #tokens_ident: proc_macro::TokenStream = __combined_args.tokens_ident.into();
                 let __source_path: proc_macro::TokenStream = __combined_args.source_path.into();
                 let __custom_tokens: proc_macro::TokenStream = __combined_args.custom_tokens.into();
@@ -879,7 +1454,14 @@ pub fn import_tokens_attr_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2
             });
 
             if forwarded {
-                #inner_macro_ident(attr_minus_first_token)
+                let __stage = stringify!(#orig_sig_ident);
+                if !#mm_path::mm_core::trace_enabled(__stage) {
+                    return #inner_macro_ident(attr_minus_first_token);
+                }
+                let __input: TokenStream2 = attr_minus_first_token.clone().into();
+                let __output = #inner_macro_ident(attr_minus_first_token);
+                #mm_path::mm_core::trace_stage(__stage, None, None, &__input, &__output.clone().into());
+                __output
             } else {
                 let attached_item = syn::parse_macro_input!(#tokens_ident as syn::Item);
                 let attached_item = attached_item.to_token_stream();
@@ -961,6 +1543,10 @@ pub fn import_tokens_proc_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2
         *
         pub #orig_sig {
             #inner_sig {
+                let (#tokens_ident, __forwarded_args) = #mm_path::mm_core::split_forwarded_args(#tokens_ident.into());
+                let #tokens_ident: proc_macro::TokenStream = #tokens_ident.into();
+                let __forwarded_args: proc_macro::TokenStream = __forwarded_args.into();
+
                 #(#orig_stmts)
                 *
             }
@@ -984,7 +1570,14 @@ pub fn import_tokens_proc_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2
             });
 
             if forwarded {
-                #inner_macro_ident(tokens_minus_first)
+                let __stage = stringify!(#orig_sig_ident);
+                if !#mm_path::mm_core::trace_enabled(__stage) {
+                    return #inner_macro_ident(tokens_minus_first);
+                }
+                let __input: TokenStream2 = tokens_minus_first.clone().into();
+                let __output = #inner_macro_ident(tokens_minus_first);
+                #mm_path::mm_core::trace_stage(__stage, None, None, &__input, &__output.clone().into());
+                __output
             } else {
                 use #mm_path::__private::*;
                 use #mm_path::__private::quote::ToTokens;
@@ -1088,6 +1681,74 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn export_tokens_internal_with_deps() {
+        let output = export_tokens_internal(
+            quote!(with_deps),
+            quote!(
+                struct Config {
+                    timeout: Timeout,
+                    name: String,
+                }
+            ),
+            true,
+            true,
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("__private_macro_magic_deps { struct Config"));
+        assert!(output.contains("{ Timeout }"));
+        let err = export_tokens_internal(
+            quote!(with_deps),
+            quote!(
+                struct Config {
+                    name: String,
+                }
+            ),
+            true,
+            true,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("`with_deps(path, ..)`"));
+        let output = export_tokens_internal(
+            quote!(with_deps(Timeout)),
+            quote!(
+                struct Config {
+                    timeout: Timeout,
+                    name: String,
+                }
+            ),
+            true,
+            true,
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("__private_macro_magic_deps { struct Config"));
+        assert!(output.contains("{ Timeout }"));
+        let output = export_tokens_internal(
+            quote!(some_name, with_deps(crate::a::Timeout, Retry)),
+            quote!(
+                struct Config {}
+            ),
+            true,
+            true,
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("some_name"));
+        assert!(output.contains("{ $ crate :: a :: Timeout , Retry }"));
+        assert!(export_tokens_internal(
+            quote!(with_deps(Timeout), extra),
+            quote!(
+                struct Config {}
+            ),
+            true,
+            true
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_export_tokens_no_emit() {
         assert!(export_tokens_internal(
@@ -1202,6 +1863,104 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn forward_tokens_args_forms() {
+        let args = parse2::<ForwardTokensArgs>(quote!(my_crate::Item, my_macro)).unwrap();
+        assert!(args.mm_path.is_none() && args.extra.is_none() && args.decl_extra.is_none());
+        let args =
+            parse2::<ForwardTokensArgs>(quote!(my_crate::Item, my_macro, ::some::path, { a b }))
+                .unwrap();
+        assert_eq!(
+            args.mm_path.to_token_stream().to_string(),
+            ":: some :: path"
+        );
+        assert_eq!(args.extra.unwrap().stream.to_string(), "a b");
+        let args =
+            parse2::<ForwardTokensArgs>(quote!(my_crate::Item, my_macro, extra = { a b })).unwrap();
+        assert!(args.mm_path.is_none() && args.extra.is_none());
+        assert_eq!(args.decl_extra.unwrap().stream.to_string(), "a b");
+        assert!(parse2::<ForwardTokensArgs>(quote!(a, b, { x }, extra = { y })).is_err());
+        assert!(parse2::<ForwardTokensArgs>(quote!(a, b, c, d)).is_err());
+    }
+
+    #[test]
+    fn forward_tokens_decl_round_trip() {
+        let forwarded =
+            forward_tokens_internal(quote!(my_crate::MyItem, my_macro, extra = { 1, 2 }), true)
+                .unwrap()
+                .to_string();
+        assert!(forwarded.contains("__private_macro_magic_decl_forward { my_crate :: MyItem }"));
+        let output = forward_tokens_inner_internal(quote! {
+            my_macro,
+            struct MyItem {},
+            { __private_macro_magic_decl_forward { my_crate::MyItem } { 1, 2 } }
+        })
+        .unwrap();
+        let Item::Macro(item_macro) = parse2::<Item>(output).unwrap() else {
+            panic!("expected a macro invocation");
+        };
+        let parsed = item_macro.mac.parse_body::<DeclForwardedTokens>().unwrap();
+        assert_eq!(
+            parsed.item.to_token_stream().to_string(),
+            "struct MyItem { }"
+        );
+        assert_eq!(
+            parsed.source.to_token_stream().to_string(),
+            "my_crate :: MyItem"
+        );
+        assert_eq!(parsed.extra.to_string(), "1 , 2");
+    }
+
+    #[test]
+    fn split_forwarded_args_only_splits_args() {
+        let (item, args) = split_forwarded_args(quote!(struct Foo { a: u8 }, { 1, two }));
+        assert_eq!(item.to_string(), "struct Foo { a : u8 }");
+        assert_eq!(args.to_string(), "1 , two");
+        let (item, args) = split_forwarded_args(quote!(
+            struct Foo;
+        ));
+        assert_eq!(item.to_string(), "struct Foo ;");
+        assert!(args.is_empty());
+        let tokens = quote!(
+            impl<T> Foo for T where T: Copy {}
+        );
+        let (item, args) = split_forwarded_args(tokens.clone());
+        assert_eq!(item.to_string(), tokens.to_string());
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn forward_tokens_args_round_trip() {
+        let args =
+            parse2::<ForwardTokensArgs>(quote!(my_crate::Item, my_macro, ::mm, args = { x }))
+                .unwrap();
+        assert!(args.extra.is_none() && args.decl_extra.is_none());
+        assert_eq!(args.args.unwrap().stream.to_string(), "x");
+        let forwarded =
+            forward_tokens_internal(quote!(my_crate::MyItem, my_macro, args = { x }), true)
+                .unwrap()
+                .to_string();
+        assert!(forwarded.contains("{ __private_macro_magic_args_forward { x } }"));
+        let output = forward_tokens_inner_internal(quote! {
+            my_macro,
+            struct MyItem {},
+            { __private_macro_magic_args_forward { x y } }
+        })
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            output,
+            "my_macro ! { __private_macro_magic_tokens_forwarded struct MyItem { } , { x y } }"
+        );
+        let parsed = parse2::<ForwardedItemWithArgs>(quote!(struct MyItem {}, { x y })).unwrap();
+        assert_eq!(parsed.args.to_string(), "x y");
+        let parsed = parse2::<ForwardedItemWithArgs>(quote!(
+            struct MyItem {}
+        ))
+        .unwrap();
+        assert!(parsed.args.is_empty());
+    }
+
     #[test]
     fn test_snake_case() {
         assert_eq!(to_snake_case("ThisIsATriumph"), "this_is_a_triumph");
@@ -0,0 +1,252 @@
+//! Contains the implementation behind the `mirror_type!` macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2,
+    spanned::Spanned,
+    Attribute, Error, Ident, Item, ItemStruct, Path, Result, Token,
+};
+
+use crate::{fields_binding, keywords, macro_magic_root, parse_forwarded_proc, trace_stage};
+
+/// Used to parse the args of [`mirror_type_internal`], i.e.
+/// `#[derive(Debug)] upstream::Foo as LocalFoo, deref`.
+///
+/// You shouldn't need to use this directly.
+pub struct MirrorTypeArgs {
+    /// Extra attributes that will be applied to the mirror, such as derives
+    pub attrs: Vec<Attribute>,
+    /// The path of the `#[export_tokens]` struct that will be mirrored
+    pub source: Path,
+    /// The ident of the mirror
+    pub ident: Ident,
+    /// Specified via `, deref`. When present, a `Deref` impl targeting the only field of the
+    /// mirror is generated.
+    pub deref: Option<keywords::deref>,
+}
+
+impl Parse for MirrorTypeArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let source = input.parse::<Path>()?;
+        input.parse::<Token![as]>()?;
+        let ident = input.parse::<Ident>()?;
+        let mut deref = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            deref = Some(input.parse::<keywords::deref>()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens, expected `upstream::Foo as LocalFoo`"));
+        }
+        Ok(MirrorTypeArgs {
+            attrs,
+            source,
+            ident,
+            deref,
+        })
+    }
+}
+
+impl ToTokens for MirrorTypeArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let attrs = &self.attrs;
+        let source = &self.source;
+        let ident = &self.ident;
+        tokens.extend(quote!(#(#attrs)* #source as #ident));
+        if let Some(deref) = &self.deref {
+            tokens.extend(quote!(, #deref));
+        }
+    }
+}
+
+/// Generates a local copy of the `foreign` struct as specified by `args`, preserving its
+/// generics, visibility, field visibility, `#[repr]` and doc attributes, along with `From`
+/// impls converting field-by-field between the copy and the foreign struct in both
+/// directions, and a `Deref` impl if requested.
+pub fn mirror_type_tokens(args: &MirrorTypeArgs, foreign: &ItemStruct) -> Result<TokenStream2> {
+    let source = &args.source;
+    let ident = &args.ident;
+    let mut mirror = foreign.clone();
+    mirror.ident = ident.clone();
+    mirror
+        .attrs
+        .retain(|attr| attr.path().is_ident("repr") || attr.path().is_ident("doc"));
+    mirror.attrs.splice(0..0, args.attrs.iter().cloned());
+    for field in mirror.fields.iter_mut() {
+        field.attrs.retain(|attr| attr.path().is_ident("doc"));
+    }
+    let (impl_generics, ty_generics, where_clause) = foreign.generics.split_for_impl();
+    let foreign_ty = quote!(#source #ty_generics);
+    let mirror_ty = quote!(#ident #ty_generics);
+    let foreign_binding = fields_binding(&source.to_token_stream(), &foreign.fields);
+    let mirror_binding = fields_binding(&quote!(#ident), &foreign.fields);
+    let self_binding = fields_binding(&quote!(Self), &foreign.fields);
+    let deref_impl = match &args.deref {
+        Some(deref) => {
+            let mut fields = foreign.fields.iter();
+            let (Some(field), None) = (fields.next(), fields.next()) else {
+                return Err(Error::new(
+                    deref.span(),
+                    format!(
+                        "`deref` requires `{}` to have exactly one field",
+                        source.to_token_stream()
+                    ),
+                ));
+            };
+            let target = &field.ty;
+            let member = match &field.ident {
+                Some(field_ident) => quote!(#field_ident),
+                None => quote!(0),
+            };
+            quote! {
+                impl #impl_generics ::core::ops::Deref for #mirror_ty #where_clause {
+                    type Target = #target;
+
+                    fn deref(&self) -> &Self::Target {
+                        &self.#member
+                    }
+                }
+            }
+        }
+        None => TokenStream2::new(),
+    };
+    Ok(quote! {
+        #mirror
+
+        impl #impl_generics ::core::convert::From<#foreign_ty> for #mirror_ty #where_clause {
+            fn from(value: #foreign_ty) -> Self {
+                let #foreign_binding = value;
+                #self_binding
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<#mirror_ty> for #foreign_ty #where_clause {
+            fn from(value: #mirror_ty) -> Self {
+                let #mirror_binding = value;
+                #self_binding
+            }
+        }
+
+        #deref_impl
+    })
+}
+
+/// Internal implementation of the `mirror_type!` macro.
+///
+/// When invoked by the user, `tokens` are parsed as [`MirrorTypeArgs`] and the tokens of the
+/// `#[export_tokens]` struct are forwarded back to `mirror_type!` along with these args. When
+/// invoked with forwarded tokens, the mirror is generated via [`mirror_type_tokens`].
+pub fn mirror_type_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output = match parse_forwarded_proc(&tokens) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<MirrorTypeArgs>(forwarded.args)?;
+            let Item::Struct(foreign) = forwarded.item else {
+                return Err(Error::new(
+                    args.source.span(),
+                    format!(
+                        "`{}` is not a struct, so it cannot be mirrored",
+                        args.source.to_token_stream()
+                    ),
+                ));
+            };
+            mirror_type_tokens(&args, &foreign)?
+        }
+        None => {
+            let args = parse2::<MirrorTypeArgs>(tokens.clone())?;
+            let mm_path = macro_magic_root();
+            let source = &args.source;
+            quote! {
+                #mm_path::forward_tokens! {
+                    #source,
+                    #mm_path::__private::mirror_type,
+                    #mm_path,
+                    args = { #args }
+                }
+            }
+        }
+    };
+    trace_stage("mirror_type", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn mirror_type_struct() {
+        let output = mirror_type_tokens(
+            &parse_quote!(
+                #[derive(Debug)]
+                upstream::Foo as LocalFoo
+            ),
+            &parse_quote! {
+                /// A foo.
+                #[derive(Clone)]
+                #[repr(C)]
+                pub struct Foo<'a, T: Copy> where T: Default {
+                    /// The name.
+                    #[serde(skip)]
+                    pub name: &'a str,
+                    pub(crate) value: T,
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.starts_with(
+            "# [derive (Debug)] # [doc = r\" A foo.\"] # [repr (C)] pub struct LocalFoo < 'a , T \
+            : Copy > where T : Default { # [doc = r\" The name.\"] pub name : & 'a str , pub \
+            (crate) value : T , }"
+        ));
+        assert!(output.contains(
+            "impl < 'a , T : Copy > :: core :: convert :: From < upstream :: Foo < 'a , T > > for \
+            LocalFoo < 'a , T > where T : Default"
+        ));
+        assert!(output.contains("let LocalFoo { name , value } = value ; Self { name , value }"));
+        assert!(!output.contains("Deref"));
+    }
+
+    #[test]
+    fn mirror_type_deref() {
+        let output = mirror_type_tokens(
+            &parse_quote!(upstream::Meters as LocalMeters, deref),
+            &parse_quote!(
+                pub struct Meters(pub f64);
+            ),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("type Target = f64 ;"));
+        assert!(output.contains("& self . 0"));
+        let Err(err) = mirror_type_tokens(
+            &parse_quote!(upstream::Point as LocalPoint, deref),
+            &parse_quote!(
+                pub struct Point(pub f64, pub f64);
+            ),
+        ) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "`deref` requires `upstream :: Point` to have exactly one field"
+        );
+    }
+
+    #[test]
+    fn mirror_type_forwards_args() {
+        let output = mirror_type_internal(quote!(a::Foo as Bar, deref))
+            .unwrap()
+            .to_string();
+        assert!(output.contains(":: macro_magic :: __private :: mirror_type"));
+        assert!(output.contains("args = { a :: Foo as Bar , deref }"));
+        assert!(mirror_type_internal(quote!(a::Foo)).is_err());
+        assert!(mirror_type_internal(quote!(a::Foo as Bar, other)).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+//! Contains the implementation behind the `#[mixin]` attribute macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::Parser, parse2, punctuated::Punctuated, spanned::Spanned, token::Comma, Error, Fields,
+    GenericParam, Generics, ItemStruct, Path, Result, Token, WhereClause,
+};
+
+use crate::{forward_to_builtin_attr, macro_magic_root, parse_forwarded_attr, trace_stage};
+
+/// Returns the name a [`GenericParam`] is referred to by, i.e. `'a`, `T` or `N`.
+fn generic_param_name(param: &GenericParam) -> String {
+    match param {
+        GenericParam::Lifetime(lifetime) => lifetime.lifetime.to_string(),
+        GenericParam::Type(type_param) => type_param.ident.to_string(),
+        GenericParam::Const(const_param) => const_param.ident.to_string(),
+    }
+}
+
+/// Merges the generic params and where-clause predicates of `foreign` into `local`.
+///
+/// Params that already exist in `local` (by name) are left as-is, and lifetimes are kept in
+/// front of all other params, as required by Rust.
+pub fn merge_generics(local: &mut Generics, foreign: &Generics) {
+    let mut params: Vec<GenericParam> = local.params.iter().cloned().collect();
+    for param in &foreign.params {
+        let name = generic_param_name(param);
+        if !params.iter().any(|p| generic_param_name(p) == name) {
+            params.push(param.clone());
+        }
+    }
+    params.sort_by_key(|param| !matches!(param, GenericParam::Lifetime(_)));
+    local.params = params.into_iter().collect();
+    if !local.params.is_empty() && local.lt_token.is_none() {
+        local.lt_token = Some(Default::default());
+        local.gt_token = Some(Default::default());
+    }
+    let Some(foreign_where) = &foreign.where_clause else {
+        return;
+    };
+    let local_where = local.where_clause.get_or_insert_with(|| WhereClause {
+        where_token: Default::default(),
+        predicates: Punctuated::new(),
+    });
+    for predicate in &foreign_where.predicates {
+        let predicate_str = predicate.to_token_stream().to_string();
+        if !local_where
+            .predicates
+            .iter()
+            .any(|p| p.to_token_stream().to_string() == predicate_str)
+        {
+            local_where.predicates.push(predicate.clone());
+        }
+    }
+}
+
+/// Merges the fields, generics and where-clause of the `foreign` struct (imported from
+/// `source`) into the `local` struct, returning an error for each field of `local` whose name
+/// conflicts with a field of `foreign`.
+///
+/// Named fields can only be merged into structs with named fields and tuple fields can only be
+/// merged into tuple structs, however unit structs can receive either kind of field, and
+/// merging a unit struct into any struct only merges its generics.
+pub fn merge_structs(
+    mut local: ItemStruct,
+    foreign: &ItemStruct,
+    source: &Path,
+) -> Result<ItemStruct> {
+    let source_str = source.to_token_stream().to_string();
+    match (&mut local.fields, &foreign.fields) {
+        (_, Fields::Unit) => (),
+        (Fields::Unit, foreign_fields) => {
+            local.fields = foreign_fields.clone();
+            local.semi_token = match local.fields {
+                Fields::Named(_) => None,
+                _ => Some(Default::default()),
+            };
+        }
+        (Fields::Named(local_fields), Fields::Named(foreign_fields)) => {
+            let mut errors: Option<Error> = None;
+            for field in &local_fields.named {
+                let Some(ident) = &field.ident else {
+                    continue;
+                };
+                if foreign_fields
+                    .named
+                    .iter()
+                    .any(|foreign_field| foreign_field.ident.as_ref() == Some(ident))
+                {
+                    let err = Error::new(
+                        ident.span(),
+                        format!(
+                            "field `{}` conflicts with a field of the same name in mixed-in \
+                            struct `{}`",
+                            ident, source_str
+                        ),
+                    );
+                    match &mut errors {
+                        Some(errors) => errors.combine(err),
+                        None => errors = Some(err),
+                    }
+                }
+            }
+            if let Some(errors) = errors {
+                return Err(errors);
+            }
+            local_fields
+                .named
+                .extend(foreign_fields.named.iter().cloned());
+        }
+        (Fields::Unnamed(local_fields), Fields::Unnamed(foreign_fields)) => {
+            local_fields
+                .unnamed
+                .extend(foreign_fields.unnamed.iter().cloned());
+        }
+        (Fields::Named(_), Fields::Unnamed(_)) => {
+            return Err(Error::new(
+                local.ident.span(),
+                format!(
+                    "cannot mix the tuple fields of `{}` into a struct with named fields",
+                    source_str
+                ),
+            ))
+        }
+        (Fields::Unnamed(_), Fields::Named(_)) => {
+            return Err(Error::new(
+                local.ident.span(),
+                format!(
+                    "cannot mix the named fields of `{}` into a tuple struct",
+                    source_str
+                ),
+            ))
+        }
+    }
+    merge_generics(&mut local.generics, &foreign.generics);
+    Ok(local)
+}
+
+/// Internal implementation of the `#[mixin]` attribute macro.
+///
+/// When invoked by the user, `attr` contains one or more paths of `#[export_tokens]` structs,
+/// and the tokens of the first one are forwarded back to `#[mixin]`. When invoked with
+/// forwarded tokens, the fields of the imported struct are merged into the attached struct
+/// and `#[mixin]` is re-applied with the remaining paths, if there are any.
+pub fn mixin_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    let tokens = tokens.into();
+    let output = match parse_forwarded_attr(&attr) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let source = parse2::<Path>(forwarded.source_path)?;
+            let foreign =
+                parse2::<ItemStruct>(forwarded.imported_item.to_token_stream()).map_err(|_| {
+                    Error::new(
+                        source.span(),
+                        format!(
+                            "`{}` is not a struct, so its fields cannot be mixed in",
+                            source.to_token_stream()
+                        ),
+                    )
+                })?;
+            let local = parse2::<ItemStruct>(forwarded.tokens_ident)?;
+            let merged = merge_structs(local, &foreign, &source)?;
+            let remaining = forwarded.custom_tokens;
+            match remaining.is_empty() {
+                true => merged.to_token_stream(),
+                false => {
+                    let mm_path = macro_magic_root();
+                    quote! {
+                        #[#mm_path::__private::mixin(#remaining)]
+                        #merged
+                    }
+                }
+            }
+        }
+        None => {
+            let paths = Punctuated::<Path, Token![,]>::parse_terminated.parse2(attr.clone())?;
+            let local = parse2::<ItemStruct>(tokens.clone()).map_err(|_| {
+                Error::new(tokens.span(), "`#[mixin]` can only be attached to a struct")
+            })?;
+            let mut paths = paths.into_iter();
+            let Some(first) = paths.next() else {
+                return Err(Error::new(
+                    attr.span(),
+                    "expected the path of at least one `#[export_tokens]` struct, i.e. \
+                    `#[mixin(some_crate::SomeStruct)]`",
+                ));
+            };
+            let remaining = Punctuated::<Path, Comma>::from_iter(paths);
+            forward_to_builtin_attr(
+                "mixin",
+                &first,
+                &local.to_token_stream(),
+                &remaining.to_token_stream(),
+            )
+        }
+    };
+    trace_stage("mixin", None, None, &quote!(#attr #tokens), &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn merge(local: ItemStruct, foreign: ItemStruct) -> Result<String> {
+        merge_structs(local, &foreign, &parse_quote!(a::Foreign))
+            .map(|merged| merged.to_token_stream().to_string())
+    }
+
+    #[test]
+    fn mixin_named_fields() {
+        let merged = merge(
+            parse_quote!(
+                struct Local {
+                    a: u32,
+                }
+            ),
+            parse_quote!(
+                struct Foreign {
+                    pub b: bool,
+                }
+            ),
+        )
+        .unwrap();
+        assert_eq!(merged, "struct Local { a : u32 , pub b : bool }");
+    }
+
+    #[test]
+    fn mixin_tuple_and_unit_fields() {
+        let merged = merge(
+            parse_quote!(
+                struct Local(u8);
+            ),
+            parse_quote!(
+                struct Foreign(u16);
+            ),
+        );
+        assert_eq!(merged.unwrap(), "struct Local (u8 , u16) ;");
+        let merged = merge(
+            parse_quote!(
+                struct Local;
+            ),
+            parse_quote!(
+                struct Foreign(u16);
+            ),
+        );
+        assert_eq!(merged.unwrap(), "struct Local (u16) ;");
+        let merged = merge(
+            parse_quote!(
+                struct Local;
+            ),
+            parse_quote!(
+                struct Foreign {
+                    b: bool,
+                }
+            ),
+        );
+        assert_eq!(merged.unwrap(), "struct Local { b : bool , }");
+        let merged = merge(
+            parse_quote!(
+                struct Local(u8);
+            ),
+            parse_quote!(
+                struct Foreign;
+            ),
+        );
+        assert_eq!(merged.unwrap(), "struct Local (u8) ;");
+        assert!(merge(
+            parse_quote!(
+                struct Local(u8);
+            ),
+            parse_quote!(
+                struct Foreign {
+                    b: bool,
+                }
+            )
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn mixin_field_conflicts() {
+        let err = merge(
+            parse_quote!(
+                struct Local {
+                    a: u32,
+                    b: u32,
+                }
+            ),
+            parse_quote!(
+                struct Foreign {
+                    a: u32,
+                    b: u32,
+                }
+            ),
+        )
+        .unwrap_err();
+        let messages: Vec<String> = err.into_iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "field `a` conflicts with a field of the same name in mixed-in struct `a :: Foreign`",
+                "field `b` conflicts with a field of the same name in mixed-in struct `a :: Foreign`"
+            ]
+        );
+    }
+
+    #[test]
+    fn mixin_generics() {
+        let merged = merge(
+            parse_quote!(
+                struct Local<T>
+                where
+                    T: Clone,
+                {
+                    t: T,
+                }
+            ),
+            parse_quote!(
+                struct Foreign<'a, T, U: Copy>
+                where
+                    T: Clone,
+                    U: Default,
+                {
+                    u: &'a U,
+                }
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            "struct Local < 'a , T , U : Copy > where T : Clone , U : Default { t : T , u : & 'a U }"
+        );
+    }
+
+    #[test]
+    fn mixin_forwards_remaining_paths() {
+        let output = mixin_internal(
+            quote!(a::Foo, b::Bar),
+            quote!(
+                struct Local {}
+            ),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains(":: macro_magic :: __private :: mixin"));
+        assert!(output.contains("{ { struct Local { } } , { a :: Foo } , { b :: Bar } }"));
+        assert!(mixin_internal(
+            quote!(),
+            quote!(
+                struct Local {}
+            )
+        )
+        .is_err());
+        assert!(mixin_internal(
+            quote!(a::Foo),
+            quote!(
+                fn local() {}
+            )
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,540 @@
+//! Contains the implementation behind the `mock_trait!` macro.
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2, parse_quote,
+    spanned::Spanned,
+    visit::{self, Visit},
+    visit_mut::{self, VisitMut},
+    Error, FnArg, Ident, Item, ItemTrait, Lifetime, Pat, PatIdent, Path, Result, ReturnType,
+    Signature, Token, TraitItem, TraitItemFn, Type, TypeParam, TypePath, TypeReference,
+};
+
+use crate::{macro_magic_root, parse_forwarded_proc, trace_stage};
+
+/// Used to parse the args of [`mock_trait_internal`], i.e. `upstream::Repo` or
+/// `upstream::Repo as FakeRepo`.
+///
+/// You shouldn't need to use this directly.
+pub struct MockTraitArgs {
+    /// The path of the `#[export_tokens]` trait that will be mocked
+    pub source: Path,
+    /// The ident of the mock, if specified via `as ..`. Defaults to `Mock<Trait>`.
+    pub ident: Option<Ident>,
+}
+
+impl Parse for MockTraitArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse::<Path>()?;
+        let ident = match input.parse::<Option<Token![as]>>()? {
+            Some(_) => Some(input.parse::<Ident>()?),
+            None => None,
+        };
+        input.parse::<Option<Token![,]>>()?;
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens, expected `upstream::Trait as MockName`"));
+        }
+        Ok(MockTraitArgs { source, ident })
+    }
+}
+
+impl ToTokens for MockTraitArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.source.to_tokens(tokens);
+        if let Some(ident) = &self.ident {
+            tokens.extend(quote!(as #ident));
+        }
+    }
+}
+
+/// Finds the generic type params of a method and `impl Trait` types, neither of which can be
+/// stored in the fields of a mock.
+struct GenericTypeFinder<'a> {
+    params: &'a [Ident],
+    found: bool,
+}
+
+impl<'a, 'ast> Visit<'ast> for GenericTypeFinder<'a> {
+    fn visit_type(&mut self, ty: &'ast Type) {
+        match ty {
+            Type::ImplTrait(_) => self.found = true,
+            Type::Path(TypePath { qself: None, path }) => {
+                if let Some(ident) = path.get_ident() {
+                    if self.params.contains(ident) {
+                        self.found = true;
+                    }
+                }
+            }
+            _ => (),
+        }
+        visit::visit_type(self, ty);
+    }
+}
+
+/// Returns `true` if `ty` refers to one of the generic type `params` or an `impl Trait`.
+fn is_generic_type(ty: &Type, params: &[Ident]) -> bool {
+    let mut finder = GenericTypeFinder {
+        params,
+        found: false,
+    };
+    finder.visit_type(ty);
+    finder.found
+}
+
+/// Rewrites the types of a trait method so that they can be stored in the fields of a mock,
+/// replacing `Self::Assoc` with the corresponding generic param of the mock, `Self` with the
+/// mock itself and, optionally, every lifetime with `'static`.
+struct StoredTypeRewriter<'a> {
+    assoc_types: &'a [Ident],
+    mock_ty: &'a Type,
+    make_static: bool,
+}
+
+impl<'a> VisitMut for StoredTypeRewriter<'a> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself: None, path }) = ty {
+            if path.is_ident("Self") {
+                *ty = self.mock_ty.clone();
+                return;
+            }
+            let segments: Vec<&Ident> = path.segments.iter().map(|s| &s.ident).collect();
+            if let [first, assoc] = segments[..] {
+                if first == "Self" && self.assoc_types.contains(assoc) {
+                    *ty = parse_quote!(#assoc);
+                    return;
+                }
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_type_reference_mut(&mut self, reference: &mut TypeReference) {
+        if self.make_static {
+            reference.lifetime = Some(Lifetime::new("'static", Span::call_site()));
+        }
+        visit_mut::visit_type_reference_mut(self, reference);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if self.make_static {
+            *lifetime = Lifetime::new("'static", lifetime.span());
+        }
+    }
+}
+
+/// Returns the field of the mock that records the calls to the trait method `item_fn`, along
+/// with the mocked implementation of the method.
+///
+/// Returns an error if the method has no receiver, or if its return type can't be stored in
+/// the mock (i.e. it is an `impl Trait` or refers to the generic params of the method).
+fn mock_fn(
+    item_fn: &TraitItemFn,
+    method_ty: &Ident,
+    rewriter: &mut StoredTypeRewriter,
+) -> Result<(TokenStream2, TokenStream2)> {
+    let mut sig: Signature = item_fn.sig.clone();
+    let ident = sig.ident.clone();
+    let Some(FnArg::Receiver(receiver)) = sig.inputs.first() else {
+        return Err(Error::new(
+            ident.span(),
+            format!(
+                "cannot mock `{}` because it doesn't take `self`, so there is no mock to record \
+                the call on",
+                ident
+            ),
+        ));
+    };
+    // the receiver comes from the forwarded tokens, so its `self` must be reused for hygiene
+    let self_token = receiver.self_token;
+    let generic_params: Vec<Ident> = sig
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let ret_ty = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) if is_generic_type(ty, &generic_params) => {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "cannot mock `{}` because its return type depends on its generic params or \
+                    is an `impl Trait`",
+                    ident
+                ),
+            ))
+        }
+        ReturnType::Type(_, ty) => {
+            let mut ty = (**ty).clone();
+            rewriter.make_static = true;
+            rewriter.visit_type_mut(&mut ty);
+            ty.to_token_stream()
+        }
+    };
+    rewriter.make_static = false;
+    let mut arg_tys = Vec::new();
+    let mut arg_exprs = Vec::new();
+    for (i, input) in sig.inputs.iter_mut().skip(1).enumerate() {
+        let FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+        let arg = format_ident!("__arg{}", i);
+        *pat_type.pat = Pat::Ident(PatIdent {
+            attrs: Vec::new(),
+            by_ref: None,
+            mutability: None,
+            ident: arg.clone(),
+            subpat: None,
+        });
+        match &*pat_type.ty {
+            ty if is_generic_type(ty, &generic_params) => {
+                arg_tys.push(quote!(&'static str));
+                arg_exprs.push(quote!(::core::any::type_name_of_val(&#arg)));
+            }
+            Type::Reference(reference) => {
+                let mut elem = (*reference.elem).clone();
+                rewriter.visit_type_mut(&mut elem);
+                arg_tys.push(quote!(<#elem as ::std::borrow::ToOwned>::Owned));
+                arg_exprs.push(quote!(::std::borrow::ToOwned::to_owned(&*#arg)));
+            }
+            ty => {
+                let mut ty = ty.clone();
+                rewriter.visit_type_mut(&mut ty);
+                arg_tys.push(ty.to_token_stream());
+                arg_exprs.push(quote!(#arg));
+            }
+        }
+    }
+    let field = quote! {
+        pub #ident: #method_ty<(#(#arg_tys,)*), #ret_ty>
+    };
+    let method = quote! {
+        #sig {
+            #self_token.#ident.record((#(#arg_exprs,)*))
+        }
+    };
+    Ok((field, method))
+}
+
+/// Generates a mock of the `foreign` trait (imported from `args.source`), i.e. a struct with
+/// one field per trait method that records the arguments of each call and hands out return
+/// values that were set by the test, along with an impl of the trait for it.
+///
+/// The mock is generic over the associated types of the trait, and uses `std` types without
+/// requiring any other crate at runtime.
+pub fn mock_trait_tokens(args: &MockTraitArgs, foreign: &ItemTrait) -> Result<TokenStream2> {
+    let source = &args.source;
+    let trait_ident = &foreign.ident;
+    if !foreign.generics.params.is_empty() {
+        return Err(Error::new(
+            source.span(),
+            format!(
+                "`{}` has generic params, which `mock_trait!` does not support",
+                source.to_token_stream()
+            ),
+        ));
+    }
+    let mock_ident = args
+        .ident
+        .clone()
+        .unwrap_or_else(|| format_ident!("Mock{}", trait_ident));
+    let method_ty = format_ident!("{}Method", mock_ident);
+    let assoc_params: Vec<TypeParam> = foreign
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            TraitItem::Type(item_type) => {
+                let mut param = TypeParam::from(item_type.ident.clone());
+                param.bounds = item_type.bounds.clone();
+                param.bounds.push(parse_quote!('static));
+                Some(param)
+            }
+            _ => None,
+        })
+        .collect();
+    let assoc_types: Vec<Ident> = assoc_params
+        .iter()
+        .map(|param| param.ident.clone())
+        .collect();
+    let generics = quote!(<#(#assoc_params),*>);
+    let ty_generics = quote!(<#(#assoc_types),*>);
+    let mock_ty: Type = parse_quote!(#mock_ident #ty_generics);
+    let mut rewriter = StoredTypeRewriter {
+        assoc_types: &assoc_types,
+        mock_ty: &mock_ty,
+        make_static: false,
+    };
+    let mut fields = Vec::new();
+    let mut field_idents = Vec::new();
+    let mut field_names = Vec::new();
+    let mut items = Vec::new();
+    let mut errors: Option<Error> = None;
+    for item in &foreign.items {
+        let result = match item {
+            TraitItem::Fn(item_fn) => {
+                mock_fn(item_fn, &method_ty, &mut rewriter).map(|(field, method)| {
+                    let ident = &item_fn.sig.ident;
+                    fields.push(field);
+                    field_names.push(format!("{}::{}", trait_ident, ident));
+                    field_idents.push(ident.clone());
+                    method
+                })
+            }
+            TraitItem::Type(item_type) => {
+                let ident = &item_type.ident;
+                Ok(quote!(type #ident = #ident;))
+            }
+            TraitItem::Const(item_const) => match &item_const.default {
+                Some(_) => continue,
+                None => Err(Error::new(
+                    item_const.ident.span(),
+                    format!(
+                        "cannot mock associated const `{}` because it has no default value",
+                        item_const.ident
+                    ),
+                )),
+            },
+            _ => continue,
+        };
+        match result {
+            Ok(tokens) => items.push(tokens),
+            Err(err) => {
+                // point at the invocation, since the trait lives in another crate
+                let err = Error::new(source.span(), err.to_string());
+                match &mut errors {
+                    Some(errors) => errors.combine(err),
+                    None => errors = Some(err),
+                }
+            }
+        }
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+    let method_doc = format!(
+        "Records the calls to a single method of [`{}`] and hands out the values it returns.",
+        mock_ident
+    );
+    let mock_doc = format!(
+        "A mock of `{}` generated by `mock_trait!`, with one field per trait method.",
+        source.to_token_stream().to_string().replace(' ', "")
+    );
+    Ok(quote! {
+        #[doc = #method_doc]
+        pub struct #method_ty<A, R> {
+            name: &'static str,
+            calls: ::core::cell::RefCell<::std::vec::Vec<A>>,
+            returns: ::core::cell::RefCell<::std::collections::VecDeque<R>>,
+            fallback: ::core::cell::RefCell<
+                ::core::option::Option<::std::boxed::Box<dyn ::core::ops::FnMut(&A) -> R>>,
+            >,
+        }
+
+        impl<A, R> #method_ty<A, R> {
+            /// Creates a method mock with no calls and no return values, whose `name` is used
+            /// in panic messages.
+            pub fn new(name: &'static str) -> Self {
+                Self {
+                    name,
+                    calls: ::core::default::Default::default(),
+                    returns: ::core::default::Default::default(),
+                    fallback: ::core::default::Default::default(),
+                }
+            }
+
+            /// Queues a value that will be returned by the next call that doesn't have a
+            /// queued value yet.
+            pub fn returns(&self, value: R) -> &Self {
+                self.returns.borrow_mut().push_back(value);
+                self
+            }
+
+            /// Sets a closure that computes the return value from the arguments once all
+            /// queued values have been returned.
+            pub fn returns_with(&self, f: impl ::core::ops::FnMut(&A) -> R + 'static) -> &Self {
+                *self.fallback.borrow_mut() = ::core::option::Option::Some(::std::boxed::Box::new(f));
+                self
+            }
+
+            /// Returns the arguments of every call so far, in order.
+            pub fn calls(&self) -> ::core::cell::Ref<'_, ::std::vec::Vec<A>> {
+                self.calls.borrow()
+            }
+
+            /// Returns the number of calls so far.
+            pub fn call_count(&self) -> usize {
+                self.calls.borrow().len()
+            }
+
+            /// Records a call with the specified arguments and returns the next return value,
+            /// panicking if there is none.
+            #[doc(hidden)]
+            pub fn record(&self, args: A) -> R {
+                let queued = self.returns.borrow_mut().pop_front();
+                let value = match queued {
+                    ::core::option::Option::Some(value) => value,
+                    ::core::option::Option::None => match &mut *self.fallback.borrow_mut() {
+                        ::core::option::Option::Some(f) => f(&args),
+                        ::core::option::Option::None => ::core::panic!(
+                            "no return value was set for `{}`, use `returns` or `returns_with`",
+                            self.name
+                        ),
+                    },
+                };
+                self.calls.borrow_mut().push(args);
+                value
+            }
+        }
+
+        #[doc = #mock_doc]
+        pub struct #mock_ident #generics {
+            #(#fields,)*
+        }
+
+        impl #generics ::core::default::Default for #mock_ty {
+            fn default() -> Self {
+                Self {
+                    #(#field_idents: #method_ty::new(#field_names),)*
+                }
+            }
+        }
+
+        impl #generics #source for #mock_ty {
+            #(#items)*
+        }
+    })
+}
+
+/// Internal implementation of the `mock_trait!` macro.
+///
+/// When invoked by the user, `tokens` are parsed as [`MockTraitArgs`] and the tokens of the
+/// `#[export_tokens]` trait are forwarded back to `mock_trait!` along with these args. When
+/// invoked with forwarded tokens, the mock is generated via [`mock_trait_tokens`].
+pub fn mock_trait_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output = match parse_forwarded_proc(&tokens) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<MockTraitArgs>(forwarded.args)?;
+            let Item::Trait(foreign) = forwarded.item else {
+                return Err(Error::new(
+                    args.source.span(),
+                    format!(
+                        "`{}` is not a trait, so it cannot be mocked",
+                        args.source.to_token_stream()
+                    ),
+                ));
+            };
+            mock_trait_tokens(&args, &foreign)?
+        }
+        None => {
+            let args = parse2::<MockTraitArgs>(tokens.clone())?;
+            let mm_path = macro_magic_root();
+            let source = &args.source;
+            quote! {
+                #mm_path::forward_tokens! {
+                    #source,
+                    #mm_path::__private::mock_trait,
+                    #mm_path,
+                    args = { #args }
+                }
+            }
+        }
+    };
+    trace_stage("mock_trait", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock(args: MockTraitArgs, foreign: ItemTrait) -> Result<String> {
+        mock_trait_tokens(&args, &foreign).map(|tokens| tokens.to_string())
+    }
+
+    #[test]
+    fn mock_trait_fields() {
+        let output = mock(
+            parse_quote!(upstream::Repo),
+            parse_quote! {
+                pub trait Repo {
+                    type Item: Clone;
+                    fn get(&self, id: u32, name: &str) -> Option<&Self::Item>;
+                    fn put<T: Into<u32>>(&mut self, id: T, item: Self::Item);
+                    fn log(&self, message: impl AsRef<str>);
+                }
+            },
+        )
+        .unwrap();
+        assert!(output.contains("pub struct MockRepoMethod < A , R >"));
+        assert!(output.contains("pub struct MockRepo < Item : Clone + 'static >"));
+        assert!(output.contains(
+            "pub get : MockRepoMethod < (u32 , < str as :: std :: borrow :: ToOwned > :: Owned ,) \
+            , Option < & 'static Item > >"
+        ));
+        assert!(output.contains("pub put : MockRepoMethod < (& 'static str , Item ,) , () >"));
+        assert!(output.contains("pub log : MockRepoMethod < (& 'static str ,) , () >"));
+        assert!(output.contains("get : MockRepoMethod :: new (\"Repo::get\")"));
+        assert!(output
+            .contains("impl < Item : Clone + 'static > upstream :: Repo for MockRepo < Item >"));
+        assert!(output.contains("type Item = Item ;"));
+        assert!(output.contains(
+            "fn put < T : Into < u32 > > (& mut self , __arg0 : T , __arg1 : Self :: Item) { self \
+            . put . record ((:: core :: any :: type_name_of_val (& __arg0) , __arg1 ,)) }"
+        ));
+    }
+
+    #[test]
+    fn mock_trait_custom_ident() {
+        let output = mock(
+            parse_quote!(upstream::Clock as FakeClock),
+            parse_quote! {
+                pub trait Clock {
+                    const HZ: u32 = 60;
+                    fn now(&self) -> u64;
+                }
+            },
+        )
+        .unwrap();
+        assert!(output.contains("pub struct FakeClock < >"));
+        assert!(output.contains("pub now : FakeClockMethod < () , u64 >"));
+        assert!(!output.contains("HZ"));
+    }
+
+    #[test]
+    fn mock_trait_errors() {
+        let Err(err) = mock(
+            parse_quote!(upstream::Repo),
+            parse_quote! {
+                pub trait Repo {
+                    const MAX: u32;
+                    fn new() -> Self;
+                    fn make<T: Default>(&self) -> T;
+                }
+            },
+        ) else {
+            panic!("expected an error");
+        };
+        let messages: Vec<String> = err.into_iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot mock associated const `MAX` because it has no default value",
+                "cannot mock `new` because it doesn't take `self`, so there is no mock to record \
+                the call on",
+                "cannot mock `make` because its return type depends on its generic params or is \
+                an `impl Trait`"
+            ]
+        );
+        assert!(mock(
+            parse_quote!(upstream::Repo),
+            parse_quote!(
+                pub trait Repo<K> {}
+            )
+        )
+        .is_err());
+        assert!(mock_trait_internal(quote!(upstream::Repo as)).is_err());
+    }
+}
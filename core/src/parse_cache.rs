@@ -0,0 +1,235 @@
+//! Contains a per-process cache that lets the inner proc macros generated by
+//! `#[import_tokens_attr]` with `#[with_parse_cache]` skip the `Item` parse that validates
+//! imported items which were already validated in the same compilation. The user's macro still
+//! parses the item itself, so this saves one parse per invocation.
+//!
+//! Parsed [`Item`]s themselves can't be kept across invocations: their spans are handles into
+//! the compiler that are only valid during the invocation that created them, and
+//! `proc_macro2` types are neither `Send` nor `Sync`. Instead, the cache records a
+//! span-free fingerprint of every item that has been successfully parsed, keyed by the path it
+//! was imported from, and later invocations with the same fingerprint pass the item tokens
+//! along as-is. The cache is guarded by a [`Mutex`], so it is safe to use from any thread the
+//! proc-macro server expands macros on.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    sync::{Mutex, PoisonError},
+};
+
+use proc_macro2::{Delimiter, Spacing, TokenStream as TokenStream2, TokenTree};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream},
+    parse2, Error, Item, Result,
+};
+
+/// The fingerprints (see [`import_fingerprint`]) of the imported items that were successfully
+/// parsed in this process.
+static PARSED_IMPORTS: Mutex<Option<HashSet<u64>>> = Mutex::new(None);
+
+/// Feeds the structure of `tokens` into `hasher`, ignoring spans.
+fn hash_tokens(tokens: TokenStream2, hasher: &mut DefaultHasher) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let delimiter: u8 = match group.delimiter() {
+                    Delimiter::Parenthesis => 0,
+                    Delimiter::Brace => 1,
+                    Delimiter::Bracket => 2,
+                    Delimiter::None => 3,
+                };
+                (0u8, delimiter).hash(hasher);
+                hash_tokens(group.stream(), hasher);
+                // closes the group, so `(a) b` and `(a b)` differ
+                4u8.hash(hasher);
+            }
+            TokenTree::Ident(ident) => (1u8, ident.to_string()).hash(hasher),
+            TokenTree::Punct(punct) => {
+                (2u8, punct.as_char(), punct.spacing() == Spacing::Joint).hash(hasher)
+            }
+            TokenTree::Literal(literal) => (3u8, literal.to_string()).hash(hasher),
+        }
+    }
+}
+
+/// Returns the fingerprint of the item `item` imported from `source_path`, which only depends
+/// on the structure of the tokens (not their spans), so it is the same across invocations.
+pub fn import_fingerprint(source_path: &TokenStream2, item: &TokenStream2) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_tokens(source_path.clone(), &mut hasher);
+    5u8.hash(&mut hasher);
+    hash_tokens(item.clone(), &mut hasher);
+    hasher.finish()
+}
+
+/// Returns `true` if an item with the specified fingerprint (see [`import_fingerprint`]) was
+/// already parsed in this process.
+pub fn is_import_parsed(fingerprint: u64) -> bool {
+    let parsed = PARSED_IMPORTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    parsed
+        .as_ref()
+        .is_some_and(|parsed| parsed.contains(&fingerprint))
+}
+
+/// Records that an item with the specified fingerprint (see [`import_fingerprint`]) parsed
+/// successfully.
+fn mark_import_parsed(fingerprint: u64) {
+    PARSED_IMPORTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(HashSet::new)
+        .insert(fingerprint);
+}
+
+/// The braced tokens following the imported item in the args of the inner proc macro
+/// generated by `#[import_tokens_attr]`, i.e. `{ { tokens } , { source } , { custom } }`.
+struct AttrExtra {
+    tokens_ident: TokenStream2,
+    source_path: TokenStream2,
+    custom_tokens: TokenStream2,
+}
+
+impl Parse for AttrExtra {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let content;
+        braced!(content in input);
+        let mut groups = Vec::new();
+        for index in 0..3 {
+            if index > 0 {
+                content.parse::<syn::token::Comma>()?;
+            }
+            let group;
+            braced!(group in content);
+            groups.push(group.parse::<TokenStream2>()?);
+        }
+        if !content.is_empty() {
+            return Err(content.error("unexpected tokens"));
+        }
+        let [tokens_ident, source_path, custom_tokens] =
+            <[TokenStream2; 3]>::try_from(groups).unwrap_or_else(|_| unreachable!());
+        Ok(AttrExtra {
+            tokens_ident,
+            source_path,
+            custom_tokens,
+        })
+    }
+}
+
+/// Like [`AttrItemWithExtra`](`crate::AttrItemWithExtra`), but with the imported item kept as
+/// tokens. Returned by [`parse_attr_item_cached`].
+///
+/// You shouldn't need to use this directly.
+pub struct CachedAttrItemWithExtra {
+    /// The tokens of the [`Item`] that is being imported, which are known to parse as an item
+    pub imported_item: TokenStream2,
+    /// The raw tokens for the ident the generated macro will use to refer to the tokens
+    /// argument of the macro
+    pub tokens_ident: TokenStream2,
+    /// The path of the item that is being imported
+    pub source_path: TokenStream2,
+    /// The raw tokens produced by custom parsing, if `#[with_custom_parsing(..)]` is used
+    pub custom_tokens: TokenStream2,
+}
+
+/// Parses the args of the inner proc macro generated by `#[import_tokens_attr]`, like
+/// [`AttrItemWithExtra`](`crate::AttrItemWithExtra`) does.
+///
+/// The imported item is only parsed as an [`Item`] the first time it is seen from a given
+/// source path in this process (see [`import_fingerprint`]). Later invocations with the same
+/// item only split off the trailing args, which is much cheaper for large items imported by
+/// many invocations.
+pub fn parse_attr_item_cached<T: Into<TokenStream2>>(tokens: T) -> Result<CachedAttrItemWithExtra> {
+    let mut tokens: Vec<TokenTree> = tokens.into().into_iter().collect();
+    let extra = match tokens.as_slice() {
+        [.., TokenTree::Punct(punct), TokenTree::Group(group)]
+            if punct.as_char() == ',' && group.delimiter() == Delimiter::Brace =>
+        {
+            parse2::<AttrExtra>(TokenTree::Group(group.clone()).into())?
+        }
+        [.., last] => {
+            return Err(Error::new(
+                last.span(),
+                "expected the imported item followed by `, { .. }`",
+            ))
+        }
+        [] => {
+            return Err(Error::new(
+                proc_macro2::Span::call_site(),
+                "expected the imported item followed by `, { .. }`",
+            ))
+        }
+    };
+    tokens.truncate(tokens.len() - 2);
+    let imported_item = TokenStream2::from_iter(tokens);
+    let fingerprint = import_fingerprint(&extra.source_path, &imported_item);
+    if !is_import_parsed(fingerprint) {
+        parse2::<Item>(imported_item.clone())?;
+        mark_import_parsed(fingerprint);
+    }
+    Ok(CachedAttrItemWithExtra {
+        imported_item,
+        tokens_ident: extra.tokens_ident,
+        source_path: extra.source_path,
+        custom_tokens: extra.custom_tokens,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    #[test]
+    fn parse_attr_item_cached_skips_parsed_items() {
+        let args = quote! {
+            pub struct CachedFoo<T, U> { a: T, b: U },
+            { { tokens }, { crate::CachedFoo }, { } }
+        };
+        let item = quote! { pub struct CachedFoo<T, U> { a: T, b: U } };
+        let fingerprint = import_fingerprint(&quote!(crate::CachedFoo), &item);
+        assert!(!is_import_parsed(fingerprint));
+        let parsed = parse_attr_item_cached(args.clone()).unwrap();
+        assert_eq!(parsed.imported_item.to_string(), item.to_string());
+        assert_eq!(parsed.tokens_ident.to_string(), "tokens");
+        assert_eq!(parsed.source_path.to_string(), "crate :: CachedFoo");
+        assert!(parsed.custom_tokens.is_empty());
+        assert!(is_import_parsed(fingerprint));
+        // the fingerprint ignores spans but not structure or the source path
+        assert_eq!(
+            import_fingerprint(&quote!(crate::CachedFoo), &item),
+            fingerprint
+        );
+        assert_ne!(
+            import_fingerprint(&quote!(other::CachedFoo), &item),
+            fingerprint
+        );
+        assert_ne!(
+            import_fingerprint(
+                &quote!(crate::CachedFoo),
+                &quote!(
+                    pub struct CachedFoo<T, U>;
+                )
+            ),
+            fingerprint
+        );
+        assert!(parse_attr_item_cached(args).is_ok());
+    }
+
+    #[test]
+    fn parse_attr_item_cached_rejects_invalid_items() {
+        let args = quote!(pub struct, { { tokens }, { crate::Broken }, { } });
+        assert!(parse_attr_item_cached(args.clone()).is_err());
+        assert!(!is_import_parsed(import_fingerprint(
+            &quote!(crate::Broken),
+            &quote!(pub struct)
+        )));
+        assert!(parse_attr_item_cached(args).is_err());
+        assert!(parse_attr_item_cached(quote!(
+            pub struct Foo;
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,569 @@
+//! Contains the implementation behind `forward_pipeline!`, which chains several
+//! `forward_tokens!` hops together declaratively.
+
+use std::collections::VecDeque;
+
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{
+    braced, bracketed,
+    parse::{Parse, ParseStream},
+    parse2,
+    token::Comma,
+    Error, Item, LitInt, Path, Result, Token,
+};
+
+use crate::{
+    export_tokens_macro_path, keywords, macro_magic_root, resolve_dep_path, starts_with_keyword,
+    trace_stage, DepsForwardPayload, ForwardTokensExtraArg, ForwardedItemWithArgs,
+};
+
+/// The maximum number of hops (imports plus stage invocations) a pipeline may perform before
+/// it is aborted with a compiler error, unless overridden via `max_depth = N`.
+pub const DEFAULT_PIPELINE_MAX_DEPTH: usize = 32;
+
+mod pipeline_keywords {
+    use syn::custom_keyword;
+
+    custom_keyword!(max_depth);
+    custom_keyword!(mm_path);
+    custom_keyword!(import);
+    custom_keyword!(import_deps);
+    custom_keyword!(stage);
+}
+
+/// A single step of a `forward_pipeline!`.
+#[derive(Clone)]
+pub enum PipelineStep {
+    /// Imports the tokens of the `#[export_tokens]` item at the specified path, adding them to
+    /// the accumulated items
+    Import(Path),
+    /// Like [`PipelineStep::Import`], but also imports the dependencies recorded via
+    /// `#[export_tokens(with_deps(..))]`, recursively. Items that have already been imported are
+    /// skipped rather than treated as a duplicate import.
+    ImportDeps(Path),
+    /// Invokes the specified stage macro with all of the items accumulated so far
+    Stage(Path),
+}
+
+impl ToTokens for PipelineStep {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        tokens.extend(match self {
+            PipelineStep::Import(path) => quote!(import #path;),
+            PipelineStep::ImportDeps(path) => quote!(import_deps #path;),
+            PipelineStep::Stage(path) => quote!(stage #path;),
+        });
+    }
+}
+
+impl Parse for PipelineStep {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let step = if input.peek(pipeline_keywords::import_deps) {
+            input.parse::<pipeline_keywords::import_deps>()?;
+            PipelineStep::ImportDeps(input.parse()?)
+        } else if input.peek(pipeline_keywords::import) {
+            input.parse::<pipeline_keywords::import>()?;
+            PipelineStep::Import(input.parse()?)
+        } else {
+            input.parse::<pipeline_keywords::stage>()?;
+            PipelineStep::Stage(input.parse()?)
+        };
+        input.parse::<Token![;]>()?;
+        Ok(step)
+    }
+}
+
+/// Parses the args of `forward_pipeline!`, i.e. `a::Foo => transform, b::Bar => target;
+/// args = { .. }, max_depth = 8, mm_path = ::my_crate::macro_magic`.
+///
+/// You shouldn't need to use this directly.
+pub struct ForwardPipelineArgs {
+    /// The steps of the pipeline, in order
+    pub steps: Vec<PipelineStep>,
+    /// User-supplied args that will be passed to every stage
+    pub args: TokenStream2,
+    /// The maximum number of hops the pipeline may perform
+    pub max_depth: usize,
+    /// The path of the `macro_magic` crate used to resolve the hops of the pipeline, which
+    /// defaults to [`macro_magic_root`]
+    pub mm_path: Path,
+}
+
+impl Parse for ForwardPipelineArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut steps = Vec::new();
+        while !input.is_empty() && !input.peek(Token![;]) {
+            steps.push(PipelineStep::Import(input.parse()?));
+            while input.peek(Token![=>]) {
+                input.parse::<Token![=>]>()?;
+                steps.push(PipelineStep::Stage(input.parse()?));
+            }
+            if input.parse::<Option<Comma>>()?.is_none() {
+                break;
+            }
+        }
+        let mut args = TokenStream2::new();
+        let mut max_depth = DEFAULT_PIPELINE_MAX_DEPTH;
+        let mut mm_path = macro_magic_root();
+        if input.parse::<Option<Token![;]>>()?.is_some() {
+            while !input.is_empty() {
+                if input.peek(keywords::args) {
+                    input.parse::<keywords::args>()?;
+                    input.parse::<Token![=]>()?;
+                    args = input.parse::<ForwardTokensExtraArg>()?.stream;
+                } else if input.peek(pipeline_keywords::mm_path) {
+                    input.parse::<pipeline_keywords::mm_path>()?;
+                    input.parse::<Token![=]>()?;
+                    mm_path = input.parse()?;
+                } else {
+                    input.parse::<pipeline_keywords::max_depth>()?;
+                    input.parse::<Token![=]>()?;
+                    max_depth = input.parse::<LitInt>()?.base10_parse()?;
+                }
+                if input.parse::<Option<Comma>>()?.is_none() {
+                    break;
+                }
+            }
+        }
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens in `forward_pipeline!`"));
+        }
+        if !steps
+            .iter()
+            .any(|step| matches!(step, PipelineStep::Import(_)))
+        {
+            return Err(Error::new(
+                Span::call_site(),
+                "a pipeline must import at least one item",
+            ));
+        }
+        if !matches!(steps.last(), Some(PipelineStep::Stage(_))) {
+            return Err(Error::new(
+                Span::call_site(),
+                "a pipeline must end with `=> target_macro`",
+            ));
+        }
+        Ok(ForwardPipelineArgs {
+            steps,
+            args,
+            max_depth,
+            mm_path,
+        })
+    }
+}
+
+/// Parses a sequence of `[path] { item }` entries, as used by pipeline payloads.
+pub(crate) fn parse_path_items(input: ParseStream) -> Result<Vec<(Path, Item)>> {
+    let mut items = Vec::new();
+    while !input.is_empty() {
+        let path_content;
+        bracketed!(path_content in input);
+        let item_content;
+        braced!(item_content in input);
+        items.push((path_content.parse()?, item_content.parse()?));
+    }
+    Ok(items)
+}
+
+/// Emits a sequence of `[path] { item }` entries, as used by pipeline payloads.
+pub(crate) fn path_items_to_tokens(items: &[(Path, Item)]) -> TokenStream2 {
+    let items = items.iter().map(|(path, item)| quote!([#path] { #item }));
+    quote!(#(#items)*)
+}
+
+/// The state of an in-progress pipeline, which is threaded through each hop.
+#[derive(Clone)]
+pub(crate) struct PipelineState {
+    pub(crate) depth: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) visited: Vec<Path>,
+    pub(crate) items: Vec<(Path, Item)>,
+    pub(crate) steps: VecDeque<PipelineStep>,
+    pub(crate) args: TokenStream2,
+    pub(crate) mm_path: Path,
+}
+
+impl ToTokens for PipelineState {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let keyword = keywords::__private_macro_magic_pipeline::default();
+        let depth = Literal::usize_unsuffixed(self.depth);
+        let max_depth = Literal::usize_unsuffixed(self.max_depth);
+        let visited = &self.visited;
+        let items = path_items_to_tokens(&self.items);
+        let steps = self.steps.iter();
+        let args = &self.args;
+        let mm_path = &self.mm_path;
+        tokens.extend(quote! {
+            #keyword
+            { #mm_path }
+            { #depth } { #max_depth }
+            { #(#visited),* }
+            { #items }
+            { #(#steps)* }
+            { #args }
+        });
+    }
+}
+
+impl Parse for PipelineState {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<keywords::__private_macro_magic_pipeline>()?;
+        let content;
+        braced!(content in input);
+        let mm_path = content.parse()?;
+        let content;
+        braced!(content in input);
+        let depth = content.parse::<LitInt>()?.base10_parse()?;
+        let content;
+        braced!(content in input);
+        let max_depth = content.parse::<LitInt>()?.base10_parse()?;
+        let content;
+        braced!(content in input);
+        let visited = content
+            .parse_terminated(Path::parse, Comma)?
+            .into_iter()
+            .collect();
+        let content;
+        braced!(content in input);
+        let items = parse_path_items(&content)?;
+        let content;
+        braced!(content in input);
+        let mut steps = VecDeque::new();
+        while !content.is_empty() {
+            steps.push_back(content.parse()?);
+        }
+        let args = input.parse::<ForwardTokensExtraArg>()?.stream;
+        Ok(PipelineState {
+            depth,
+            max_depth,
+            visited,
+            items,
+            steps,
+            args,
+            mm_path,
+        })
+    }
+}
+
+impl PipelineState {
+    /// Returns whether the item at the specified path has already been imported.
+    fn is_visited(&self, path: &Path) -> bool {
+        let path_str = path.to_token_stream().to_string();
+        self.visited
+            .iter()
+            .any(|visited| visited.to_token_stream().to_string() == path_str)
+    }
+
+    /// Performs the next hop of the pipeline.
+    pub(crate) fn run(mut self) -> Result<TokenStream2> {
+        let Some(step) = self.steps.pop_front() else {
+            return Ok(TokenStream2::new());
+        };
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            return Err(Error::new(
+                Span::call_site(),
+                format!(
+                    "pipeline exceeded its maximum depth of {} hops, consider increasing it \
+                    using `max_depth = N`",
+                    self.max_depth
+                ),
+            ));
+        }
+        let mm_path = self.mm_path.clone();
+        match step {
+            PipelineStep::Import(path) => {
+                if self.is_visited(&path) {
+                    return Err(Error::new(
+                        Span::call_site(),
+                        format!(
+                            "duplicate pipeline import: `{}` has already been imported by this \
+                            pipeline",
+                            path.to_token_stream()
+                        ),
+                    ));
+                }
+                self.visited.push(path.clone());
+                Ok(quote! {
+                    #mm_path::forward_tokens! {
+                        #path,
+                        #mm_path::__private::forward_pipeline_inner,
+                        #mm_path,
+                        args = { #self }
+                    }
+                })
+            }
+            PipelineStep::ImportDeps(path) => {
+                if self.is_visited(&path) {
+                    self.depth -= 1;
+                    return self.run();
+                }
+                self.visited.push(path.clone());
+                let source_path = export_tokens_macro_path(&path);
+                let deps_keyword = keywords::__private_macro_magic_deps::default();
+                Ok(quote! {
+                    #source_path! {
+                        #deps_keyword,
+                        #mm_path::__private::forward_pipeline_inner,
+                        { #self }
+                    }
+                })
+            }
+            PipelineStep::Stage(stage) => {
+                let tokens_forwarded_keyword =
+                    keywords::__private_macro_magic_tokens_forwarded::default();
+                let items = path_items_to_tokens(&self.items);
+                let args = &self.args;
+                let continuation = match self.steps.is_empty() {
+                    true => TokenStream2::new(),
+                    false => self.to_token_stream(),
+                };
+                Ok(quote! {
+                    #stage! {
+                        #tokens_forwarded_keyword
+                        { #items }
+                        { #args }
+                        { #continuation }
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// The input received by a stage (or the final target) of a `forward_pipeline!`.
+///
+/// Stage macros are regular `#[proc_macro]`s that parse their input into this struct, i.e.
+/// `let pipeline = parse_macro_input!(tokens as ForwardedPipeline);`. Intermediate stages
+/// must emit the tokens returned by [`ForwardedPipeline::resume`] (with whatever items they
+/// want the remaining stages to receive) for the pipeline to continue.
+///
+/// ## Example
+///
+/// ```ignore
+/// #[proc_macro]
+/// pub fn my_stage(tokens: TokenStream) -> TokenStream {
+///     let pipeline = parse_macro_input!(tokens as ForwardedPipeline);
+///     let items = pipeline
+///         .items
+///         .iter()
+///         .cloned()
+///         .map(|(path, item)| (path, transform(item)))
+///         .collect();
+///     pipeline.resume(items).into()
+/// }
+/// ```
+pub struct ForwardedPipeline {
+    /// The items accumulated by the pipeline so far, along with the paths they were imported
+    /// from, in order
+    pub items: Vec<(Path, Item)>,
+    /// The user-supplied args that were passed via `args = { .. }`, or an empty
+    /// [`TokenStream2`] if none were specified
+    pub args: TokenStream2,
+    continuation: Option<PipelineState>,
+}
+
+impl Parse for ForwardedPipeline {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Option<keywords::__private_macro_magic_tokens_forwarded>>()?;
+        let content;
+        braced!(content in input);
+        let items = parse_path_items(&content)?;
+        let args = input.parse::<ForwardTokensExtraArg>()?.stream;
+        let content;
+        braced!(content in input);
+        let continuation = match content.is_empty() {
+            true => None,
+            false => Some(content.parse()?),
+        };
+        Ok(ForwardedPipeline {
+            items,
+            args,
+            continuation,
+        })
+    }
+}
+
+impl ForwardedPipeline {
+    /// Returns `true` if this is the final stage of the pipeline, in which case
+    /// [`ForwardedPipeline::resume`] does nothing.
+    pub fn is_final_stage(&self) -> bool {
+        self.continuation.is_none()
+    }
+
+    /// Returns the tokens that continue the pipeline with the remaining stages, which will
+    /// receive the specified `items` (rather than the items this stage received) as the
+    /// accumulated items. Returns an empty [`TokenStream2`] if this is the final stage.
+    pub fn resume(&self, items: Vec<(Path, Item)>) -> TokenStream2 {
+        let Some(mut state) = self.continuation.clone() else {
+            return TokenStream2::new();
+        };
+        state.items = items;
+        let mm_path = &state.mm_path;
+        quote! {
+            #mm_path::__private::forward_pipeline_inner! { #state }
+        }
+    }
+}
+
+/// Internal implementation of `forward_pipeline!`.
+pub fn forward_pipeline_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let args = parse2::<ForwardPipelineArgs>(tokens.clone())?;
+    let output = PipelineState {
+        depth: 0,
+        max_depth: args.max_depth,
+        visited: Vec::new(),
+        items: Vec::new(),
+        steps: args.steps.into(),
+        args: args.args,
+        mm_path: args.mm_path,
+    }
+    .run()?;
+    trace_stage("forward_pipeline", None, None, &tokens, &output);
+    Ok(output)
+}
+
+/// Internal implementation of the hidden `forward_pipeline_inner!` macro, which receives
+/// either a forwarded item along with the pipeline state (after an import), an item along with
+/// its recorded dependencies and the pipeline state (after an import with dependencies), or
+/// just the pipeline state (when a stage resumes the pipeline).
+pub fn forward_pipeline_inner_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let state = if starts_with_keyword::<keywords::__private_macro_magic_pipeline>(&tokens) {
+        parse2::<PipelineState>(tokens.clone())?
+    } else if starts_with_keyword::<keywords::__private_macro_magic_deps>(&tokens) {
+        let payload = parse2::<DepsForwardPayload>(tokens.clone())?;
+        let mut state = parse2::<PipelineState>(payload.extra)?;
+        let Some(path) = state.visited.last().cloned() else {
+            return Err(Error::new(Span::call_site(), "corrupt pipeline state"));
+        };
+        // the same item may be reachable via differently spelled paths (i.e. `$crate::a::B`
+        // vs `my_crate::a::B`), so identical items are only kept the first time they arrive
+        let item_str = payload.item.to_token_stream().to_string();
+        if state
+            .items
+            .iter()
+            .all(|(_, item)| item.to_token_stream().to_string() != item_str)
+        {
+            for dep in payload.deps.iter().rev() {
+                let dep = resolve_dep_path(&path, dep);
+                if !state.is_visited(&dep) {
+                    state.steps.push_front(PipelineStep::ImportDeps(dep));
+                }
+            }
+            state.items.push((path, payload.item));
+        }
+        state
+    } else {
+        let forwarded = parse2::<ForwardedTokensWithState>(tokens.clone())?;
+        let mut state = parse2::<PipelineState>(forwarded.0.args)?;
+        let Some(path) = state.visited.last().cloned() else {
+            return Err(Error::new(Span::call_site(), "corrupt pipeline state"));
+        };
+        state.items.push((path, forwarded.0.item));
+        state
+    };
+    let output = state.run()?;
+    trace_stage("forward_pipeline_inner", None, None, &tokens, &output);
+    Ok(output)
+}
+
+/// Parses `__private_macro_magic_tokens_forwarded <item>, { <state> }`.
+struct ForwardedTokensWithState(ForwardedItemWithArgs);
+
+impl Parse for ForwardedTokensWithState {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<keywords::__private_macro_magic_tokens_forwarded>()?;
+        Ok(ForwardedTokensWithState(input.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_pipeline_args_parsing() {
+        let args =
+            parse2::<ForwardPipelineArgs>(quote!(a::Foo => transform, b::Bar => target)).unwrap();
+        assert_eq!(args.steps.len(), 4);
+        assert_eq!(args.max_depth, DEFAULT_PIPELINE_MAX_DEPTH);
+        let args = parse2::<ForwardPipelineArgs>(
+            quote!(a::Foo, b::Bar => t1 => t2; args = { x y }, max_depth = 3),
+        )
+        .unwrap();
+        assert_eq!(args.steps.len(), 4);
+        assert_eq!(args.args.to_string(), "x y");
+        assert_eq!(args.max_depth, 3);
+        let args =
+            parse2::<ForwardPipelineArgs>(quote!(a::Foo => t; mm_path = ::other::mm)).unwrap();
+        assert_eq!(args.mm_path.to_token_stream().to_string(), ":: other :: mm");
+        let output = forward_pipeline_internal(quote!(a::Foo => t; mm_path = ::other::mm))
+            .unwrap()
+            .to_string();
+        assert!(output.starts_with(":: other :: mm :: forward_tokens !"));
+        assert!(output.contains("{ :: other :: mm }"));
+        assert!(parse2::<ForwardPipelineArgs>(quote!(a::Foo, b::Bar)).is_err());
+        assert!(parse2::<ForwardPipelineArgs>(quote!()).is_err());
+        assert!(parse2::<ForwardPipelineArgs>(quote!(a::Foo => t; bogus = 3)).is_err());
+    }
+
+    #[test]
+    fn forward_pipeline_first_hop() {
+        let output = forward_pipeline_internal(quote!(a::Foo, b::Bar => target))
+            .unwrap()
+            .to_string();
+        assert!(output.starts_with(":: macro_magic :: forward_tokens ! { a :: Foo ,"));
+        assert!(output.contains("{ import b :: Bar ; stage target ; }"));
+    }
+
+    #[test]
+    fn forward_pipeline_state_round_trip() {
+        let output = forward_pipeline_inner_internal(quote! {
+            __private_macro_magic_tokens_forwarded struct Foo {},
+            {
+                __private_macro_magic_pipeline { ::macro_magic } { 1 } { 32 } { a::Foo } {} { stage target; } { x }
+            }
+        })
+        .unwrap();
+        let Item::Macro(item_macro) = parse2::<Item>(output).unwrap() else {
+            panic!("expected a macro invocation");
+        };
+        assert_eq!(item_macro.mac.path.to_token_stream().to_string(), "target");
+        let forwarded = item_macro.mac.parse_body::<ForwardedPipeline>().unwrap();
+        assert!(forwarded.is_final_stage());
+        assert_eq!(forwarded.items.len(), 1);
+        assert_eq!(
+            forwarded.items[0].0.to_token_stream().to_string(),
+            "a :: Foo"
+        );
+        assert_eq!(forwarded.args.to_string(), "x");
+        assert!(forwarded.resume(forwarded.items.clone()).is_empty());
+    }
+
+    #[test]
+    fn forward_pipeline_max_depth() {
+        assert!(forward_pipeline_internal(quote!(a::Foo, b::Bar => target; max_depth = 2)).is_ok());
+        let err = forward_pipeline_inner_internal(quote! {
+            __private_macro_magic_pipeline { ::macro_magic } { 2 } { 2 } { a::Foo } {} { stage target; } {}
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("maximum depth of 2 hops"));
+    }
+
+    #[test]
+    fn forward_pipeline_duplicate_import() {
+        let err = forward_pipeline_inner_internal(quote! {
+            __private_macro_magic_tokens_forwarded struct Foo {},
+            {
+                __private_macro_magic_pipeline { ::macro_magic } { 1 } { 32 } { a::Foo } {}
+                { import a::Foo; stage target; } {}
+            }
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("duplicate pipeline import: `a :: Foo`"));
+    }
+}
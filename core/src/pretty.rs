@@ -0,0 +1,72 @@
+//! Contains the printer used wherever `macro_magic` renders tokens for humans (traces, docs,
+//! `stringify_item!` and friends). With the `pretty` feature enabled, tokens are formatted
+//! using `prettyplease`. Otherwise they are printed on a single line using `to_string()`, so
+//! proc macro crates that don't need formatted output don't have to compile a formatter.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+/// Pretty-prints the specified tokens using `prettyplease` (with the `pretty` feature enabled)
+/// if they parse as a sequence of items, otherwise falls back to the plain `to_string()`
+/// representation of the tokens.
+#[cfg(feature = "pretty")]
+pub fn pretty_print(tokens: &TokenStream2) -> String {
+    match syn::parse2::<syn::File>(tokens.clone()) {
+        Ok(file) if !file.items.is_empty() => prettyplease::unparse(&file),
+        _ => tokens.to_string(),
+    }
+}
+
+/// Pretty-prints the specified tokens using `prettyplease` (with the `pretty` feature enabled)
+/// if they parse as a sequence of items, otherwise falls back to the plain `to_string()`
+/// representation of the tokens.
+#[cfg(not(feature = "pretty"))]
+pub fn pretty_print(tokens: &TokenStream2) -> String {
+    tokens.to_string()
+}
+
+/// Returns the pretty-printed form of the type `ty`, i.e. `Option<&'a str>`.
+pub(crate) fn pretty_type(ty: &TokenStream2) -> String {
+    let pretty = pretty_print(&quote!(type __T = #ty;));
+    pretty
+        .trim()
+        .trim_start_matches("type __T = ")
+        .trim_end_matches(';')
+        .trim()
+        .to_string()
+}
+
+#[cfg(all(test, feature = "pretty"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_print_items() {
+        let tokens = quote! {
+            /// A store
+            #[derive(Debug, Clone)]
+            pub struct Store<'a, T: Copy + 'a> { pub items: Vec<&'a T>, count: [u8; 4] }
+            impl<'a, T> Store<'a, T> where T: Copy {
+                pub fn get(&self, key: usize) -> Option<&T> {
+                    let ok = LIMIT < key; let a = 1;
+                    if ok && !self.items.is_empty() { return None; } else { self.items.get(key).map(|v| *v) }
+                }
+            }
+        };
+        assert_eq!(
+            pretty_print(&tokens),
+            "/// A store\n#[derive(Debug, Clone)]\npub struct Store<'a, T: Copy + 'a> {\n    \
+            pub items: Vec<&'a T>,\n    count: [u8; 4],\n}\n\
+            impl<'a, T> Store<'a, T>\nwhere\n    T: Copy,\n{\n    \
+            pub fn get(&self, key: usize) -> Option<&T> {\n        \
+            let ok = LIMIT < key;\n        let a = 1;\n        \
+            if ok && !self.items.is_empty() {\n            return None;\n        \
+            } else {\n            self.items.get(key).map(|v| *v)\n        }\n    }\n}\n"
+        );
+        assert_eq!(pretty_print(&quote!(a + b)), "a + b");
+        assert_eq!(
+            pretty_type(&quote!(Option<&'a str>)),
+            "Option<&'a str>".to_string()
+        );
+    }
+}
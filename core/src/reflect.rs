@@ -0,0 +1,322 @@
+//! Contains the implementation behind the `reflect!` macro.
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{
+    parse2, Attribute, Expr, ExprLit, Fields, Generics, Ident, ImplItem, Item, Lit, Meta, Path,
+    Result, Signature, TraitItem, Type, Visibility,
+};
+
+use crate::{macro_magic_root, parse_forwarded_proc, pretty_print, pretty_type, trace_stage};
+
+/// Collapses the (possibly multi-line) output of [`pretty_print`] into a single line.
+fn single_line(pretty: &str) -> String {
+    pretty
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .replace("< ", "<")
+        .replace(", >", ">")
+}
+
+/// Returns the pretty-printed form of `ty`, i.e. `Option<&'a str>`.
+pub fn reflect_type_string(ty: &Type) -> String {
+    single_line(&pretty_type(&ty.to_token_stream()))
+}
+
+/// Returns the pretty-printed form of `sig`, i.e. `fn get(&self, key: u32) -> Option<&u8>`.
+pub fn reflect_signature_string(sig: &Signature) -> String {
+    let pretty = pretty_print(&quote!(#sig {}));
+    single_line(&pretty)
+        .trim_end_matches("{}")
+        .trim_end()
+        .to_string()
+}
+
+/// Returns the contents of the `#[doc = ".."]` attributes (i.e. doc comments) in `attrs`, one
+/// line per attribute, with the leading space of each line removed.
+pub fn reflect_docs(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(name_value) if name_value.path.is_ident("doc") => {
+                match &name_value.value {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => Some(lit_str.value()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(String::from).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the tokens of a `&[FieldInfo { .. }, ..]` slice describing `fields`.
+fn fields_info(fields: &Fields, mm_path: &Path) -> TokenStream2 {
+    let fields = fields.iter().map(|field| {
+        let name = match &field.ident {
+            Some(ident) => {
+                let name = ident.to_string();
+                quote!(::core::option::Option::Some(#name))
+            }
+            None => quote!(::core::option::Option::None),
+        };
+        let ty = reflect_type_string(&field.ty);
+        let visibility = match &field.vis {
+            Visibility::Inherited => String::new(),
+            vis => vis.to_token_stream().to_string().replace(' ', ""),
+        };
+        let docs = reflect_docs(&field.attrs);
+        quote! {
+            #mm_path::reflect::FieldInfo {
+                name: #name,
+                ty: #ty,
+                visibility: #visibility,
+                docs: #docs,
+            }
+        }
+    });
+    quote!(&[#(#fields),*])
+}
+
+/// Returns the tokens of a `MethodInfo { .. }` describing a method with the specified
+/// signature and attributes.
+fn method_info(sig: &Signature, attrs: &[Attribute], mm_path: &Path) -> TokenStream2 {
+    let name = sig.ident.to_string();
+    let signature = reflect_signature_string(sig);
+    let docs = reflect_docs(attrs);
+    quote! {
+        #mm_path::reflect::MethodInfo {
+            name: #name,
+            signature: #signature,
+            docs: #docs,
+        }
+    }
+}
+
+/// Generates an expression of type `macro_magic::reflect::ItemInfo` describing `item`, which
+/// was imported from `source`.
+///
+/// The expression only consists of literals, so it can be used to initialize a `const`.
+pub fn reflect_tokens(item: &Item, source: &Path) -> TokenStream2 {
+    let mm_path = macro_magic_root();
+    let no_generics = Generics::default();
+    let no_attrs = Vec::new();
+    let mut fields = quote!(&[]);
+    let mut variants = quote!(&[]);
+    let mut methods = quote!(&[]);
+    let (kind, name, generics, attrs) = match item {
+        Item::Struct(item) => {
+            fields = fields_info(&item.fields, &mm_path);
+            (
+                "Struct",
+                item.ident.to_string(),
+                &item.generics,
+                &item.attrs,
+            )
+        }
+        Item::Union(item) => {
+            fields = fields_info(&Fields::Named(item.fields.clone()), &mm_path);
+            ("Union", item.ident.to_string(), &item.generics, &item.attrs)
+        }
+        Item::Enum(item) => {
+            let infos = item.variants.iter().map(|variant| {
+                let name = variant.ident.to_string();
+                let fields = fields_info(&variant.fields, &mm_path);
+                let docs = reflect_docs(&variant.attrs);
+                quote! {
+                    #mm_path::reflect::VariantInfo {
+                        name: #name,
+                        fields: #fields,
+                        docs: #docs,
+                    }
+                }
+            });
+            variants = quote!(&[#(#infos),*]);
+            ("Enum", item.ident.to_string(), &item.generics, &item.attrs)
+        }
+        Item::Trait(item) => {
+            let infos = item.items.iter().filter_map(|trait_item| match trait_item {
+                TraitItem::Fn(item_fn) => Some(method_info(&item_fn.sig, &item_fn.attrs, &mm_path)),
+                _ => None,
+            });
+            methods = quote!(&[#(#infos),*]);
+            ("Trait", item.ident.to_string(), &item.generics, &item.attrs)
+        }
+        Item::Impl(item) => {
+            let infos = item.items.iter().filter_map(|impl_item| match impl_item {
+                ImplItem::Fn(item_fn) => Some(method_info(&item_fn.sig, &item_fn.attrs, &mm_path)),
+                _ => None,
+            });
+            methods = quote!(&[#(#infos),*]);
+            let name = reflect_type_string(&item.self_ty);
+            ("Impl", name, &item.generics, &item.attrs)
+        }
+        Item::Fn(item) => {
+            let info = method_info(&item.sig, &item.attrs, &mm_path);
+            methods = quote!(&[#info]);
+            (
+                "Fn",
+                item.sig.ident.to_string(),
+                &item.sig.generics,
+                &item.attrs,
+            )
+        }
+        Item::Const(item) => ("Const", item.ident.to_string(), &item.generics, &item.attrs),
+        Item::Static(item) => ("Static", item.ident.to_string(), &no_generics, &item.attrs),
+        Item::Type(item) => ("Type", item.ident.to_string(), &item.generics, &item.attrs),
+        Item::Mod(item) => ("Mod", item.ident.to_string(), &no_generics, &item.attrs),
+        _ => ("Other", String::new(), &no_generics, &no_attrs),
+    };
+    let kind = Ident::new(kind, Span::call_site());
+    let generics = match generics.params.is_empty() {
+        true => String::new(),
+        false => {
+            // print the params via a dummy struct so that bounds are formatted consistently
+            let pretty = single_line(&pretty_print(&quote!(struct __T #generics;)));
+            pretty
+                .trim_start_matches("struct __T")
+                .trim_end_matches(';')
+                .to_string()
+        }
+    };
+    let docs = reflect_docs(attrs);
+    let path = source.to_token_stream().to_string().replace(' ', "");
+    quote! {
+        #mm_path::reflect::ItemInfo {
+            name: #name,
+            path: #path,
+            kind: #mm_path::reflect::ItemKind::#kind,
+            generics: #generics,
+            docs: #docs,
+            fields: #fields,
+            variants: #variants,
+            methods: #methods,
+        }
+    }
+}
+
+/// Internal implementation of the `reflect!` macro.
+///
+/// When invoked by the user, `tokens` contain the path of an `#[export_tokens]` item, whose
+/// tokens are forwarded back to `reflect!` along with the path. When invoked with forwarded
+/// tokens, the descriptor is generated via [`reflect_tokens`].
+pub fn reflect_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output = match parse_forwarded_proc(&tokens) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let source = parse2::<Path>(forwarded.args)?;
+            reflect_tokens(&forwarded.item, &source)
+        }
+        None => {
+            let source = parse2::<Path>(tokens.clone())?;
+            let mm_path = macro_magic_root();
+            quote! {
+                #mm_path::forward_tokens! {
+                    #source,
+                    #mm_path::__private::reflect,
+                    #mm_path,
+                    args = { #source }
+                }
+            }
+        }
+    };
+    trace_stage("reflect", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(all(test, feature = "pretty"))]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn reflect_strings() {
+        assert_eq!(
+            reflect_type_string(&parse_quote!(Option<&'a ::core::primitive::str>)),
+            "Option<&'a ::core::primitive::str>"
+        );
+        assert_eq!(
+            reflect_signature_string(&parse_quote!(
+                async fn get<T: Into<u32>>(&mut self, key: T) -> Option<&Self::Value>
+            )),
+            "async fn get<T: Into<u32>>(&mut self, key: T) -> Option<&Self::Value>"
+        );
+        let item: syn::ItemStruct = parse_quote! {
+            /// First line.
+            ///
+            ///   Indented.
+            struct Foo;
+        };
+        assert_eq!(reflect_docs(&item.attrs), "First line.\n\n  Indented.");
+    }
+
+    #[test]
+    fn reflect_struct() {
+        let output = reflect_tokens(
+            &parse_quote! {
+                /// A config.
+                pub struct Config<'a, T: Copy> {
+                    /// The host.
+                    pub host: &'a str,
+                    pub(crate) port: u16,
+                    extra: Vec<T>,
+                }
+            },
+            &parse_quote!(upstream::Config),
+        )
+        .to_string();
+        assert!(output.contains("name : \"Config\" , path : \"upstream::Config\""));
+        assert!(output.contains("kind : :: macro_magic :: reflect :: ItemKind :: Struct"));
+        assert!(output.contains("generics : \"<'a, T: Copy>\""));
+        assert!(output.contains("docs : \"A config.\""));
+        assert!(output.contains(
+            "name : :: core :: option :: Option :: Some (\"host\") , ty : \"&'a str\" , \
+            visibility : \"pub\" , docs : \"The host.\""
+        ));
+        assert!(output.contains("visibility : \"pub(crate)\""));
+        assert!(output.contains("ty : \"Vec<T>\" , visibility : \"\""));
+        assert!(output.contains("variants : & [] , methods : & []"));
+    }
+
+    #[test]
+    fn reflect_enum_and_trait() {
+        let output = reflect_tokens(
+            &parse_quote! {
+                enum Event {
+                    /// Started.
+                    Started,
+                    Progress(u8),
+                }
+            },
+            &parse_quote!(Event),
+        )
+        .to_string();
+        assert!(output.contains("name : \"Started\" , fields : & [] , docs : \"Started.\""));
+        assert!(output.contains("name : :: core :: option :: Option :: None , ty : \"u8\""));
+        let output = reflect_tokens(
+            &parse_quote! {
+                trait Repo {
+                    type Item;
+                    /// Finds an item.
+                    fn find(&self, name: &str) -> Option<u32>;
+                }
+            },
+            &parse_quote!(Repo),
+        )
+        .to_string();
+        assert!(output.contains(
+            "name : \"find\" , signature : \"fn find(&self, name: &str) -> Option<u32>\" , docs \
+            : \"Finds an item.\""
+        ));
+    }
+}
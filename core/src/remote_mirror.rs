@@ -0,0 +1,321 @@
+//! Contains the implementation behind the `#[remote_mirror]` attribute macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parse2, parse_quote, spanned::Spanned, Attribute, Error, Fields, Item, ItemEnum, ItemStruct,
+    Path, Result,
+};
+
+use crate::{
+    fields_binding, forward_to_builtin_attr, parse_forwarded_attr, trace_stage, variant_binding,
+};
+
+/// Returns the string form of `path` expected by `#[serde(remote = "...")]`, i.e.
+/// `upstream::Duration`.
+fn remote_path_string(path: &Path) -> String {
+    let segments: Vec<String> = path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect();
+    match path.leading_colon {
+        Some(_) => format!("::{}", segments.join("::")),
+        None => segments.join("::"),
+    }
+}
+
+/// Only keeps the `#[serde(..)]` and `#[doc]` attributes, since any other attributes of the
+/// upstream definition (i.e. those of other derive macros) are meaningless on the mirror.
+fn retain_mirrored_attrs(attrs: &mut Vec<Attribute>) {
+    attrs.retain(|attr| attr.path().is_ident("serde") || attr.path().is_ident("doc"));
+}
+
+/// Strips the fields of a mirrored struct or variant of any non-serde attributes.
+fn retain_mirrored_field_attrs(fields: &mut Fields) {
+    for field in fields.iter_mut() {
+        retain_mirrored_attrs(&mut field.attrs);
+    }
+}
+
+/// Generates a serde remote definition of the `foreign` struct or enum (imported from
+/// `source`) named after and carrying the attributes of the `local` item, along with `From`
+/// impls converting field-by-field between the mirror and the upstream type in both
+/// directions.
+///
+/// `local` must be a struct or enum without any fields or variants, since its body is
+/// replaced by that of `foreign`.
+pub fn remote_mirror_tokens(local: Item, foreign: &Item, source: &Path) -> Result<TokenStream2> {
+    let (attrs, vis, ident, has_body) = match &local {
+        Item::Struct(item_struct) => (
+            &item_struct.attrs,
+            &item_struct.vis,
+            &item_struct.ident,
+            !item_struct.fields.is_empty(),
+        ),
+        Item::Enum(item_enum) => (
+            &item_enum.attrs,
+            &item_enum.vis,
+            &item_enum.ident,
+            !item_enum.variants.is_empty(),
+        ),
+        _ => {
+            return Err(Error::new(
+                local.span(),
+                "`#[remote_mirror]` can only be attached to a struct or enum",
+            ))
+        }
+    };
+    if has_body {
+        return Err(Error::new(
+            ident.span(),
+            format!(
+                "`{}` must not define any fields or variants, since they are mirrored from `{}`",
+                ident,
+                source.to_token_stream()
+            ),
+        ));
+    }
+    // serde's derive helper attributes must come after the derive itself
+    let remote = remote_path_string(source);
+    let mut attrs = attrs.clone();
+    attrs.push(parse_quote!(#[serde(remote = #remote)]));
+    let (mirror, generics, into_mirror, into_foreign) = match foreign {
+        Item::Struct(foreign) => {
+            let mut fields = foreign.fields.clone();
+            retain_mirrored_field_attrs(&mut fields);
+            let mirror = ItemStruct {
+                attrs,
+                vis: vis.clone(),
+                struct_token: foreign.struct_token,
+                ident: ident.clone(),
+                generics: foreign.generics.clone(),
+                fields,
+                semi_token: foreign.semi_token,
+            };
+            let foreign_binding = fields_binding(&source.to_token_stream(), &foreign.fields);
+            let self_binding = fields_binding(&quote!(Self), &foreign.fields);
+            let mirror_binding = fields_binding(&quote!(#ident), &foreign.fields);
+            (
+                mirror.to_token_stream(),
+                &foreign.generics,
+                quote! {
+                    let #foreign_binding = value;
+                    #self_binding
+                },
+                quote! {
+                    let #mirror_binding = value;
+                    #self_binding
+                },
+            )
+        }
+        Item::Enum(foreign) => {
+            let mut variants = foreign.variants.clone();
+            for variant in variants.iter_mut() {
+                retain_mirrored_attrs(&mut variant.attrs);
+                retain_mirrored_field_attrs(&mut variant.fields);
+            }
+            let mirror = ItemEnum {
+                attrs,
+                vis: vis.clone(),
+                enum_token: foreign.enum_token,
+                ident: ident.clone(),
+                generics: foreign.generics.clone(),
+                brace_token: foreign.brace_token,
+                variants,
+            };
+            let source_prefix = source.to_token_stream();
+            let self_prefix = quote!(Self);
+            let mirror_prefix = quote!(#ident);
+            let into_mirror = foreign.variants.iter().map(|variant| {
+                let foreign_binding = variant_binding(&source_prefix, variant);
+                let self_binding = variant_binding(&self_prefix, variant);
+                quote!(#foreign_binding => #self_binding)
+            });
+            let into_foreign = foreign.variants.iter().map(|variant| {
+                let mirror_binding = variant_binding(&mirror_prefix, variant);
+                let self_binding = variant_binding(&self_prefix, variant);
+                quote!(#mirror_binding => #self_binding)
+            });
+            (
+                mirror.to_token_stream(),
+                &foreign.generics,
+                quote!(match value { #(#into_mirror),* }),
+                quote!(match value { #(#into_foreign),* }),
+            )
+        }
+        _ => {
+            return Err(Error::new(
+                source.span(),
+                format!(
+                    "`{}` is not a struct or enum, so it cannot be mirrored",
+                    source.to_token_stream()
+                ),
+            ))
+        }
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let foreign_ty = quote!(#source #ty_generics);
+    let mirror_ty = quote!(#ident #ty_generics);
+    Ok(quote! {
+        #mirror
+
+        impl #impl_generics ::core::convert::From<#foreign_ty> for #mirror_ty #where_clause {
+            fn from(value: #foreign_ty) -> Self {
+                #into_mirror
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<#mirror_ty> for #foreign_ty #where_clause {
+            fn from(value: #mirror_ty) -> Self {
+                #into_foreign
+            }
+        }
+    })
+}
+
+/// Internal implementation of the `#[remote_mirror]` attribute macro.
+///
+/// When invoked by the user, `attr` contains the path of an `#[export_tokens]` struct or enum,
+/// whose tokens are forwarded back to `#[remote_mirror]`. When invoked with forwarded tokens,
+/// the attached item is replaced by the serde remote definition generated by
+/// [`remote_mirror_tokens`].
+pub fn remote_mirror_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let attr = attr.into();
+    let tokens = tokens.into();
+    let output = match parse_forwarded_attr(&attr) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let source = parse2::<Path>(forwarded.source_path)?;
+            let foreign = parse2::<Item>(forwarded.imported_item.to_token_stream())?;
+            let local = parse2::<Item>(forwarded.tokens_ident)?;
+            remote_mirror_tokens(local, &foreign, &source)?
+        }
+        None => {
+            let source = parse2::<Path>(attr.clone())?;
+            let local = match parse2::<Item>(tokens.clone()) {
+                Ok(local @ (Item::Struct(_) | Item::Enum(_))) => local,
+                _ => {
+                    return Err(Error::new(
+                        tokens.span(),
+                        "`#[remote_mirror]` can only be attached to a struct or enum",
+                    ))
+                }
+            };
+            forward_to_builtin_attr(
+                "remote_mirror",
+                &source,
+                &local.to_token_stream(),
+                &TokenStream2::new(),
+            )
+        }
+    };
+    trace_stage("remote_mirror", None, None, &quote!(#attr #tokens), &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_mirror_struct() {
+        let output = remote_mirror_tokens(
+            parse_quote! {
+                #[derive(Serialize)]
+                pub struct DurationDef;
+            },
+            &parse_quote! {
+                #[derive(Clone)]
+                pub struct Duration {
+                    /// Whole seconds.
+                    #[serde(rename = "s")]
+                    #[other]
+                    pub secs: u64,
+                    pub nanos: u32,
+                }
+            },
+            &parse_quote!(::upstream::Duration),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.starts_with(
+            "# [derive (Serialize)] # [serde (remote = \"::upstream::Duration\")] pub struct \
+            DurationDef { # [doc = r\" Whole seconds.\"] # [serde (rename = \"s\")] pub secs : \
+            u64 , pub nanos : u32 , }"
+        ));
+        assert!(!output.contains("Clone"));
+        assert!(!output.contains("other"));
+        assert!(output.contains(
+            "let :: upstream :: Duration { secs , nanos } = value ; Self { secs , nanos }"
+        ));
+        assert!(output.contains("let DurationDef { secs , nanos } = value ; Self { secs , nanos }"));
+    }
+
+    #[test]
+    fn remote_mirror_enum_and_generics() {
+        let output = remote_mirror_tokens(
+            parse_quote!(
+                enum ShapeDef {}
+            ),
+            &parse_quote! {
+                enum Shape<T: Copy> {
+                    Circle(T),
+                    Rect { w: T, h: T },
+                    Empty,
+                }
+            },
+            &parse_quote!(upstream::Shape),
+        )
+        .unwrap()
+        .to_string();
+        assert!(output.contains("enum ShapeDef < T : Copy > { Circle (T) ,"));
+        assert!(output.contains(
+            "impl < T : Copy > :: core :: convert :: From < upstream :: Shape < T > > for ShapeDef < T >"
+        ));
+        assert!(output.contains("upstream :: Shape :: Rect { w , h } => Self :: Rect { w , h }"));
+        assert!(output.contains("ShapeDef :: Circle (__0) => Self :: Circle (__0)"));
+    }
+
+    #[test]
+    fn remote_mirror_invalid_items() {
+        let foreign: Item = parse_quote!(
+            struct Duration(u64);
+        );
+        let source: Path = parse_quote!(upstream::Duration);
+        let Err(err) = remote_mirror_tokens(
+            parse_quote!(
+                struct DurationDef(u64);
+            ),
+            &foreign,
+            &source,
+        ) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "`DurationDef` must not define any fields or variants, since they are mirrored from \
+            `upstream :: Duration`"
+        );
+        assert!(remote_mirror_tokens(
+            parse_quote!(
+                struct DurationDef;
+            ),
+            &parse_quote!(
+                fn duration() {}
+            ),
+            &source
+        )
+        .is_err());
+        assert!(remote_mirror_internal(
+            quote!(upstream::Duration),
+            quote!(
+                fn local() {}
+            )
+        )
+        .is_err());
+    }
+}
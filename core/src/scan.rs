@@ -0,0 +1,382 @@
+//! Contains an offline scanner that reads the source of a crate from disk and finds its
+//! `#[export_tokens]` items, without compiling the crate.
+
+use std::{
+    fs, io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::{parse2, Attribute, Expr, ExprLit, Ident, Item, ItemMod, Lit, Meta, Visibility};
+
+use crate::{
+    export_tokens_item_ident, export_tokens_macro_ident, item_kind, ExportTemplateArgs,
+    ExportTokensArgs,
+};
+
+/// A module of a scanned crate, which is either a file or an inline `mod { .. }`.
+pub struct ScannedModule {
+    /// The path of the module relative to the crate root, which is empty for the root itself
+    pub path: Vec<String>,
+    /// Whether the module and all of its ancestors are `pub`, in which case its items can be
+    /// referred to from other crates
+    pub accessible: bool,
+    /// The file the module is defined in
+    pub file: PathBuf,
+    /// The items of the module
+    pub items: Vec<Item>,
+}
+
+/// An item of a scanned crate with an `#[export_tokens]` attribute (or an alias of it)
+/// attached.
+pub struct ScannedExport {
+    /// The path of the module containing the item, relative to the crate root
+    pub module: Vec<String>,
+    /// The ident the item is exported under, which is the override ident passed to the
+    /// attribute if one was specified
+    pub ident: Ident,
+    /// The item, including all of its attributes
+    pub item: Item,
+    /// The export attribute attached to the item
+    pub attr: Attribute,
+    /// Whether the item itself is emitted, which is `false` for `#[export_tokens_no_emit]` and
+    /// `#[export_template]`
+    pub emit: bool,
+    /// Whether the module containing the item is accessible from other crates
+    pub accessible: bool,
+    /// The file the item is defined in
+    pub file: PathBuf,
+}
+
+impl ScannedExport {
+    /// Returns the path the item is exported under, relative to the crate root, i.e.
+    /// `config::Timeout`.
+    pub fn path(&self) -> String {
+        let mut path = self.module.clone();
+        path.push(self.ident.to_string());
+        path.join("::")
+    }
+
+    /// Returns the kind of the item as it is written in the source, i.e. `struct` or `fn`.
+    pub fn kind(&self) -> &'static str {
+        item_kind(&self.item)
+    }
+
+    /// Returns the ident of the hidden macro that is re-exported next to the item, i.e.
+    /// `__export_tokens_tt_timeout`, as computed by [`export_tokens_macro_ident`].
+    pub fn macro_ident(&self) -> Ident {
+        export_tokens_macro_ident(&self.ident)
+    }
+
+    /// Returns the path of the hidden macro, relative to the crate root, i.e.
+    /// `config::__export_tokens_tt_timeout`.
+    pub fn macro_path(&self) -> String {
+        let mut path = self.module.clone();
+        path.push(self.macro_ident().to_string());
+        path.join("::")
+    }
+}
+
+/// The result of [`scan_crate`].
+pub struct CrateScan {
+    /// Every module of the crate, in the order they were visited
+    pub modules: Vec<ScannedModule>,
+    /// Every exported item of the crate, sorted by path
+    pub exports: Vec<ScannedExport>,
+}
+
+impl CrateScan {
+    /// Returns the files the crate consists of, i.e. to emit `cargo:rerun-if-changed` for.
+    pub fn files(&self) -> Vec<&FsPath> {
+        let mut files: Vec<&FsPath> = Vec::new();
+        for module in &self.modules {
+            if !files.contains(&module.file.as_path()) {
+                files.push(&module.file);
+            }
+        }
+        files
+    }
+}
+
+/// Returns the attributes of `item`, or an empty slice for items without attributes.
+pub(crate) fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// Returns the attributes of `item` mutably, or [`None`] for items without attributes.
+pub(crate) fn item_attrs_mut(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    match item {
+        Item::Const(item) => Some(&mut item.attrs),
+        Item::Enum(item) => Some(&mut item.attrs),
+        Item::ExternCrate(item) => Some(&mut item.attrs),
+        Item::Fn(item) => Some(&mut item.attrs),
+        Item::Impl(item) => Some(&mut item.attrs),
+        Item::Macro(item) => Some(&mut item.attrs),
+        Item::Mod(item) => Some(&mut item.attrs),
+        Item::Static(item) => Some(&mut item.attrs),
+        Item::Struct(item) => Some(&mut item.attrs),
+        Item::Trait(item) => Some(&mut item.attrs),
+        Item::TraitAlias(item) => Some(&mut item.attrs),
+        Item::Type(item) => Some(&mut item.attrs),
+        Item::Union(item) => Some(&mut item.attrs),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the attributes contain `#[cfg(test)]`.
+fn is_cfg_test(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .meta
+                .require_list()
+                .is_ok_and(|list| list.tokens.to_string() == "test")
+    })
+}
+
+/// Returns the value of the `#[path = ".."]` attribute in `attrs`, if there is one.
+fn path_attr(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(name_value) if name_value.path.is_ident("path") => {
+            match &name_value.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => Some(lit_str.value()),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Returns the export attribute in `attrs` along with whether it emits the item, if there is
+/// one. `aliases` are the names of additional attributes that behave like `#[export_tokens]`.
+pub(crate) fn export_attr<'a>(
+    attrs: &'a [Attribute],
+    aliases: &[&str],
+) -> Option<(&'a Attribute, bool)> {
+    attrs.iter().find_map(|attr| {
+        let name = attr.path().segments.last()?.ident.to_string();
+        match name.as_str() {
+            "export_tokens" => Some((attr, true)),
+            "export_tokens_no_emit" | "export_template" => Some((attr, false)),
+            name if aliases.contains(&name) => Some((attr, true)),
+            _ => None,
+        }
+    })
+}
+
+/// Returns the ident `item` is exported under by its export attribute `attr`, which is the
+/// override ident passed to the attribute if one was specified, or [`None`] if the item has
+/// no ident of its own and none was specified.
+pub(crate) fn export_ident(item: &Item, attr: &Attribute) -> Option<Ident> {
+    if attr.path().segments.last()?.ident == "export_template" {
+        let name = attr
+            .parse_args::<ExportTemplateArgs>()
+            .ok()
+            .and_then(|args| args.name);
+        return name.or_else(|| export_tokens_item_ident(item));
+    }
+    let args = match &attr.meta {
+        Meta::List(list) => parse2::<ExportTokensArgs>(list.tokens.clone()).ok(),
+        _ => parse2::<ExportTokensArgs>(TokenStream2::new()).ok(),
+    };
+    args.and_then(|args| args.ident)
+        .or_else(|| export_tokens_item_ident(item))
+}
+
+/// Walks the modules of a crate, building up a [`CrateScan`].
+struct Scanner<'a> {
+    aliases: &'a [&'a str],
+    scan: CrateScan,
+}
+
+impl Scanner<'_> {
+    /// Records the module with the specified items and descends into its child modules. `dir`
+    /// is the directory the files of child modules are located in.
+    fn module(
+        &mut self,
+        items: Vec<Item>,
+        path: Vec<String>,
+        accessible: bool,
+        file: &FsPath,
+        dir: &FsPath,
+    ) -> io::Result<()> {
+        for item in &items {
+            if let Some((attr, emit)) = export_attr(item_attrs(item), self.aliases) {
+                if let Some(ident) = export_ident(item, attr) {
+                    self.scan.exports.push(ScannedExport {
+                        module: path.clone(),
+                        ident,
+                        item: item.clone(),
+                        attr: attr.clone(),
+                        emit,
+                        accessible,
+                        file: file.to_path_buf(),
+                    });
+                }
+            }
+        }
+        let children: Vec<_> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Mod(item_mod) if !is_cfg_test(&item_mod.attrs) => Some(item_mod.clone()),
+                _ => None,
+            })
+            .collect();
+        self.scan.modules.push(ScannedModule {
+            path: path.clone(),
+            accessible,
+            file: file.to_path_buf(),
+            items,
+        });
+        for item_mod in children {
+            let name = item_mod.ident.to_string();
+            let mut child = path.clone();
+            child.push(name.clone());
+            let accessible = accessible && matches!(item_mod.vis, Visibility::Public(_));
+            match item_mod.content {
+                Some((_, items)) => {
+                    self.module(items, child, accessible, file, &dir.join(&name))?;
+                }
+                None => {
+                    let Some(child_file) = module_file(&item_mod, dir) else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("could not find the file of module `{}`", child.join("::")),
+                        ));
+                    };
+                    self.file(&child_file, child, accessible)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the module file `file` and records it via [`Scanner::module`].
+    fn file(&mut self, file: &FsPath, path: Vec<String>, accessible: bool) -> io::Result<()> {
+        let parsed = parse_source_file(file)?;
+        let dir = child_module_dir(file, path.is_empty());
+        self.module(parsed.items, path, accessible, file, &dir)
+    }
+}
+
+/// Reads and parses the source file `file`.
+pub(crate) fn parse_source_file(file: &FsPath) -> io::Result<syn::File> {
+    let source = fs::read_to_string(file)?;
+    syn::parse_file(&source).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse {}: {}", file.display(), err),
+        )
+    })
+}
+
+/// Returns the directory the files of the child modules of the module file `file` are located
+/// in, i.e. `src/` for `src/lib.rs` and `src/shapes/` for `src/shapes.rs`.
+pub(crate) fn child_module_dir(file: &FsPath, is_crate_root: bool) -> PathBuf {
+    let parent = file.parent().unwrap_or(FsPath::new(""));
+    let is_mod_root = matches!(
+        file.file_name().and_then(|name| name.to_str()),
+        Some("lib.rs" | "main.rs" | "mod.rs")
+    ) || is_crate_root;
+    match (is_mod_root, file.file_stem()) {
+        (false, Some(stem)) => parent.join(stem),
+        _ => parent.to_path_buf(),
+    }
+}
+
+/// Returns the file of the non-inline module `item_mod`, honoring `#[path = ".."]`, where `dir`
+/// is the directory the files of its sibling modules are located in, or [`None`] if the file
+/// doesn't exist.
+pub(crate) fn module_file(item_mod: &ItemMod, dir: &FsPath) -> Option<PathBuf> {
+    let name = item_mod.ident.to_string();
+    let candidates = match path_attr(&item_mod.attrs) {
+        Some(path) => vec![dir.join(path)],
+        None => vec![
+            dir.join(format!("{}.rs", name)),
+            dir.join(&name).join("mod.rs"),
+        ],
+    };
+    candidates.into_iter().find(|file| file.is_file())
+}
+
+/// Reads the crate located at `crate_dir` and finds every `#[export_tokens]`,
+/// `#[export_tokens_no_emit]` and `#[export_template]` item, along with items that use one of the specified `aliases`
+/// (created via `export_tokens_alias!`), which are assumed to emit the item.
+///
+/// The crate is read starting at `src/lib.rs` (or `src/main.rs`), following `mod`
+/// declarations (including `#[path = ".."]`) and skipping `#[cfg(test)]` modules. Modules
+/// generated by macros or gated by `#[cfg(..)]` conditions are not evaluated.
+pub fn scan_crate(crate_dir: impl AsRef<FsPath>, aliases: &[&str]) -> io::Result<CrateScan> {
+    let src = crate_dir.as_ref().join("src");
+    let root = [src.join("lib.rs"), src.join("main.rs")]
+        .into_iter()
+        .find(|file| file.is_file())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} contains no lib.rs or main.rs", src.display()),
+            )
+        })?;
+    let mut scanner = Scanner {
+        aliases,
+        scan: CrateScan {
+            modules: Vec::new(),
+            exports: Vec::new(),
+        },
+    };
+    scanner.file(&root, Vec::new(), true)?;
+    let mut scan = scanner.scan;
+    scan.exports.sort_by_key(|export| export.path());
+    Ok(scan)
+}
+
+/// Returns `true` if `a` and `b` are the same attribute.
+pub(crate) fn same_attr(a: &Attribute, b: &Attribute) -> bool {
+    a.to_token_stream().to_string() == b.to_token_stream().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_external_crate() {
+        let crate_dir = FsPath::new(env!("CARGO_MANIFEST_DIR")).join("../tests/external_crate");
+        let scan = scan_crate(&crate_dir, &[]).unwrap();
+        let timeout = scan
+            .exports
+            .iter()
+            .find(|export| export.path() == "config::Timeout")
+            .unwrap();
+        assert!(timeout.accessible && timeout.emit);
+        assert_eq!(timeout.macro_path(), "config::__export_tokens_tt_timeout");
+        let square_shape = scan
+            .exports
+            .iter()
+            .find(|export| export.ident == "square_shape")
+            .unwrap();
+        assert_eq!(square_shape.module, ["inherit"]);
+        assert!(matches!(square_shape.item, Item::Impl(_)));
+        assert_eq!(scan.files().len(), 1);
+        assert!(scan.modules.iter().any(|module| module.path == ["config"]));
+    }
+}
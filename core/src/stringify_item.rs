@@ -0,0 +1,149 @@
+//! Contains the implementation behind the `stringify_item!` macro.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2, Error, Item, LitStr, Path, Result, Token,
+};
+
+use crate::{keywords, macro_magic_root, parse_forwarded_proc, pretty_print, trace_stage};
+
+/// Used to parse the args of [`stringify_item_internal`], i.e. `upstream::Thing` or
+/// `upstream::Thing, pretty`.
+///
+/// You shouldn't need to use this directly.
+pub struct StringifyItemArgs {
+    /// The path of the `#[export_tokens]` item that will be stringified
+    pub source: Path,
+    /// Whether the item will be pretty-printed
+    pub pretty: Option<keywords::pretty>,
+}
+
+impl Parse for StringifyItemArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse::<Path>()?;
+        let mut pretty = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let keyword = input.parse::<keywords::pretty>()?;
+            if !cfg!(feature = "pretty") {
+                return Err(Error::new(
+                    keyword.span,
+                    "`pretty` requires the `pretty` feature of `macro_magic`",
+                ));
+            }
+            pretty = Some(keyword);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after `pretty`"));
+        }
+        Ok(StringifyItemArgs { source, pretty })
+    }
+}
+
+impl ToTokens for StringifyItemArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.source.to_tokens(tokens);
+        if let Some(pretty) = &self.pretty {
+            tokens.extend(quote!(, #pretty));
+        }
+    }
+}
+
+/// Returns the string form of `item`. By default this is the same as what `stringify!` would
+/// produce for the item, while `pretty` formats it using [`pretty_print`], without a trailing
+/// newline.
+pub fn stringify_item_string(item: &Item, pretty: bool) -> String {
+    let tokens = item.to_token_stream();
+    match pretty {
+        true => pretty_print(&tokens).trim_end().to_string(),
+        false => tokens.to_string(),
+    }
+}
+
+/// Internal implementation of the `stringify_item!` macro.
+///
+/// When invoked by the user, `tokens` are parsed as [`StringifyItemArgs`] and the tokens of the
+/// `#[export_tokens]` item are forwarded back to `stringify_item!` along with these args. When
+/// invoked with forwarded tokens, the item is expanded to a string literal via
+/// [`stringify_item_string`].
+pub fn stringify_item_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output = match parse_forwarded_proc(&tokens) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<StringifyItemArgs>(forwarded.args)?;
+            let string = stringify_item_string(&forwarded.item, args.pretty.is_some());
+            let lit = LitStr::new(&string, args.source.segments.last().unwrap().ident.span());
+            quote!(#lit)
+        }
+        None => {
+            let args = parse2::<StringifyItemArgs>(tokens.clone())?;
+            let mm_path = macro_magic_root();
+            let source = &args.source;
+            quote! {
+                #mm_path::forward_tokens! {
+                    #source,
+                    #mm_path::__private::stringify_item,
+                    #mm_path,
+                    args = { #args }
+                }
+            }
+        }
+    };
+    trace_stage("stringify_item", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn stringify_item_forms() {
+        let item: Item = parse_quote! {
+            pub struct Config<T> { pub host: T, port: u16 }
+        };
+        assert_eq!(
+            stringify_item_string(&item, false),
+            "pub struct Config < T > { pub host : T , port : u16 }"
+        );
+        #[cfg(feature = "pretty")]
+        assert_eq!(
+            stringify_item_string(&item, true),
+            "pub struct Config<T> {\n    pub host: T,\n    port: u16,\n}"
+        );
+    }
+
+    #[test]
+    fn stringify_item_args() {
+        #[cfg(feature = "pretty")]
+        {
+            let args = parse2::<StringifyItemArgs>(quote!(upstream::Config, pretty)).unwrap();
+            assert!(args.pretty.is_some());
+            assert_eq!(
+                args.to_token_stream().to_string(),
+                "upstream :: Config , pretty"
+            );
+        }
+        #[cfg(not(feature = "pretty"))]
+        assert!(parse2::<StringifyItemArgs>(quote!(upstream::Config, pretty)).is_err());
+        assert!(parse2::<StringifyItemArgs>(quote!(upstream::Config))
+            .unwrap()
+            .pretty
+            .is_none());
+        assert!(parse2::<StringifyItemArgs>(quote!(upstream::Config, ugly)).is_err());
+    }
+
+    #[test]
+    fn stringify_item_forwards() {
+        let output = stringify_item_internal(quote!(upstream::Config))
+            .unwrap()
+            .to_string();
+        assert!(output.contains("forward_tokens !"));
+        assert!(output.contains(":: __private :: stringify_item"));
+        assert!(output.contains("args = { upstream :: Config }"));
+    }
+}
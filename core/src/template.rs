@@ -0,0 +1,407 @@
+//! Contains the implementation behind `#[export_template]` and `instantiate!`, which export an
+//! item with placeholder idents and import it with the placeholders substituted.
+
+use proc_macro2::{Delimiter, Group, Spacing, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse2, parse_quote,
+    spanned::Spanned,
+    token::Comma,
+    Attribute, Error, Expr, Ident, Item, Path, Result, Token, Type,
+};
+
+use crate::{
+    export_tokens_internal, export_tokens_item_ident, item_attrs, item_attrs_mut, macro_magic_root,
+    parse_forwarded_proc, trace_stage,
+};
+
+/// The name of the attribute `#[export_template]` records the placeholders of a template in.
+/// It is attached to the exported tokens only, since templates are never emitted locally.
+pub const TEMPLATE_ATTR: &str = "__macro_magic_template";
+
+/// Parses the args of `#[export_template]`, i.e. `T, N`, optionally preceded by the name the
+/// template is exported under, i.e. `sized_buffer; T, N`, which is required for items without
+/// an ident of their own (such as impl blocks).
+///
+/// You shouldn't need to use this directly.
+pub struct ExportTemplateArgs {
+    /// The optional override name the template will be exported under
+    pub name: Option<Ident>,
+    /// The placeholder idents of the template
+    pub placeholders: Vec<Ident>,
+}
+
+impl Parse for ExportTemplateArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = match input.peek2(Token![;]) {
+            true => {
+                let name = input.parse()?;
+                input.parse::<Token![;]>()?;
+                Some(name)
+            }
+            false => None,
+        };
+        let placeholders: Vec<Ident> = input
+            .parse_terminated(Ident::parse, Comma)?
+            .into_iter()
+            .collect();
+        if placeholders.is_empty() {
+            return Err(Error::new(
+                Span::call_site(),
+                "expected at least one placeholder, i.e. `#[export_template(T, N)]`",
+            ));
+        }
+        for (i, placeholder) in placeholders.iter().enumerate() {
+            if placeholders[..i].contains(placeholder) {
+                return Err(Error::new(
+                    placeholder.span(),
+                    format!("placeholder `{}` is declared more than once", placeholder),
+                ));
+            }
+        }
+        Ok(ExportTemplateArgs { name, placeholders })
+    }
+}
+
+/// A `placeholder = value` binding passed to `instantiate!`.
+pub struct TemplateBinding {
+    /// The placeholder being bound
+    pub placeholder: Ident,
+    /// The tokens the placeholder will be replaced with, which are parsed as a type if
+    /// possible (so that i.e. `Result<u8, u16>` can be bound) and otherwise as an expression
+    pub value: TokenStream2,
+}
+
+impl Parse for TemplateBinding {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let placeholder = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let fork = input.fork();
+        let value = match fork.parse::<Type>() {
+            Ok(_) if fork.is_empty() || fork.peek(Token![,]) => {
+                input.parse::<Type>()?.to_token_stream()
+            }
+            _ => input.parse::<Expr>()?.to_token_stream(),
+        };
+        Ok(TemplateBinding { placeholder, value })
+    }
+}
+
+impl ToTokens for TemplateBinding {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let placeholder = &self.placeholder;
+        let value = &self.value;
+        tokens.extend(quote!(#placeholder = #value));
+    }
+}
+
+/// Parses the args of `instantiate!`, i.e. `upstream::sized_buffer, T = MyType, N = 8`.
+///
+/// You shouldn't need to use this directly.
+pub struct InstantiateArgs {
+    /// The path of the `#[export_template]` item
+    pub source: Path,
+    /// The bindings for the placeholders of the template
+    pub bindings: Vec<TemplateBinding>,
+}
+
+impl Parse for InstantiateArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = input.parse()?;
+        let mut bindings = Vec::new();
+        while input.parse::<Option<Comma>>()?.is_some() && !input.is_empty() {
+            bindings.push(input.parse()?);
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `,`"));
+        }
+        Ok(InstantiateArgs { source, bindings })
+    }
+}
+
+impl ToTokens for InstantiateArgs {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let source = &self.source;
+        let bindings = &self.bindings;
+        tokens.extend(quote!(#source #(, #bindings)*));
+    }
+}
+
+/// Returns `true` if `attr` is the placeholder attribute recorded by `#[export_template]`.
+fn is_template_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident(TEMPLATE_ATTR)
+}
+
+/// Returns the placeholders recorded by `#[export_template]` on `item`, or [`None`] if the
+/// item is not a template.
+pub fn template_placeholders(item: &Item) -> Option<Result<Vec<Ident>>> {
+    let attr = item_attrs(item)
+        .iter()
+        .find(|attr| is_template_attr(attr))?;
+    Some(attr.parse_args_with(|input: ParseStream| {
+        Ok(input
+            .parse_terminated(Ident::parse, Comma)?
+            .into_iter()
+            .collect())
+    }))
+}
+
+/// Replaces every occurrence of a bound placeholder ident in `tokens` with its value.
+///
+/// Idents that follow a `.` (field and method accesses) or `::` (later path segments) refer to
+/// members rather than placeholders and are left as they are. Values consisting of a single
+/// ident are substituted directly, so placeholders can also be used as names, while other
+/// values are wrapped in an invisible group, so that i.e. `N * 2` with `N = 4 + 4` keeps its
+/// meaning, just like a `$n:expr` fragment in a `macro_rules!` would.
+pub fn substitute_placeholders(
+    tokens: TokenStream2,
+    bindings: &[(Ident, TokenStream2)],
+) -> TokenStream2 {
+    let mut output = Vec::new();
+    let mut prev: [Option<TokenTree>; 2] = [None, None];
+    for token in tokens {
+        let substituted = match &token {
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(
+                    group.delimiter(),
+                    substitute_placeholders(group.stream(), bindings),
+                );
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group)
+            }
+            TokenTree::Ident(ident) if !is_member(&prev) => {
+                match bindings
+                    .iter()
+                    .find(|(placeholder, _)| placeholder == ident)
+                {
+                    Some((_, value)) => {
+                        let mut value_tokens = value.clone().into_iter();
+                        match (value_tokens.next(), value_tokens.next()) {
+                            (Some(TokenTree::Ident(value)), None) => TokenTree::Ident(value),
+                            _ => TokenTree::Group(Group::new(Delimiter::None, value.clone())),
+                        }
+                    }
+                    None => token.clone(),
+                }
+            }
+            _ => token.clone(),
+        };
+        output.push(substituted);
+        prev = [prev[1].take(), Some(token)];
+    }
+    output.into_iter().collect()
+}
+
+/// Returns `true` if the previous two tokens `prev` make the next ident a member access (i.e.
+/// `x.N`) or a later path segment (i.e. `a::N`), as opposed to a range such as `0..N`.
+fn is_member(prev: &[Option<TokenTree>; 2]) -> bool {
+    let punct = |token: &Option<TokenTree>, ch: char| match token {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ch => Some(punct.spacing()),
+        _ => None,
+    };
+    match (punct(&prev[0], '.'), punct(&prev[1], '.')) {
+        (Some(Spacing::Joint), Some(_)) => return false,
+        (_, Some(_)) => return true,
+        _ => (),
+    }
+    punct(&prev[0], ':') == Some(Spacing::Joint) && punct(&prev[1], ':').is_some()
+}
+
+/// Instantiates the template `item` (imported from `source`) by substituting its placeholders
+/// with `bindings`, returning an error if a placeholder is left unbound, bound more than once,
+/// or if a binding doesn't correspond to a placeholder.
+pub fn instantiate_template(
+    mut item: Item,
+    source: &Path,
+    bindings: &[TemplateBinding],
+) -> Result<TokenStream2> {
+    let Some(placeholders) = template_placeholders(&item) else {
+        return Err(Error::new(
+            source.span(),
+            format!(
+                "`{}` is not an `#[export_template]` item",
+                source.to_token_stream().to_string().replace(' ', "")
+            ),
+        ));
+    };
+    let placeholders = placeholders?;
+    let mut resolved: Vec<(Ident, TokenStream2)> = Vec::new();
+    for binding in bindings {
+        if !placeholders.contains(&binding.placeholder) {
+            let expected: Vec<String> = placeholders
+                .iter()
+                .map(|placeholder| format!("`{}`", placeholder))
+                .collect();
+            return Err(Error::new(
+                binding.placeholder.span(),
+                format!(
+                    "`{}` is not a placeholder of this template, expected one of {}",
+                    binding.placeholder,
+                    expected.join(", ")
+                ),
+            ));
+        }
+        if resolved
+            .iter()
+            .any(|(placeholder, _)| placeholder == &binding.placeholder)
+        {
+            return Err(Error::new(
+                binding.placeholder.span(),
+                format!(
+                    "placeholder `{}` is bound more than once",
+                    binding.placeholder
+                ),
+            ));
+        }
+        resolved.push((binding.placeholder.clone(), binding.value.clone()));
+    }
+    let missing: Vec<String> = placeholders
+        .iter()
+        .filter(|placeholder| resolved.iter().all(|(bound, _)| bound != *placeholder))
+        .map(|placeholder| format!("`{}`", placeholder))
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::new(
+            source.span(),
+            format!("missing bindings for placeholders {}", missing.join(", ")),
+        ));
+    }
+    if let Some(attrs) = item_attrs_mut(&mut item) {
+        attrs.retain(|attr| !is_template_attr(attr));
+    }
+    Ok(substitute_placeholders(item.to_token_stream(), &resolved))
+}
+
+/// Internal implementation of `#[export_template]`.
+///
+/// Records the placeholders in `attr` on the item in `tokens` and exports it via
+/// [`export_tokens_internal`] without emitting it locally, since the placeholders are not
+/// expected to resolve at the export site.
+pub fn export_template_internal<T1: Into<TokenStream2>, T2: Into<TokenStream2>>(
+    attr: T1,
+    tokens: T2,
+) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let args = parse2::<ExportTemplateArgs>(attr.into())?;
+    let mut item = parse2::<Item>(tokens.clone())?;
+    if args.name.is_none() && export_tokens_item_ident(&item).is_none() {
+        return Err(Error::new(
+            item.span(),
+            "this item type requires a name to be specified, i.e. \
+            `#[export_template(my_template; T)]`",
+        ));
+    }
+    let template_attr = Ident::new(TEMPLATE_ATTR, Span::call_site());
+    let placeholders = &args.placeholders;
+    let Some(attrs) = item_attrs_mut(&mut item) else {
+        return Err(Error::new(
+            item.span(),
+            "this item type cannot be a template",
+        ));
+    };
+    attrs.insert(0, parse_quote!(#[#template_attr(#(#placeholders),*)]));
+    let name = args
+        .name
+        .map(|name| name.to_token_stream())
+        .unwrap_or_default();
+    let output = export_tokens_internal(name, item.to_token_stream(), false, true)?;
+    trace_stage("export_template", None, None, &tokens, &output);
+    Ok(output)
+}
+
+/// Internal implementation of the `instantiate!` macro.
+///
+/// When invoked by the user, `tokens` are parsed as [`InstantiateArgs`] and the tokens of the
+/// `#[export_template]` item are forwarded back to `instantiate!` along with these args. When
+/// invoked with forwarded tokens, the template is expanded via [`instantiate_template`].
+pub fn instantiate_internal<T: Into<TokenStream2>>(tokens: T) -> Result<TokenStream2> {
+    let tokens = tokens.into();
+    let output = match parse_forwarded_proc(&tokens) {
+        Some(forwarded) => {
+            let forwarded = forwarded?;
+            let args = parse2::<InstantiateArgs>(forwarded.args)?;
+            instantiate_template(forwarded.item, &args.source, &args.bindings)?
+        }
+        None => {
+            let args = parse2::<InstantiateArgs>(tokens.clone())?;
+            let mm_path = macro_magic_root();
+            let source = &args.source;
+            quote! {
+                #mm_path::forward_tokens! {
+                    #source,
+                    #mm_path::__private::instantiate,
+                    #mm_path,
+                    args = { #args }
+                }
+            }
+        }
+    };
+    trace_stage("instantiate", None, None, &tokens, &output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Item {
+        parse_quote! {
+            #[__macro_magic_template(T, N)]
+            impl T {
+                pub const CAPACITY: usize = N;
+                fn ranges(&self) -> usize { self.N + (0..N).len() + Self::N }
+            }
+        }
+    }
+
+    fn instantiate(bindings: TokenStream2) -> Result<String> {
+        let args = parse2::<InstantiateArgs>(quote!(upstream::sized #bindings))?;
+        instantiate_template(template(), &args.source, &args.bindings).map(|t| t.to_string())
+    }
+
+    #[test]
+    fn instantiate_substitutes_placeholders() {
+        let output = instantiate(quote!(, T = Packet, N = 4 + 4)).unwrap();
+        let expected = quote! {
+            impl Packet {
+                pub const CAPACITY: usize = 4 + 4;
+                fn ranges(&self) -> usize { self.N + (0..4 + 4).len() + Self::N }
+            }
+        };
+        // invisible groups are printed transparently by the fallback implementation
+        assert_eq!(
+            output.replace(' ', ""),
+            expected.to_string().replace(' ', "")
+        );
+        let output = instantiate(quote!(, N = 2, T = Result<u8, u16>)).unwrap();
+        assert!(output.starts_with("impl Result < u8 , u16 >"));
+    }
+
+    #[test]
+    fn instantiate_checks_bindings() {
+        let error = |bindings| instantiate(bindings).unwrap_err().to_string();
+        assert_eq!(
+            error(quote!(, T = Packet)),
+            "missing bindings for placeholders `N`"
+        );
+        assert_eq!(
+            error(quote!(, T = Packet, N = 1, M = 2)),
+            "`M` is not a placeholder of this template, expected one of `T`, `N`"
+        );
+        assert_eq!(
+            error(quote!(, T = Packet, T = Packet, N = 1)),
+            "placeholder `T` is bound more than once"
+        );
+        let item: Item = parse_quote!(
+            struct Plain;
+        );
+        let source: Path = parse_quote!(upstream::Plain);
+        assert_eq!(
+            instantiate_template(item, &source, &[])
+                .unwrap_err()
+                .to_string(),
+            "`upstream::Plain` is not an `#[export_template]` item"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Contains the expansion tracing facility used to debug chains of `macro_magic` macros.
+//!
+//! Tracing is disabled by default. Setting the `MACRO_MAGIC_TRACE` environment variable
+//! (i.e. in the `[env]` section of `.cargo/config.toml` or directly on the command line)
+//! causes each stage of an `export_tokens` → `forward_tokens` → `forward_tokens_inner` → user
+//! macro chain to append a record of its input and output to a log file.
+
+use std::{
+    fs::{create_dir_all, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::Path;
+
+use crate::pretty_print;
+
+/// The environment variable used to enable expansion tracing.
+///
+/// Set to `1`, `true` or `all` to trace every stage, or to a comma-separated list of stage
+/// names (i.e. `forward_tokens,forward_tokens_inner`) to only trace those stages. Set to `0`,
+/// `false` or leave empty to disable tracing.
+pub const TRACE_ENV_VAR: &str = "MACRO_MAGIC_TRACE";
+
+/// The environment variable used to override the directory trace files are written to.
+///
+/// Defaults to `$CARGO_TARGET_DIR/macro_magic_trace` if `CARGO_TARGET_DIR` is set, otherwise
+/// `$CARGO_MANIFEST_DIR/target/macro_magic_trace`.
+pub const TRACE_DIR_ENV_VAR: &str = "MACRO_MAGIC_TRACE_DIR";
+
+/// The name of the sub-directory trace files are written to when [`TRACE_DIR_ENV_VAR`] is not
+/// specified.
+const DEFAULT_TRACE_DIR_NAME: &str = "macro_magic_trace";
+
+/// Lazily loaded trace configuration for the current (compiler) process.
+static TRACE_CONFIG: OnceLock<Option<TraceConfig>> = OnceLock::new();
+
+/// Serializes writes to trace files within the current process.
+static TRACE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Determines which stages are traced when tracing is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceFilter {
+    /// Every stage is traced
+    All,
+    /// Only the stages with the specified names are traced
+    Only(Vec<String>),
+}
+
+/// The parsed form of the [`TRACE_ENV_VAR`] and [`TRACE_DIR_ENV_VAR`] environment variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceConfig {
+    /// Determines which stages will be traced
+    pub filter: TraceFilter,
+    /// The directory trace files will be written to
+    pub dir: PathBuf,
+    /// The name of the trace file within [`TraceConfig::dir`], derived from the name of the
+    /// crate currently being compiled.
+    pub file_name: String,
+}
+
+impl TraceConfig {
+    /// Loads the trace configuration from the environment of the current process, returning
+    /// [`None`] if tracing is disabled.
+    pub fn from_env() -> Option<TraceConfig> {
+        let var = |name: &str| std::env::var(name).ok();
+        TraceConfig::from_vars(
+            var(TRACE_ENV_VAR).as_deref(),
+            var(TRACE_DIR_ENV_VAR).as_deref(),
+            var("CARGO_TARGET_DIR").as_deref(),
+            var("CARGO_MANIFEST_DIR").as_deref(),
+            var("CARGO_CRATE_NAME").as_deref(),
+        )
+    }
+
+    /// Builds a trace configuration from the raw values of the relevant environment
+    /// variables, returning [`None`] if tracing is disabled.
+    pub fn from_vars(
+        trace: Option<&str>,
+        trace_dir: Option<&str>,
+        target_dir: Option<&str>,
+        manifest_dir: Option<&str>,
+        crate_name: Option<&str>,
+    ) -> Option<TraceConfig> {
+        let trace = trace?.trim();
+        let filter = match trace.to_ascii_lowercase().as_str() {
+            "" | "0" | "false" | "off" => return None,
+            "1" | "true" | "all" | "on" => TraceFilter::All,
+            _ => TraceFilter::Only(
+                trace
+                    .split(',')
+                    .map(|stage| stage.trim().to_string())
+                    .filter(|stage| !stage.is_empty())
+                    .collect(),
+            ),
+        };
+        let dir = match (trace_dir, target_dir, manifest_dir) {
+            (Some(dir), _, _) => PathBuf::from(dir),
+            (None, Some(target), _) => PathBuf::from(target).join(DEFAULT_TRACE_DIR_NAME),
+            (None, None, Some(manifest)) => PathBuf::from(manifest)
+                .join("target")
+                .join(DEFAULT_TRACE_DIR_NAME),
+            (None, None, None) => PathBuf::from("target").join(DEFAULT_TRACE_DIR_NAME),
+        };
+        let file_name = format!("{}.log", crate_name.unwrap_or("unknown_crate"));
+        Some(TraceConfig {
+            filter,
+            dir,
+            file_name,
+        })
+    }
+
+    /// Returns whether the stage with the specified name should be traced.
+    pub fn enabled_for(&self, stage: &str) -> bool {
+        match &self.filter {
+            TraceFilter::All => true,
+            TraceFilter::Only(stages) => stages.iter().any(|s| s == stage),
+        }
+    }
+
+    /// The full path of the trace file records will be appended to.
+    pub fn file_path(&self) -> PathBuf {
+        self.dir.join(&self.file_name)
+    }
+}
+
+/// A single traced stage of a `macro_magic` expansion chain.
+pub struct TraceRecord<'a> {
+    /// The name of the macro (stage) that is being traced, i.e. `forward_tokens`
+    pub stage: &'a str,
+    /// The path of the item whose tokens are being exported/forwarded/imported, if known
+    pub source: Option<&'a Path>,
+    /// The path of the macro receiving the tokens, if known
+    pub target: Option<&'a Path>,
+    /// The tokens this stage received as input
+    pub input: &'a TokenStream2,
+    /// The tokens this stage produced as output
+    pub output: &'a TokenStream2,
+}
+
+impl TraceRecord<'_> {
+    /// Renders this record in the human-readable format used in trace files.
+    pub fn render(&self) -> String {
+        let path_str = |path: Option<&Path>| match path {
+            Some(path) => path.to_token_stream().to_string(),
+            None => String::from("-"),
+        };
+        format!(
+            "==== {} ====\nsource: {}\ntarget: {}\n---- input ----\n{}\n---- output ----\n{}\n\n",
+            self.stage,
+            path_str(self.source),
+            path_str(self.target),
+            pretty_print(self.input).trim_end(),
+            pretty_print(self.output).trim_end(),
+        )
+    }
+}
+
+/// Returns whether the specified stage is currently being traced.
+///
+/// Useful for avoiding expensive work that is only needed when tracing.
+pub fn trace_enabled(stage: &str) -> bool {
+    match TRACE_CONFIG.get_or_init(TraceConfig::from_env) {
+        Some(config) => config.enabled_for(stage),
+        None => false,
+    }
+}
+
+/// Appends a [`TraceRecord`] for the specified stage to the trace file, if tracing is enabled
+/// for that stage via [`TRACE_ENV_VAR`].
+///
+/// Failing to write the trace file never causes compilation to fail, since tracing is purely
+/// a debugging aid.
+pub fn trace_stage(
+    stage: &str,
+    source: Option<&Path>,
+    target: Option<&Path>,
+    input: &TokenStream2,
+    output: &TokenStream2,
+) {
+    let Some(config) = TRACE_CONFIG.get_or_init(TraceConfig::from_env) else {
+        return;
+    };
+    if !config.enabled_for(stage) {
+        return;
+    }
+    let record = TraceRecord {
+        stage,
+        source,
+        target,
+        input,
+        output,
+    }
+    .render();
+    let _guard = TRACE_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    if create_dir_all(&config.dir).is_err() {
+        return;
+    }
+    if let Ok(mut file) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(config.file_path())
+    {
+        let _ = file.write_all(record.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::parse_quote;
+
+    #[test]
+    fn trace_config_disabled() {
+        assert_eq!(TraceConfig::from_vars(None, None, None, None, None), None);
+        assert_eq!(
+            TraceConfig::from_vars(Some(""), None, None, None, None),
+            None
+        );
+        assert_eq!(
+            TraceConfig::from_vars(Some("0"), None, None, None, None),
+            None
+        );
+        assert_eq!(
+            TraceConfig::from_vars(Some("false"), None, None, None, None),
+            None
+        );
+    }
+
+    #[test]
+    fn trace_config_filters() {
+        let config = TraceConfig::from_vars(Some("1"), None, None, None, None).unwrap();
+        assert_eq!(config.filter, TraceFilter::All);
+        assert!(config.enabled_for("forward_tokens"));
+        let config = TraceConfig::from_vars(
+            Some("forward_tokens, forward_tokens_inner"),
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(config.enabled_for("forward_tokens"));
+        assert!(config.enabled_for("forward_tokens_inner"));
+        assert!(!config.enabled_for("export_tokens"));
+    }
+
+    #[test]
+    fn trace_config_dirs() {
+        let config = TraceConfig::from_vars(
+            Some("1"),
+            Some("/tmp/mm"),
+            Some("/t"),
+            Some("/m"),
+            Some("foo"),
+        )
+        .unwrap();
+        assert_eq!(config.file_path(), PathBuf::from("/tmp/mm/foo.log"));
+        let config =
+            TraceConfig::from_vars(Some("1"), None, Some("/t"), Some("/m"), Some("foo")).unwrap();
+        assert_eq!(
+            config.file_path(),
+            PathBuf::from("/t/macro_magic_trace/foo.log")
+        );
+        let config = TraceConfig::from_vars(Some("1"), None, None, Some("/m"), None).unwrap();
+        assert_eq!(
+            config.file_path(),
+            PathBuf::from("/m/target/macro_magic_trace/unknown_crate.log")
+        );
+    }
+
+    #[test]
+    fn trace_record_render() {
+        let source: Path = parse_quote!(my_crate::MyStruct);
+        let target: Path = parse_quote!(my_macro);
+        let input = quote!(my_crate::MyStruct, my_macro);
+        let output = quote!(
+            struct MyStruct {
+                field: u32,
+            }
+        );
+        let rendered = TraceRecord {
+            stage: "forward_tokens",
+            source: Some(&source),
+            target: Some(&target),
+            input: &input,
+            output: &output,
+        }
+        .render();
+        assert!(rendered.starts_with("==== forward_tokens ====\n"));
+        assert!(rendered.contains("source: my_crate :: MyStruct\n"));
+        assert!(rendered.contains("target: my_macro\n"));
+        assert!(rendered.contains(pretty_print(&output).trim_end()));
+    }
+}
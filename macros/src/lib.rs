@@ -18,6 +18,16 @@ use proc_macro::TokenStream;
 /// ident, and so for these items specifying an override name is required or you will get a
 /// compiler error. This also applies to `macro_rules!` definitions that do not specify a name.
 ///
+/// Dependencies on other `#[export_tokens]` items can be recorded by listing them via
+/// `with_deps`, i.e. `#[export_tokens(SomeName, with_deps(Timeout, crate::Retry))]`. A bare
+/// `with_deps` records every (non-`std`) type the item references instead, on a best-effort
+/// basis. These are used by [`forward_bundle!`](`macro@forward_bundle`) to import the full closure of
+/// the item.
+///
+/// Items directly inside a module marked with [`#[export_group]`](`macro@export_group`) can
+/// join one of its groups via `#[export_tokens(group = plugins)]`, which can be combined with
+/// the other arguments, i.e. `#[export_tokens(SomeName, group = plugins)]`.
+///
 /// Note also that while you can presently _attach_ `#[export_tokens]` to anything attributes
 /// can be attached to, some of these items do not exist at the module path level, and
 /// therefore cannot be accessed. You should only attach `#[export_tokens]` to items that are
@@ -84,6 +94,80 @@ pub fn export_tokens_no_emit(attr: TokenStream, tokens: TokenStream) -> TokenStr
     }
 }
 
+/// Declares one or more named groups of exported items, which can be forwarded to a target
+/// macro all at once using [`collect_group!`](`macro@collect_group`).
+///
+/// Can be attached to an inline module. Items directly inside the module join a group by
+/// specifying `group = name` in their [`#[export_tokens]`](`macro@export_tokens`) (or
+/// [`#[export_tokens_no_emit]`](`macro@export_tokens_no_emit`)) attribute, and are otherwise
+/// exported as usual. Without arguments a single group named after the module is declared,
+/// while `#[export_group(plugins, hooks)]` declares several.
+///
+/// A group is referred to by the path of its module with the last segment replaced by the
+/// name of the group, i.e. `my_crate::plugins` for a group named `plugins` declared by
+/// `my_crate::plugins` or by `my_crate::registry`.
+///
+/// The module attribute sees every member of the group at once and generates a single hidden
+/// macro providing all of them, so no global state or file I/O is involved. Because
+/// `macro_rules!` definitions generated by separate macro expansions cannot shadow one another
+/// (see `E0659`), a group cannot be extended one export site at a time, which is why its
+/// members must share a module.
+///
+/// ## Example
+///
+/// ```ignore
+/// #[export_group]
+/// pub mod plugins {
+///     #[export_tokens(group = plugins)]
+///     pub struct Logger;
+///
+///     #[export_tokens(group = plugins)]
+///     pub struct Metrics;
+///
+///     // not part of the group
+///     #[export_tokens]
+///     pub struct Helper;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn export_group(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match export_group_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Exports an item as a template whose placeholder idents are substituted when it is
+/// imported via [`instantiate!`](`macro@instantiate`).
+///
+/// The placeholders are passed as a comma-separated list of idents, i.e.
+/// `#[export_template(T, N)]`, and can stand in for types, expressions or names anywhere in the
+/// item. Items without an ident of their own (such as impl blocks) must be given a name to be
+/// exported under, followed by a `;`, i.e. `#[export_template(sized_buffer; T, N)]`.
+///
+/// Like [`#[export_tokens_no_emit]`](`macro@export_tokens_no_emit`), the item is not emitted
+/// locally, since its placeholders aren't expected to resolve at the export site.
+///
+/// ## Example
+///
+/// ```ignore
+/// #[export_template(sized_buffer; T, N)]
+/// impl T {
+///     pub const CAPACITY: usize = N;
+///
+///     pub fn buffer() -> [u8; N] {
+///         [0; N]
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn export_template(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match export_template_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// Creates an attribute proc macro that is an alias for
 /// [`#[export_tokens]`](`macro@export_tokens`).
 ///
@@ -120,23 +204,55 @@ pub fn export_tokens_alias_no_emit(tokens: TokenStream) -> TokenStream {
 /// Note that the referenced item _must_ have the [`#[export_tokens]`][`macro@export_tokens`]
 /// attribute attached to it, or this will not work.
 ///
-/// There is also an optional third argument called "extra" which allows you to forward
-/// arbitrary data to the target macro. This is used by
-/// [`#[import_tokens_attr]`](`macro@import_tokens_proc`) to pass the tokens for the attached
-/// item in addition to the tokens for the external item.
+/// An optional third argument can be used to override the path to `macro_magic`, and an
+/// optional (braced) "extra" argument after that allows you to forward arbitrary data to the
+/// target macro. This is used by [`#[import_tokens_attr]`](`macro@import_tokens_attr`) to pass
+/// the tokens for the attached item in addition to the tokens for the external item, and
+/// causes the target to be invoked as an attribute macro.
+///
+/// By default the target is invoked as `target! { __private_macro_magic_tokens_forwarded
+/// <item> }`, which is what [`#[import_tokens_proc]`](`macro@import_tokens_proc`) macros
+/// expect.
+///
+/// ## User-Supplied Args
+///
+/// Arbitrary data can be passed along to a function-like target using the `args = { .. }`
+/// form, i.e. `forward_tokens!(path, target, args = { .. })`. The target is then invoked as
+/// `target! { __private_macro_magic_tokens_forwarded <item>, { <args> } }`. Macros defined
+/// with [`#[import_tokens_proc]`](`macro@import_tokens_proc`) receive the item as usual and
+/// can access the args via the magic `__forwarded_args: TokenStream` variable. Hand-written
+/// proc macros can parse this input using `macro_magic::mm_core::ForwardedItemWithArgs`.
+///
+/// Unlike the braced "extra" argument, `args = { .. }` never switches the target into
+/// attribute mode.
+///
+/// ## `macro_rules!` Targets
+///
+/// To forward tokens to a declarative macro, use the `extra = { .. }` form. The target will
+/// then receive the following stable, documented input shape (see
+/// `macro_magic::mm_core::DeclForwardedTokens`):
+///
+/// ```ignore
+/// target! {
+///     item = { /* the tokens of the exported item */ },
+///     source = { /* the path of the exported item, as passed to forward_tokens! */ },
+///     extra = { /* the tokens passed via `extra = { .. }` */ }
+/// }
+/// ```
 ///
 /// ## Example
 ///
 /// ```ignore
-/// #[macro_export]
 /// macro_rules! receiver {
-///     ($tokens:item) => {
-///         stringify!($tokens)
+///     (item = { $item:item }, source = { $source:path }, extra = { $($extra:tt)* }) => {
+///         (stringify!($item), stringify!($source), stringify!($($extra)*))
 ///     };
 /// }
 ///
-/// let result = forward_tokens!(LionStruct, receiver);
-/// assert_eq!(result, "struct LionStruct {}");
+/// let (item, source, extra) = forward_tokens!(LionStruct, receiver, extra = { roar });
+/// assert_eq!(item, "struct LionStruct {}");
+/// assert_eq!(source, "LionStruct");
+/// assert_eq!(extra, "roar");
 /// ```
 #[proc_macro]
 pub fn forward_tokens(tokens: TokenStream) -> TokenStream {
@@ -154,6 +270,200 @@ pub fn forward_tokens_verbatim(tokens: TokenStream) -> TokenStream {
     }
 }
 
+/// Chains several [`forward_tokens!`](`macro@forward_tokens`) hops together declaratively,
+/// accumulating the tokens of multiple [`#[export_tokens]`][`macro@export_tokens`] items and
+/// passing them through one or more stage macros before they reach a final target macro.
+///
+/// The pipeline is specified as a comma-separated list of exported item paths, where each
+/// path can be followed by one or more `=> stage_macro` arrows. Each stage is invoked with
+/// _all_ of the items accumulated so far (including any changes made by earlier stages), and
+/// the pipeline must end with `=> target_macro`. Options can be specified after a `;`:
+///
+/// - `args = { .. }`: arbitrary tokens passed along to every stage
+/// - `max_depth = N`: the maximum number of hops (imports plus stage invocations) the
+///   pipeline may perform before it is aborted, defaulting to `32`
+/// - `mm_path = ::path::to::macro_magic`: the path of the `macro_magic` crate used for each
+///   hop, like the optional `mm_path` of [`forward_tokens!`](`macro@forward_tokens`)
+///
+/// Importing the same item twice in one pipeline is reported as a duplicate import, and exceeding
+/// `max_depth` is reported as a compiler error rather than overflowing the recursion limit.
+///
+/// Stages (and the final target) are plain `#[proc_macro]`s that parse their input as
+/// `macro_magic::mm_core::ForwardedPipeline`. Intermediate stages must emit the tokens
+/// returned by `ForwardedPipeline::resume` for the pipeline to continue.
+///
+/// ## Example
+///
+/// ```ignore
+/// // `LionStruct` is passed through `my_transform`, then `TigerStruct` is imported, and
+/// // finally `my_target` receives both items along with the args
+/// forward_pipeline! {
+///     LionStruct => my_transform,
+///     external_crate::TigerStruct => my_target;
+///     args = { roar }
+/// }
+///
+/// #[proc_macro]
+/// pub fn my_target(tokens: TokenStream) -> TokenStream {
+///     let pipeline = parse_macro_input!(tokens as ForwardedPipeline);
+///     for (path, item) in pipeline.items {
+///         // ...
+///     }
+///     // ...
+/// }
+/// ```
+#[proc_macro]
+pub fn forward_pipeline(tokens: TokenStream) -> TokenStream {
+    match forward_pipeline_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Forwards the tokens of the specified exported item _along with_ the tokens of every
+/// `#[export_tokens]` item it (transitively) depends on to the specified proc macro.
+///
+/// Dependencies are recorded at the export site using `#[export_tokens(with_deps(A, b::B))]`,
+/// and every dependency must itself be marked with `#[export_tokens]`. A bare
+/// `#[export_tokens(with_deps)]` records every type the item references, skipping primitives,
+/// common `std` types, generic params and `Self`, so it only works if all the remaining types
+/// are exported. Otherwise the dependencies must be listed explicitly. Relative dependency paths
+/// are resolved relative to the module of the item that recorded them, so the source should be
+/// specified using its full path, while `crate::` paths work from anywhere.
+///
+/// The bundle is resolved through a chain of [`forward_tokens!`](`macro@forward_tokens`)-style
+/// callbacks, each dependency is only imported once, and optional `args = { .. }` and
+/// `max_depth = N` arguments work just like they do in
+/// [`forward_pipeline!`](`macro@forward_pipeline`).
+///
+/// The target is a plain `#[proc_macro]` that parses its input as
+/// `macro_magic::mm_core::ImportedBundle`.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `external_crate`
+/// #[export_tokens]
+/// pub struct Timeout { secs: u64 }
+///
+/// #[export_tokens]
+/// pub struct Retry { attempts: u8 }
+///
+/// #[export_tokens(with_deps(Timeout, Retry))]
+/// pub struct Config { timeout: Timeout, retry: Retry }
+///
+/// // elsewhere
+/// forward_bundle!(external_crate::Config, my_macro);
+///
+/// #[proc_macro]
+/// pub fn my_macro(tokens: TokenStream) -> TokenStream {
+///     let bundle = parse_macro_input!(tokens as ImportedBundle);
+///     // `bundle.root` is `Config` and `bundle.deps` contains `Timeout` and `Retry`
+///     // ...
+/// }
+/// ```
+#[proc_macro]
+pub fn forward_bundle(tokens: TokenStream) -> TokenStream {
+    match forward_bundle_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Imports an item exported via [`#[export_template]`](`macro@export_template`) and emits it
+/// with its placeholders substituted.
+///
+/// The first argument is the path of the template, followed by a `placeholder = value`
+/// binding for each of its placeholders. Values are parsed as a type where possible (so
+/// `T = Result<u8, u16>` works) and as an expression otherwise, and non-ident values are
+/// substituted as a unit, so `N = 4 + 4` behaves like a `$n:expr` fragment would. Idents
+/// following a `.` or `::` are members rather than placeholders and are never substituted.
+///
+/// Missing, duplicate or unknown bindings result in a compiler error.
+///
+/// ## Example
+///
+/// ```ignore
+/// pub struct Packet;
+///
+/// instantiate!(my_crate::sized_buffer, T = Packet, N = 8);
+///
+/// assert_eq!(Packet::CAPACITY, 8);
+/// ```
+#[proc_macro]
+pub fn instantiate(tokens: TokenStream) -> TokenStream {
+    match instantiate_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Forwards every item of an export group declared via
+/// [`#[export_group]`](`macro@export_group`) to a target macro at once.
+///
+/// The first argument is the path of the group and the second is the target, optionally
+/// followed by `args = { .. }`, which are passed along to the target. The target is a plain
+/// `#[proc_macro]` that parses its input as `macro_magic::mm_core::ForwardedPipeline`, whose
+/// `items` contain the full path and tokens of each member of the group in source order.
+///
+/// ## Example
+///
+/// ```ignore
+/// collect_group!(my_crate::plugins, register_plugins, args = { registry });
+///
+/// #[proc_macro]
+/// pub fn register_plugins(tokens: TokenStream) -> TokenStream {
+///     let group = parse_macro_input!(tokens as ForwardedPipeline);
+///     for (path, item) in group.items {
+///         // `path` is i.e. `my_crate::plugins::Logger`
+///         // ...
+///     }
+///     // ...
+/// }
+/// ```
+#[proc_macro]
+pub fn collect_group(tokens: TokenStream) -> TokenStream {
+    match collect_group_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Expands to a `&'static str` literal containing the source of an
+/// [`#[export_tokens]`](`macro@export_tokens`) item.
+///
+/// Unlike [`import_tokens!`](`macro@import_tokens`), this works in ordinary (including
+/// `no_std`) crates and doesn't require the `proc_support` feature, which makes it useful for
+/// golden tests, help output or embedding schema definitions.
+///
+/// By default the string is the same as what `stringify!` would produce for the item.
+/// Specifying `pretty` after the path formats it using `prettyplease` instead, which requires
+/// the `pretty` feature.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub struct Config {
+///     pub port: u16,
+/// }
+///
+/// // in your crate
+/// const RAW: &str = stringify_item!(upstream::Config);
+/// const PRETTY: &str = stringify_item!(upstream::Config, pretty);
+///
+/// assert_eq!(RAW, "pub struct Config { pub port : u16, }");
+/// assert_eq!(PRETTY, "pub struct Config {\n    pub port: u16,\n}");
+/// ```
+#[proc_macro]
+pub fn stringify_item(tokens: TokenStream) -> TokenStream {
+    match stringify_item_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// Allows you to import the tokens of an external item marked with
 /// [`#[export_tokens]`][`macro@export_tokens`] whose path is already known at compile-time
 /// without having to do any additional parsing.
@@ -192,6 +502,52 @@ pub fn import_tokens(tokens: TokenStream) -> TokenStream {
     }
 }
 
+/// Like [`import_tokens!`](`macro@import_tokens`), but reads the item directly from a source
+/// file rather than requiring it to be marked with [`#[export_tokens]`][`macro@export_tokens`],
+/// which is useful for crates that will never export their items.
+///
+/// The file is specified as a string literal relative to `CARGO_MANIFEST_DIR`, followed by `::`
+/// and the path of the item within the file, whose leading segments can name inline modules:
+///
+/// ```ignore
+/// import_tokens_from_file!(let tokens = "vendor/client/src/lib.rs" :: ClientSettings);
+/// import_tokens_from_file!(let encode = "vendor/client/src/lib.rs" :: codec::encode);
+/// ```
+///
+/// Items are selected by the same ident [`#[export_tokens]`][`macro@export_tokens`] would
+/// export them under, so items without an ident (such as impl blocks) can't be imported this
+/// way. The file is registered with the compiler via `include_bytes!`, so editing it triggers
+/// a rebuild.
+#[proc_macro]
+pub fn import_tokens_from_file(tokens: TokenStream) -> TokenStream {
+    match import_tokens_from_file_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// The attribute form of [`import_tokens_from_file!`](`macro@import_tokens_from_file`), which
+/// can be attached to a function definition (typically a proc macro) to declare the tokens
+/// variable at the start of its body.
+///
+/// ## Example
+///
+/// ```ignore
+/// #[import_tokens_from_file_attr(let settings = "vendor/client/src/lib.rs" :: ClientSettings)]
+/// #[proc_macro]
+/// pub fn settings_fields(_tokens: TokenStream) -> TokenStream {
+///     let settings = parse_macro_input!(settings as ItemStruct);
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn import_tokens_from_file_attr(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match import_tokens_from_file_attr_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// An attribute macro that can be attached to a proc macro function definition that will cause
 /// it to receive the tokens of the external item referred to by its argument as input to your
 /// proc macro.
@@ -220,6 +576,10 @@ pub fn import_tokens(tokens: TokenStream) -> TokenStream {
 ///
 /// Note that this attribute can only be used within a proc macro crate.
 ///
+/// If the macro is invoked via `forward_tokens!(path, my_macro, args = { .. })`, the tokens
+/// passed via `args` will be available within the proc macro via the magic
+/// `__forwarded_args: TokenStream` variable, which is otherwise empty.
+///
 /// ## Overriding [`MACRO_MAGIC_ROOT`]:
 ///
 /// You can also provide a module path as an optional argument to this attribute macro and that
@@ -382,6 +742,19 @@ pub fn import_tokens_proc(attr: TokenStream, tokens: TokenStream) -> TokenStream
 /// something that isn't initially a `syn::Path` but is transformed into one. The possibilities
 /// are endless.
 ///
+/// ## Optional Feature: `#[with_parse_cache]`
+///
+/// Before running your code, every use of an
+/// [`#[import_tokens_attr]`](`macro@import_tokens_attr`)-based macro validates that the
+/// imported tokens parse as a `syn::Item`, on top of whatever parsing your macro does itself.
+/// Attaching [`#[with_parse_cache]`](`macro@with_parse_cache`) skips this validation parse
+/// for items that were already validated from the same path in the same compilation, by
+/// recognizing them via a fingerprint of their tokens. This saves one parse of the imported
+/// item per invocation, which can add up when a large item is imported many times.
+///
+/// The imported tokens are passed to `attr` as they were received instead of being
+/// normalized through `to_token_stream()`, so the cache is opt-in.
+///
 /// ## Notes
 ///
 /// * See `tests.rs` for more examples.
@@ -431,6 +804,33 @@ pub fn with_custom_parsing(attr: TokenStream, tokens: TokenStream) -> TokenStrea
     }
 }
 
+/// To be used in tandem with [`#[import_tokens_attr]`](`macro@import_tokens_attr`)
+///
+/// Makes the resulting attribute macro skip validating that the imported tokens parse as a
+/// `syn::Item` when the same item was already validated from the same path in this
+/// compilation, which saves one parse per invocation. Your macro still parses `attr` itself.
+/// The tokens are passed to `attr` as they were received rather than being normalized through
+/// `to_token_stream()`.
+///
+/// Example:
+/// ```ignore
+/// #[import_tokens_attr]
+/// #[with_parse_cache]
+/// #[proc_macro_attribute]
+/// pub fn my_attribute(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+///     let external_item = parse_macro_input!(attr as Item);
+///     let attached_item = parse_macro_input!(tokens as Item);
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn with_parse_cache(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match with_parse_cache_internal(attr, tokens, "import_tokens_attr") {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// Deprecated: No-op
 #[deprecated(
     note = "`use_attr` is no longer needed for importing or re-exporting, implementation is no-op, it can be removed safely"
@@ -449,6 +849,481 @@ pub fn use_proc(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
     tokens
 }
 
+/// Merges the fields of one or more [`#[export_tokens]`](`macro@export_tokens`) structs into
+/// the struct this attribute is attached to. Requires the `mixin` feature.
+///
+/// The fields of each specified struct are appended (in order) after the local fields, and the
+/// generic params and where-clause predicates of each specified struct are merged into those of
+/// the local struct. Fields whose names conflict with a field of a mixed-in struct result in a
+/// compiler error pointing at the conflicting local field.
+///
+/// Tuple structs can only receive fields from other tuple structs, and structs with named
+/// fields can only receive fields from other structs with named fields, however unit structs
+/// can receive either kind of field. Mixing in a unit struct only merges its generics.
+///
+/// Note that the types of the imported fields are resolved at the location of the local
+/// struct, so any types they reference must be in scope there.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `other_crate`
+/// #[export_tokens]
+/// pub struct Timestamps<T: Clock> {
+///     pub created: T::Instant,
+///     pub updated: T::Instant,
+/// }
+///
+/// // in your crate
+/// #[mixin(other_crate::Timestamps, other_crate::SoftDelete)]
+/// pub struct User<T: Clock> {
+///     pub name: String,
+/// }
+/// ```
+#[cfg(feature = "mixin")]
+#[proc_macro_attribute]
+pub fn mixin(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match mixin_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Copies every method, associated const and associated type of an
+/// [`#[export_tokens]`](`macro@export_tokens`) impl block into the impl block this attribute
+/// is attached to, unless the local impl already defines an item with the same name. Requires
+/// the `inherit_impl` feature.
+///
+/// This allows inheritance-like reuse of trait (or inherent) impls across module, file and
+/// crate boundaries: the local impl only needs to define the items it wants to override.
+/// Occurrences of the self type of the exported impl within the copied items are replaced with
+/// the self type of the local impl (or `Self`, in expression position).
+///
+/// Both impls must implement the same trait (compared by name), or both must be inherent
+/// impls. Note that the copied items are resolved at the location of the local impl, so any
+/// items they reference must be in scope there.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `other_crate`
+/// #[export_tokens(square_shape)]
+/// impl Shape for Square {
+///     const SIDES: u32 = 4;
+///     fn sides(&self) -> u32 {
+///         Self::SIDES
+///     }
+///     fn double(&self) -> Square {
+///         Square(self.0 * 2)
+///     }
+/// }
+///
+/// // in your crate, `Triangle` gets `sides` and `double` (returning `Triangle`)
+/// #[inherit_impl(other_crate::square_shape)]
+/// impl Shape for Triangle {
+///     const SIDES: u32 = 3;
+/// }
+/// ```
+#[cfg(feature = "inherit_impl")]
+#[proc_macro_attribute]
+pub fn inherit_impl(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match inherit_impl_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Extends the enum this attribute is attached to with the variants of an
+/// [`#[export_tokens]`](`macro@export_tokens`) enum. Requires the `extend_enum` feature.
+///
+/// The variants of the exported enum are prepended to the local variants, and generic params
+/// and where-clause predicates are merged. A `From<upstream::Enum>` impl is generated for the
+/// local enum, along with a fallible `TryFrom<LocalEnum>` impl for the upstream enum whose
+/// error type is the local enum (so values of local-only variants are handed back unchanged).
+///
+/// Local variants whose names conflict with an upstream variant result in a compiler error
+/// pointing at both definitions. The exported enum must be specified by its full path, since
+/// this path is also used as the upstream type in the generated conversions.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub enum Event {
+///     Started,
+///     Finished { code: u32 },
+/// }
+///
+/// // in your crate
+/// #[extend_enum(upstream::Event)]
+/// pub enum AppEvent {
+///     Custom(String),
+/// }
+///
+/// let event: AppEvent = upstream::Event::Started.into();
+/// assert!(upstream::Event::try_from(AppEvent::Custom("hey".into())).is_err());
+/// ```
+#[cfg(feature = "extend_enum")]
+#[proc_macro_attribute]
+pub fn extend_enum(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match extend_enum_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Turns the empty struct or enum this attribute is attached to into a serde
+/// [remote definition](https://serde.rs/remote-derive.html) of an
+/// [`#[export_tokens]`](`macro@export_tokens`) struct or enum. Requires the `remote_mirror`
+/// feature.
+///
+/// The fields (or variants) of the exported type are copied into the attached item along with
+/// its generics, and `#[serde(remote = "...")]` is added pointing at the exported type. Only the
+/// `#[serde(..)]` and doc attributes of the upstream fields and variants are kept. `From` impls
+/// converting field-by-field between the mirror and the upstream type are generated in both
+/// directions.
+///
+/// The attribute must be placed above the serde derives so that they see the mirrored fields,
+/// the exported type must be specified by its full path (this path is used as the remote
+/// type), and its fields must be visible from the crate using the attribute, since serde
+/// accesses them directly.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub struct Duration {
+///     pub secs: u64,
+///     pub nanos: u32,
+/// }
+///
+/// // in your crate
+/// #[remote_mirror(upstream::Duration)]
+/// #[derive(Serialize, Deserialize)]
+/// pub struct DurationDef;
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Timeout {
+///     #[serde(with = "DurationDef")]
+///     duration: upstream::Duration,
+/// }
+/// ```
+#[cfg(feature = "remote_mirror")]
+#[proc_macro_attribute]
+pub fn remote_mirror(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match remote_mirror_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Generates a local copy of an [`#[export_tokens]`](`macro@export_tokens`) struct, so that
+/// foreign traits can be implemented for it without running into the orphan rule. Requires the
+/// `mirror_type` feature.
+///
+/// The copy preserves the generics, where-clause, visibility, field visibility, `#[repr]` and
+/// doc attributes of the exported struct, while its other attributes (such as derives) are
+/// dropped. `From` impls converting field-by-field between the copy and the exported struct
+/// are generated in both directions, which requires the fields of the exported struct to be
+/// visible from the calling crate.
+///
+/// Attributes placed in front of the path are applied to the copy, and a trailing `deref`
+/// generates a `Deref` impl targeting the only field of the struct (structs with more or fewer
+/// fields result in a compiler error).
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub struct Meters(pub f64);
+///
+/// // in your crate
+/// mirror_type!(#[derive(Debug)] upstream::Meters as Meters, deref);
+///
+/// impl core::fmt::Display for Meters {
+///     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+///         write!(f, "{}m", **self)
+///     }
+/// }
+///
+/// let meters: Meters = upstream::Meters(3.0).into();
+/// ```
+#[cfg(feature = "mirror_type")]
+#[proc_macro]
+pub fn mirror_type(tokens: TokenStream) -> TokenStream {
+    match mirror_type_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Implements the methods of an [`#[export_tokens]`](`macro@export_tokens`) trait that the
+/// impl block this attribute is attached to doesn't already define, by forwarding them to a
+/// field of `Self`. Requires the `delegate` feature.
+///
+/// The attribute takes the field (a name, or an index for tuple structs) via `field = ..` and
+/// the path of the exported trait via `to = ..`. Generic and async methods are forwarded as-is,
+/// however methods without a `self`, `&self` or `&mut self` receiver and methods whose
+/// signature refers to `Self` (other than through associated types such as `Self::Value`)
+/// can't be forwarded and must be written by hand.
+///
+/// Associated types and consts can only be delegated if the type of the field is specified
+/// via `ty = ..`, in which case methods are also called via `<Type as Trait>::method`.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub trait Storage {
+///     type Value;
+///     fn get(&self, key: u32) -> Option<&Self::Value>;
+///     fn put(&mut self, key: u32, value: Self::Value);
+/// }
+///
+/// // in your crate
+/// pub struct Wrapper {
+///     inner: MemStore,
+///     writes: usize,
+/// }
+///
+/// #[delegate(field = inner, ty = MemStore, to = upstream::Storage)]
+/// impl Storage for Wrapper {
+///     fn put(&mut self, key: u32, value: u8) {
+///         self.writes += 1;
+///         self.inner.put(key, value)
+///     }
+/// }
+/// ```
+#[cfg(feature = "delegate")]
+#[proc_macro_attribute]
+pub fn delegate(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match delegate_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Generates a mock of an [`#[export_tokens]`](`macro@export_tokens`) trait for use in tests.
+/// Requires the `mock_trait` feature.
+///
+/// `mock_trait!(upstream::Repo)` generates a `MockRepo` struct (a different name can be given
+/// via `mock_trait!(upstream::Repo as FakeRepo)`) that implements `Default` and the trait,
+/// with one public field per trait method. Each field records the arguments of every call,
+/// available via `calls()` and `call_count()`, and hands out the values queued via
+/// `returns(value)`, falling back to the closure set via `returns_with(|args| ..)`. Calling a
+/// method without a return value panics.
+///
+/// Arguments are recorded as tuples, where references are recorded as their owned form (i.e.
+/// `&str` as `String`) and arguments whose type is a generic param of the method or an
+/// `impl Trait` are recorded as their type name. Lifetimes in return types become `'static`.
+/// The mock is generic over the associated types of the trait, and methods can take `self`,
+/// `&self` or `&mut self`.
+///
+/// Methods without a receiver, methods whose return type depends on their generic params,
+/// associated consts without a default value and generic traits are not supported. The
+/// generated code uses `std` but doesn't require any other crate.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub trait Repo {
+///     type Item;
+///     fn find(&self, name: &str) -> Option<u32>;
+///     fn put<T: Into<u32>>(&mut self, id: T, item: Self::Item);
+/// }
+///
+/// // in your tests
+/// mock_trait!(upstream::Repo);
+///
+/// let mut repo = MockRepo::<String>::default();
+/// repo.find.returns(Some(7));
+/// repo.put.returns(());
+/// assert_eq!(repo.find("bob"), Some(7));
+/// repo.put(3u8, "bob".to_string());
+/// assert_eq!(repo.put.calls()[0], ("u8", "bob".to_string()));
+/// ```
+#[cfg(feature = "mock_trait")]
+#[proc_macro]
+pub fn mock_trait(tokens: TokenStream) -> TokenStream {
+    match mock_trait_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Generates a builder for an [`#[export_tokens]`](`macro@export_tokens`) struct with named
+/// fields. Requires the `builder_for` feature.
+///
+/// `builder_for!(upstream::Config)` generates a `ConfigBuilder` (a different name can be given
+/// via `builder_for!(upstream::Config as MyBuilder)`) with a setter per field and a `build()`
+/// method. Fields are required unless a default is specified in a braced list following the
+/// path, via `#[default]` (which uses `Default::default()`) or `#[default(value)]`. `build()`
+/// returns a `ConfigBuilderError` listing every required field that wasn't set.
+///
+/// All fields of the exported struct must be visible from the calling crate, and private
+/// fields result in a compiler error. The generated error type uses `std`.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub struct Config {
+///     pub host: String,
+///     pub port: u16,
+///     pub workers: u8,
+/// }
+///
+/// // in your crate
+/// builder_for!(upstream::Config {
+///     #[default(8080)] port,
+///     #[default] workers,
+/// });
+///
+/// let config = ConfigBuilder::new().host("localhost".into()).build().unwrap();
+/// assert_eq!(config.port, 8080);
+/// assert_eq!(ConfigBuilder::new().build().unwrap_err().missing, ["host"]);
+/// ```
+#[cfg(feature = "builder_for")]
+#[proc_macro]
+pub fn builder_for(tokens: TokenStream) -> TokenStream {
+    match builder_for_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Expands to a `const`-compatible `macro_magic::reflect::ItemInfo` describing an
+/// [`#[export_tokens]`](`macro@export_tokens`) item. Requires the `reflect` feature.
+///
+/// The descriptor contains the name, kind, generics and doc comments of the item, along with
+/// the fields of structs and unions (name, type, visibility and docs), the variants of enums
+/// and the methods (name, full signature and docs) of traits, impl blocks and free-standing
+/// fns. Types and signatures are pretty-printed exactly as written in the exported item.
+///
+/// Everything in the descriptor is `&'static`, so it works in `no_std` crates.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// /// A server config.
+/// #[export_tokens]
+/// pub struct Config {
+///     pub host: &'static str,
+///     pub port: u16,
+/// }
+///
+/// // in your crate
+/// use macro_magic::reflect::{ItemInfo, ItemKind};
+///
+/// const CONFIG: ItemInfo = reflect!(upstream::Config);
+///
+/// assert_eq!(CONFIG.kind, ItemKind::Struct);
+/// assert_eq!(CONFIG.docs, "A server config.");
+/// assert_eq!(CONFIG.field("port").unwrap().ty, "u16");
+/// ```
+#[cfg(feature = "reflect")]
+#[proc_macro]
+pub fn reflect(tokens: TokenStream) -> TokenStream {
+    match reflect_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Appends the pretty-printed source of an [`#[export_tokens]`](`macro@export_tokens`) item to
+/// the doc comments of the item this attribute is attached to, as a fenced code block.
+/// Requires the `doc_item` feature.
+///
+/// By default the whole exported item is rendered, including its doc comments. An optional
+/// selector can be specified after the path:
+/// - `signature` renders only the signature of a fn, the signatures of the items of a trait or
+///   impl block, or the definition of any other item, without attributes or doc comments.
+/// - `members(a, b)` renders only the specified fields, variants or associated items. Naming a
+///   member that doesn't exist is a compile error.
+///
+/// The code block is marked `rust,ignore`, since the rendered source is not expected to compile
+/// as a doctest of the crate using the attribute. If the attached item already has doc
+/// comments, an empty line separates them from the code block.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `upstream`
+/// #[export_tokens]
+/// pub trait Repo {
+///     fn get(&self, id: u32) -> Option<String> {
+///         None
+///     }
+///     fn count(&self) -> usize;
+/// }
+///
+/// // in your crate
+/// /// A wrapper around an `upstream::Repo`, which provides the following methods:
+/// #[doc_item(upstream::Repo, signature)]
+/// pub struct RepoWrapper<R: upstream::Repo>(R);
+/// ```
+#[cfg(feature = "doc_item")]
+#[proc_macro_attribute]
+pub fn doc_item(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    match doc_item_internal(attr, tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Fails compilation unless two [`#[export_tokens]`](`macro@export_tokens`) structs, enums or
+/// unions have the same shape. Requires the `assert_same_shape` feature.
+///
+/// The items are imported via [`forward_pipeline!`](`macro@forward_pipeline`) and compared at
+/// the syntax level: field names, types (as written), field order, variants, discriminants,
+/// generics, visibility, attributes and doc comments must all match, while the names of the
+/// items themselves may differ. Every difference is listed in the resulting compiler error.
+///
+/// Attributes (other than doc comments), doc comments and visibility can be excluded from the
+/// comparison by specifying `ignore(attrs, docs, visibility)` (or any subset of these) after
+/// the paths. The macro expands to nothing, so it can be used anywhere items or statements
+/// can.
+///
+/// ## Example
+///
+/// ```ignore
+/// // in `wire`
+/// #[export_tokens]
+/// #[repr(C)]
+/// pub struct Header {
+///     pub id: u32,
+///     pub len: u16,
+/// }
+///
+/// // in `ffi`
+/// #[export_tokens]
+/// #[repr(C)]
+/// pub struct Header {
+///     pub id: u32,
+///     pub len: u32,
+/// }
+///
+/// // error: `wire::Header` and `ffi::Header` have different shapes:
+/// //        - field `len`: type `u16` != `u32`
+/// assert_same_shape!(wire::Header, ffi::Header);
+/// ```
+#[cfg(feature = "assert_same_shape")]
+#[proc_macro]
+pub fn assert_same_shape(tokens: TokenStream) -> TokenStream {
+    match assert_same_shape_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// A helper macro used by [`macro@import_tokens`]. Hidden from docs.
 #[doc(hidden)]
 #[proc_macro]
@@ -468,3 +1343,23 @@ pub fn forward_tokens_inner(tokens: TokenStream) -> TokenStream {
         Err(err) => err.to_compile_error().into(),
     }
 }
+
+/// A helper macro used by [`macro@collect_group`]. Hidden from docs.
+#[doc(hidden)]
+#[proc_macro]
+pub fn collect_group_inner(tokens: TokenStream) -> TokenStream {
+    match collect_group_inner_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// A helper macro used by [`macro@forward_pipeline`]. Hidden from docs.
+#[doc(hidden)]
+#[proc_macro]
+pub fn forward_pipeline_inner(tokens: TokenStream) -> TokenStream {
+    match forward_pipeline_inner_internal(tokens) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
@@ -11,5 +11,8 @@ cd ..
 cd macros
 cargo publish
 cd ..
+cd build
+cargo publish
+cd ..
 cargo publish
 echo "published successfully."
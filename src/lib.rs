@@ -24,6 +24,10 @@
 //! `macro_magic` is designed to work with stable Rust, and is fully `no_std` compatible (in
 //! fact, there is a unit test to ensure everything is `no_std` safe).
 //!
+//! This also applies to [`stringify_item!`], which expands to a `&'static str` containing the
+//! (optionally pretty-printed) source of an [`#[export_tokens]`](`export_tokens`) item without
+//! requiring `proc_support`.
+//!
 //! ## Features
 //!
 //! ### proc_support
@@ -39,13 +43,100 @@
 //! The reason for this feature gating is that things like `syn`, `quote`, `proc_macro2`, etc.,
 //! are not 100% `no_std` compatible and should only be enabled in proc macro crates.
 //!
+//! ### mixin
+//!
+//! Enables the `#[mixin]` attribute macro, which merges the fields of one or more
+//! [`#[export_tokens]`](`export_tokens`) structs into the struct it is attached to. Unlike
+//! `proc_support`, this can be enabled in regular (non-proc-macro) crates.
+//!
+//! ### inherit_impl
+//!
+//! Enables the `#[inherit_impl]` attribute macro, which copies the items of an
+//! [`#[export_tokens]`](`export_tokens`) impl block that the attached impl block doesn't
+//! already define into it.
+//!
+//! ### extend_enum
+//!
+//! Enables the `#[extend_enum]` attribute macro, which prepends the variants of an
+//! [`#[export_tokens]`](`export_tokens`) enum to the attached enum and generates conversions
+//! between the two.
+//!
+//! ### remote_mirror
+//!
+//! Enables the `#[remote_mirror]` attribute macro, which generates a serde
+//! [remote definition](https://serde.rs/remote-derive.html) of an
+//! [`#[export_tokens]`](`export_tokens`) struct or enum, along with conversions between the
+//! mirror and the upstream type. `macro_magic` does not depend on serde itself, so the crate
+//! using the attribute must.
+//!
+//! ### mirror_type
+//!
+//! Enables the `mirror_type!` macro, which generates a local copy of an
+//! [`#[export_tokens]`](`export_tokens`) struct along with conversions between the two, so
+//! that foreign traits can be implemented for it without running into the orphan rule.
+//!
+//! ### delegate
+//!
+//! Enables the `#[delegate]` attribute macro, which implements the methods of an
+//! [`#[export_tokens]`](`export_tokens`) trait that the attached impl block doesn't already
+//! define by forwarding them to a field.
+//!
+//! ### mock_trait
+//!
+//! Enables the `mock_trait!` macro, which generates a mock of an
+//! [`#[export_tokens]`](`export_tokens`) trait that records calls and returns values set by
+//! the test. The generated code uses `std`, so it is meant for test code.
+//!
+//! ### builder_for
+//!
+//! Enables the `builder_for!` macro, which generates a builder for an
+//! [`#[export_tokens]`](`export_tokens`) struct whose `build()` method reports missing required
+//! fields. The generated error type uses `std`.
+//!
+//! ### reflect
+//!
+//! Enables the `reflect!` macro and the [`reflect`](`mod@reflect`) module, which describe the
+//! fields, variants, methods and doc comments of an [`#[export_tokens]`](`export_tokens`) item
+//! using `const`, `no_std` data.
+//!
+//! ### doc_item
+//!
+//! Enables the `#[doc_item]` attribute macro, which appends the pretty-printed source of an
+//! [`#[export_tokens]`](`export_tokens`) item (or just its signature, or selected members) to
+//! the doc comments of the item it is attached to.
+//!
+//! ### assert_same_shape
+//!
+//! Enables the `assert_same_shape!` macro, which fails compilation with a list of differences
+//! unless two [`#[export_tokens]`](`export_tokens`) structs, enums or unions have the same
+//! fields, types, field order and variants.
+//!
+//! ### pretty
+//!
+//! Formats the tokens `macro_magic` renders for humans (traces, the `pretty` form of
+//! [`stringify_item!`] and the output of `reflect!` and `#[doc_item]`) using `prettyplease`.
+//! Without it they are printed on a single line. This feature is enabled automatically by the
+//! `reflect`, `doc_item` and `assert_same_shape` features.
+//!
+//! ## Tracing
+//!
+//! Setting the `MACRO_MAGIC_TRACE` environment variable to `1` causes each stage of an
+//! [`#[export_tokens]`](`export_tokens`) → [`forward_tokens!`] → user macro chain to append
+//! its input and output tokens to `target/macro_magic_trace/<crate_name>.log`. A
+//! comma-separated list of stage names (i.e. `forward_tokens,my_macro`) can be specified
+//! instead to only trace those stages, and the output directory can be overridden using
+//! `MACRO_MAGIC_TRACE_DIR`.
+//!
 //! ## Limitations
 //!
 //! One thing that `macro_magic` _doesn't_ provide is the ability to build up state information
 //! across multiple macro invocations, however this problem can be tackled effectively using
 //! the [outer macro pattern](https://www.youtube.com/watch?v=aEWbZxNCH0A) or in some cases
 //! using static atomics and mutexes in your proc macro crate (which we actually do in this
-//! crate to keep track of unique identifiers).
+//! crate to keep track of unique identifiers). For the same reason an export group can't be
+//! extended one export site at a time, so [`#[export_group]`](`export_group`) only collects the
+//! members declared directly inside its (inline) module, which [`collect_group!`] then
+//! forwards at once. Items in other modules or files can't join such a group.
 //!
 //! ## Breaking Changes
 //!
@@ -61,7 +152,8 @@
 //!   and no longer contain this behavior, however features that provided the ability to
 //!   enumerate all the `#[export_tokens]` calls in a namespace have been removed. The proper
 //!   way to do this is with the outer macro pattern or with global state mutexes/atomics in
-//!   your proc macro crate, as mentioned above.
+//!   your proc macro crate, as mentioned above, or with a build script via
+//!   `macro_magic_build`, which writes a [`manifest`] of every exported item.
 //!
 //! More detailed historical change information can be found in
 //! [releases](https://github.com/sam0x17/docify/releases).
@@ -75,17 +167,58 @@ pub mod mm_core {
     pub use macro_magic_core::*;
 }
 
+pub mod manifest;
+
+#[cfg(feature = "reflect")]
+pub mod reflect;
+
+mod util;
+
 pub use macro_magic_macros::{
-    export_tokens, export_tokens_alias, export_tokens_no_emit, forward_tokens,
-    forward_tokens_verbatim, use_attr, use_proc,
+    collect_group, export_group, export_template, export_tokens, export_tokens_alias,
+    export_tokens_no_emit, forward_bundle, forward_pipeline, forward_tokens,
+    forward_tokens_verbatim, instantiate, stringify_item, use_attr, use_proc,
 };
 
 #[cfg(feature = "proc_support")]
 pub use macro_magic_macros::{
-    import_tokens, import_tokens_attr, import_tokens_attr_verbatim, import_tokens_proc,
-    with_custom_parsing,
+    import_tokens, import_tokens_attr, import_tokens_attr_verbatim, import_tokens_from_file,
+    import_tokens_from_file_attr, import_tokens_proc, with_custom_parsing, with_parse_cache,
 };
 
+#[cfg(feature = "mixin")]
+pub use macro_magic_macros::mixin;
+
+#[cfg(feature = "inherit_impl")]
+pub use macro_magic_macros::inherit_impl;
+
+#[cfg(feature = "extend_enum")]
+pub use macro_magic_macros::extend_enum;
+
+#[cfg(feature = "remote_mirror")]
+pub use macro_magic_macros::remote_mirror;
+
+#[cfg(feature = "mirror_type")]
+pub use macro_magic_macros::mirror_type;
+
+#[cfg(feature = "delegate")]
+pub use macro_magic_macros::delegate;
+
+#[cfg(feature = "mock_trait")]
+pub use macro_magic_macros::mock_trait;
+
+#[cfg(feature = "builder_for")]
+pub use macro_magic_macros::builder_for;
+
+#[cfg(feature = "reflect")]
+pub use macro_magic_macros::reflect;
+
+#[cfg(feature = "doc_item")]
+pub use macro_magic_macros::doc_item;
+
+#[cfg(feature = "assert_same_shape")]
+pub use macro_magic_macros::assert_same_shape;
+
 /// Contains re-exports required at compile-time by the macro_magic macros and support
 /// functions.
 #[doc(hidden)]
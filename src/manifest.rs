@@ -0,0 +1,46 @@
+//! Contains the `no_std` data model of the export manifests written by the
+//! `macro_magic_build` crate from a build script.
+//!
+//! A manifest is an expression of type `&[ExportInfo]` describing every
+//! [`#[export_tokens]`](`crate::export_tokens`) item of a crate, which can be turned into a
+//! `const` registry of exported paths:
+//!
+//! ```ignore
+//! pub const EXPORTS: &[macro_magic::manifest::ExportInfo] =
+//!     include!(concat!(env!("OUT_DIR"), "/macro_magic_manifest.rs"));
+//! ```
+
+use crate::util::str_eq;
+
+/// Describes an [`#[export_tokens]`](`crate::export_tokens`) item found by
+/// `macro_magic_build`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExportInfo {
+    /// The full path the item is exported under, starting with the crate name, i.e.
+    /// `my_crate::config::Timeout`
+    pub path: &'static str,
+    /// The full path of the hidden macro that provides the tokens of the item, i.e.
+    /// `my_crate::config::__export_tokens_tt_timeout`
+    pub macro_path: &'static str,
+    /// The kind of the item as it is written in the source, i.e. `struct` or `impl`
+    pub kind: &'static str,
+    /// Whether the item itself is emitted, which is `false` for
+    /// [`#[export_tokens_no_emit]`](`crate::export_tokens_no_emit`) and
+    /// [`#[export_template]`](`crate::export_template`)
+    pub emit: bool,
+    /// Whether every module containing the item is `pub`, i.e. whether [`ExportInfo::path`]
+    /// can be used from other crates
+    pub accessible: bool,
+}
+
+/// Returns the entry of `exports` with the specified [`ExportInfo::path`], if there is one.
+pub const fn find_export<'a>(exports: &'a [ExportInfo], path: &str) -> Option<&'a ExportInfo> {
+    let mut i = 0;
+    while i < exports.len() {
+        if str_eq(exports[i].path, path) {
+            return Some(&exports[i]);
+        }
+        i += 1;
+    }
+    None
+}
@@ -0,0 +1,132 @@
+//! Contains the `no_std`, `&'static` data model produced by the
+//! [`reflect!`](`macro@crate::reflect`) macro, which describes an
+//! [`#[export_tokens]`](`crate::export_tokens`) item without requiring any derives on it.
+//!
+//! Types are described by their pretty-printed source representation (i.e. `Option<&'a str>`),
+//! exactly as written in the exported item, so paths are not resolved.
+
+use crate::util::str_eq;
+
+/// The kind of item an [`ItemInfo`] describes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    /// A `struct`
+    Struct,
+    /// An `enum`
+    Enum,
+    /// A `union`
+    Union,
+    /// A `trait`
+    Trait,
+    /// An `impl` block
+    Impl,
+    /// A free-standing `fn`
+    Fn,
+    /// A `const` item
+    Const,
+    /// A `static` item
+    Static,
+    /// A `type` alias
+    Type,
+    /// A `mod` with an inline body
+    Mod,
+    /// Any other kind of item
+    Other,
+}
+
+/// Describes a field of a struct, union or enum variant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FieldInfo {
+    /// The name of the field, or [`None`] for tuple fields
+    pub name: Option<&'static str>,
+    /// The type of the field, i.e. `Vec<u8>`
+    pub ty: &'static str,
+    /// The visibility of the field, i.e. `pub`, `pub(crate)` or an empty string
+    pub visibility: &'static str,
+    /// The doc comments of the field
+    pub docs: &'static str,
+}
+
+/// Describes a variant of an enum.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VariantInfo {
+    /// The name of the variant
+    pub name: &'static str,
+    /// The fields of the variant, which is empty for unit variants
+    pub fields: &'static [FieldInfo],
+    /// The doc comments of the variant
+    pub docs: &'static str,
+}
+
+/// Describes a method of a trait or impl block, or a free-standing fn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodInfo {
+    /// The name of the method
+    pub name: &'static str,
+    /// The full signature of the method, i.e. `fn get(&self, key: u32) -> Option<&u8>`
+    pub signature: &'static str,
+    /// The doc comments of the method
+    pub docs: &'static str,
+}
+
+/// Describes an [`#[export_tokens]`](`crate::export_tokens`) item, as returned by
+/// [`reflect!`](`macro@crate::reflect`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ItemInfo {
+    /// The name of the item, or the self type for impl blocks
+    pub name: &'static str,
+    /// The path that was passed to [`reflect!`](`macro@crate::reflect`)
+    pub path: &'static str,
+    /// The kind of item
+    pub kind: ItemKind,
+    /// The generic params of the item, i.e. `<'a, T: Copy>`, or an empty string
+    pub generics: &'static str,
+    /// The doc comments of the item
+    pub docs: &'static str,
+    /// The fields of a struct or union
+    pub fields: &'static [FieldInfo],
+    /// The variants of an enum
+    pub variants: &'static [VariantInfo],
+    /// The methods of a trait or impl block, or the item itself for a free-standing fn
+    pub methods: &'static [MethodInfo],
+}
+
+impl ItemInfo {
+    /// Returns the field with the specified name, if there is one.
+    pub const fn field(&self, name: &str) -> Option<&'static FieldInfo> {
+        let mut i = 0;
+        while i < self.fields.len() {
+            if let Some(field_name) = self.fields[i].name {
+                if str_eq(field_name, name) {
+                    return Some(&self.fields[i]);
+                }
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Returns the variant with the specified name, if there is one.
+    pub const fn variant(&self, name: &str) -> Option<&'static VariantInfo> {
+        let mut i = 0;
+        while i < self.variants.len() {
+            if str_eq(self.variants[i].name, name) {
+                return Some(&self.variants[i]);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Returns the method with the specified name, if there is one.
+    pub const fn method(&self, name: &str) -> Option<&'static MethodInfo> {
+        let mut i = 0;
+        while i < self.methods.len() {
+            if str_eq(self.methods[i].name, name) {
+                return Some(&self.methods[i]);
+            }
+            i += 1;
+        }
+        None
+    }
+}
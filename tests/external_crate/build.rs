@@ -0,0 +1,7 @@
+use macro_magic_build::Shim;
+
+fn main() {
+    macro_magic_build::write_manifest().unwrap();
+    macro_magic_build::write_shims(&[Shim::new("proc-macro2").item("location::LineColumn")])
+        .unwrap();
+}
@@ -3,6 +3,15 @@
 
 use macro_magic::*;
 
+/// Every `#[export_tokens]` item of this crate, found by `macro_magic_build`.
+pub const EXPORTS: &[macro_magic::manifest::ExportInfo] =
+    include!(concat!(env!("OUT_DIR"), "/macro_magic_manifest.rs"));
+
+/// Exports of dependencies that don't use `macro_magic`, written by `macro_magic_build`.
+pub mod shims {
+    include!(concat!(env!("OUT_DIR"), "/macro_magic_shims.rs"));
+}
+
 pub mod some_submodule {
     use macro_magic::*;
 
@@ -53,3 +62,249 @@ fn external_fn_with_local_macro_calls() -> u32 {
     another_macro!();
     1337
 }
+
+pub mod config {
+    use macro_magic::*;
+
+    #[export_tokens]
+    pub struct Timeout {
+        secs: u64,
+    }
+
+    #[export_tokens(with_deps(crate::config::Timeout))]
+    pub struct Retry {
+        attempts: u8,
+        backoff: Timeout,
+    }
+
+    #[export_tokens(with_deps)]
+    pub struct Config {
+        timeout: Timeout,
+        retry: Option<Retry>,
+        name: &'static str,
+    }
+
+    /// The pretty-printed source of [`Timeout`], to test that `stringify_item!` works with
+    /// `no_std`.
+    pub const TIMEOUT_SOURCE: &str = stringify_item!(crate::config::Timeout, pretty);
+}
+
+pub mod mixins {
+    use macro_magic::*;
+
+    #[export_tokens]
+    pub struct Timestamps {
+        pub created: u64,
+        pub updated: u64,
+    }
+
+    #[export_tokens]
+    pub struct Labeled<'a, T: Clone>
+    where
+        T: Default,
+    {
+        pub label: &'a str,
+        pub value: T,
+    }
+
+    #[export_tokens]
+    pub struct Pair(pub u8, pub u16);
+}
+
+pub mod inherit {
+    use macro_magic::*;
+
+    pub trait Shape {
+        const SIDES: u32;
+        type Unit;
+        fn sides(&self) -> u32;
+        fn describe(&self) -> u32;
+        fn double(&self) -> Self;
+    }
+
+    pub struct Square(pub u32);
+
+    #[export_tokens(square_shape)]
+    impl Shape for Square {
+        const SIDES: u32 = 4;
+        type Unit = u32;
+
+        fn sides(&self) -> u32 {
+            Self::SIDES
+        }
+
+        fn describe(&self) -> u32 {
+            self.sides() * 10 + self.0
+        }
+
+        fn double(&self) -> Square {
+            Square(self.0 * 2)
+        }
+    }
+}
+
+pub mod events {
+    use macro_magic::*;
+
+    #[export_tokens]
+    #[derive(Debug, PartialEq)]
+    pub enum Event {
+        Started,
+        Progress(u8),
+        Finished { code: u32 },
+    }
+}
+
+pub mod remote {
+    use macro_magic::*;
+
+    #[export_tokens]
+    #[derive(Debug, PartialEq)]
+    pub struct Duration {
+        pub secs: u64,
+        pub nanos: u32,
+    }
+
+    #[export_tokens]
+    #[derive(Debug, PartialEq)]
+    pub enum Shape {
+        Circle(u32),
+        Rect { w: u32, h: u32 },
+        Empty,
+    }
+}
+
+pub mod units {
+    use macro_magic::*;
+
+    #[export_tokens]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Meters(pub f64);
+
+    /// A point in 2D space.
+    #[export_tokens]
+    #[derive(Debug, PartialEq)]
+    #[repr(C)]
+    pub struct Point<T: Copy> {
+        pub x: T,
+        pub y: T,
+    }
+}
+
+pub mod storage {
+    use macro_magic::*;
+
+    #[export_tokens]
+    #[allow(async_fn_in_trait)]
+    pub trait Storage {
+        type Value;
+        const CAPACITY: usize;
+
+        fn get(&self, key: usize) -> Option<&Self::Value>;
+        fn put(&mut self, key: usize, value: Self::Value);
+        fn contains<K: Into<usize>>(&self, key: K) -> bool {
+            self.get(key.into()).is_some()
+        }
+        async fn flush(&mut self) -> usize;
+    }
+
+    #[derive(Default)]
+    pub struct MemStore {
+        pub values: [Option<u8>; 4],
+    }
+
+    impl Storage for MemStore {
+        type Value = u8;
+        const CAPACITY: usize = 4;
+
+        fn get(&self, key: usize) -> Option<&u8> {
+            self.values.get(key)?.as_ref()
+        }
+
+        fn put(&mut self, key: usize, value: u8) {
+            self.values[key] = Some(value);
+        }
+
+        async fn flush(&mut self) -> usize {
+            let count = self.values.iter().flatten().count();
+            self.values = [None; 4];
+            count
+        }
+    }
+}
+
+pub mod repo {
+    use macro_magic::*;
+
+    #[export_tokens]
+    pub trait Repo {
+        type Item: Clone;
+
+        fn get(&self, id: u32) -> Option<&Self::Item>;
+        fn find(&self, name: &str) -> Option<u32>;
+        fn put<T: Into<u32>>(&mut self, id: T, item: Self::Item);
+        fn count(&self) -> usize {
+            0
+        }
+        fn into_items(self) -> usize;
+    }
+
+    #[export_tokens]
+    pub trait Queue {
+        type Item;
+
+        fn pop(&mut self) -> Option<Self::Item>;
+    }
+}
+
+pub mod server {
+    use macro_magic::*;
+
+    #[export_tokens]
+    #[derive(Debug, PartialEq)]
+    pub struct ServerConfig<T> {
+        pub host: &'static str,
+        pub port: u16,
+        pub workers: u8,
+        pub state: T,
+    }
+}
+
+#[export_group(plugins, hooks)]
+pub mod registry {
+    use macro_magic::*;
+
+    #[export_tokens(group = plugins)]
+    pub struct Logger;
+
+    #[export_tokens(LevelFilter, group = plugins)]
+    pub enum Level {
+        Info,
+        Debug,
+    }
+
+    #[export_tokens_no_emit(group = hooks)]
+    pub fn on_start() {}
+
+    #[export_tokens]
+    pub struct Helper;
+}
+
+pub mod templates {
+    use macro_magic::*;
+
+    #[export_template(sized_buffer; T, N)]
+    impl T {
+        pub const CAPACITY: usize = N;
+
+        pub fn buffer() -> [u8; N] {
+            [0; N]
+        }
+    }
+
+    #[export_template(Wrapper, Inner)]
+    #[derive(Debug, PartialEq)]
+    pub struct Wrapper {
+        pub inner: Inner,
+    }
+}
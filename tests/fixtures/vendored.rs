@@ -0,0 +1,14 @@
+// A source file from a crate that doesn't use `#[export_tokens]`, read by
+// `import_tokens_from_file!` in the integration tests.
+
+/// Settings for the vendored client.
+pub struct ClientSettings {
+    pub retries: u8,
+    pub verbose: bool,
+}
+
+pub mod codec {
+    pub fn encode(input: &[u8]) -> usize {
+        input.len()
+    }
+}
@@ -1,7 +1,7 @@
 use derive_syn_parse::Parse;
 use macro_magic::{mm_core::ForeignPath, *};
 use proc_macro::TokenStream;
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 use syn::{
     parse_macro_input, spanned::Spanned, Error, Fields, Ident, Item, ItemMod, ItemStruct, Path,
 };
@@ -113,6 +113,27 @@ pub fn test_tokens_attr1(attr: TokenStream, tokens: TokenStream) -> TokenStream
     .into()
 }
 
+#[import_tokens_attr]
+#[with_parse_cache]
+#[proc_macro_attribute]
+pub fn test_tokens_attr_cached_a(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    let imported_item = parse_macro_input!(attr as Item);
+    let imported_item_str = imported_item.to_token_stream().to_string();
+    assert_eq!(imported_item_str, "struct AnotherStruct { field1 : u32, }");
+    tokens
+}
+
+/// we do this one to check that both orderings work
+#[with_parse_cache]
+#[import_tokens_attr]
+#[proc_macro_attribute]
+pub fn test_tokens_attr_cached_b(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    let imported_item = parse_macro_input!(attr as Item);
+    let imported_item_str = imported_item.to_token_stream().to_string();
+    assert_eq!(imported_item_str, "struct AnotherStruct { field1 : u32, }");
+    tokens
+}
+
 #[import_tokens_attr]
 #[proc_macro_attribute]
 pub fn test_tokens_attr2(attr: TokenStream, tokens: TokenStream) -> TokenStream {
@@ -234,6 +255,15 @@ pub fn example_tokens_proc(tokens: TokenStream) -> TokenStream {
     quote!(#item_as_string).into()
 }
 
+#[import_tokens_proc]
+#[proc_macro]
+pub fn example_tokens_proc_with_args(tokens: TokenStream) -> TokenStream {
+    let imported_item = parse_macro_input!(tokens as Item);
+    let item_as_string = imported_item.to_token_stream().to_string();
+    let args_as_string = __forwarded_args.to_string();
+    quote!((#item_as_string, #args_as_string)).into()
+}
+
 #[import_tokens_proc(example_export::subpath)]
 #[proc_macro]
 pub fn item_level_proc(tokens: TokenStream) -> TokenStream {
@@ -320,3 +350,49 @@ pub fn require(tokens: TokenStream) -> TokenStream {
 }
 
 export_tokens_alias!(custom_export_tokens);
+
+/// An example intermediate `forward_pipeline!` stage that renames every accumulated struct
+/// by appending `Renamed` to its ident.
+#[proc_macro]
+pub fn pipeline_rename_stage(tokens: TokenStream) -> TokenStream {
+    let pipeline = parse_macro_input!(tokens as macro_magic::mm_core::ForwardedPipeline);
+    let items = pipeline
+        .items
+        .iter()
+        .cloned()
+        .map(|(path, item)| match item {
+            Item::Struct(mut item_struct) => {
+                item_struct.ident = format_ident!("{}Renamed", item_struct.ident);
+                (path, Item::Struct(item_struct))
+            }
+            item => (path, item),
+        })
+        .collect();
+    pipeline.resume(items).into()
+}
+
+/// An example final `forward_pipeline!` target that expands to a tuple of the accumulated
+/// `path => item` strings and the stringified args.
+#[proc_macro]
+pub fn pipeline_collect(tokens: TokenStream) -> TokenStream {
+    let pipeline = parse_macro_input!(tokens as macro_magic::mm_core::ForwardedPipeline);
+    let items = pipeline
+        .items
+        .iter()
+        .map(|(path, item)| format!("{} => {}", path.to_token_stream(), item.to_token_stream()));
+    let args = pipeline.args.to_string();
+    quote!((&[#(#items),*], #args)).into()
+}
+
+/// An example `forward_bundle!` target that expands to a tuple of the root item string and
+/// the `path => item` strings of its dependencies.
+#[proc_macro]
+pub fn bundle_collect(tokens: TokenStream) -> TokenStream {
+    let bundle = parse_macro_input!(tokens as macro_magic::mm_core::ImportedBundle);
+    let root = bundle.root.to_token_stream().to_string();
+    let deps = bundle
+        .deps
+        .iter()
+        .map(|(path, item)| format!("{} => {}", path.to_token_stream(), item.to_token_stream()));
+    quote!((#root, &[#(#deps),*])).into()
+}
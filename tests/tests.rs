@@ -12,12 +12,15 @@ use test_macros::some_macro;
 use test_macros::combine_structs;
 use test_macros::emit_foreign_path;
 use test_macros::example_tokens_proc;
+use test_macros::example_tokens_proc_with_args;
 use test_macros::import_tokens_attr_with_custom_parsing_a;
 use test_macros::import_tokens_attr_with_custom_parsing_b;
 use test_macros::item_level_proc;
 use test_macros::require;
 use test_macros::test_tokens_attr1;
 use test_macros::test_tokens_attr2;
+use test_macros::{bundle_collect, pipeline_collect, pipeline_rename_stage};
+use test_macros::{test_tokens_attr_cached_a, test_tokens_attr_cached_b};
 
 /// Some doc comment
 pub use test_macros::test_tokens_attr_direct_import;
@@ -86,6 +89,14 @@ pub mod hunter {
     }
 }
 
+#[test_tokens_attr_cached_a(AnotherStruct)]
+#[test_tokens_attr_cached_b(AnotherStruct)]
+struct CachedLocal1;
+
+#[test_tokens_attr_cached_a(AnotherStruct)]
+#[test_tokens_attr_cached_b(AnotherStruct)]
+struct CachedLocal2;
+
 #[test_tokens_attr2(external_crate::some_submodule::AnExternalTraitImpl)]
 struct LocalItemStruct {}
 
@@ -139,6 +150,677 @@ fn test_forward_tokens() {
     assert_eq!(result, "struct LionStruct {}");
 }
 
+#[test]
+fn test_forward_tokens_decl_target() {
+    macro_rules! decl_receiver {
+        (item = { $item:item }, source = { $source:path }, extra = { $($extra:tt)* }) => {
+            (stringify!($item), stringify!($source), stringify!($($extra)*))
+        };
+    }
+
+    let (item, source, extra) = forward_tokens!(LionStruct, decl_receiver, extra = { roar });
+    assert_eq!(item, "struct LionStruct {}");
+    assert_eq!(source, "LionStruct");
+    assert_eq!(extra, "roar");
+
+    let (item, source, extra) = forward_tokens!(
+        external_crate::an_external_function,
+        decl_receiver,
+        extra = {}
+    );
+    assert_eq!(
+        item,
+        "fn an_external_function(my_num : u32) -> u32 { my_num + 33 }"
+    );
+    assert_eq!(source, "external_crate :: an_external_function");
+    assert_eq!(extra, "");
+}
+
+macro_rules! decl_item_emitter {
+    (item = { $item:item }, source = { $source:path }, extra = { $name:ident }) => {
+        mod $name {
+            pub const SOURCE: &str = stringify!($source);
+
+            $item
+
+            pub fn call(n: u32) -> u32 {
+                an_external_function(n)
+            }
+        }
+    };
+}
+
+forward_tokens!(
+    external_crate::an_external_function,
+    decl_item_emitter,
+    extra = { decl_emitted }
+);
+
+#[test]
+fn test_forward_tokens_decl_target_item_position() {
+    assert_eq!(decl_emitted::call(1), 34);
+    assert_eq!(
+        decl_emitted::SOURCE,
+        "external_crate :: an_external_function"
+    );
+}
+
+#[test]
+fn test_forward_tokens_args() {
+    let (item, args) =
+        forward_tokens!(TigerStruct, example_tokens_proc_with_args, args = { 1, two });
+    assert_eq!(item, "struct TigerStruct {}");
+    assert_eq!(args, "1, two");
+    let (item, args) = example_tokens_proc_with_args!(TigerStruct);
+    assert_eq!(item, "struct TigerStruct {}");
+    assert_eq!(args, "");
+}
+
+#[cfg(feature = "mixin")]
+#[mixin(external_crate::mixins::Timestamps, external_crate::mixins::Labeled)]
+struct MixedRecord<T>
+where
+    T: Default,
+{
+    id: u32,
+}
+
+#[cfg(feature = "mixin")]
+#[mixin(external_crate::mixins::Pair)]
+struct MixedPair(bool);
+
+#[cfg(feature = "mixin")]
+#[mixin(external_crate::mixins::Pair)]
+struct MixedUnit;
+
+#[cfg(feature = "mixin")]
+#[test]
+fn test_mixin() {
+    let record = MixedRecord::<u8> {
+        id: 1,
+        created: 2,
+        updated: 3,
+        label: "four",
+        value: 5,
+    };
+    assert_eq!(record.id + record.created as u32 + record.updated as u32, 6);
+    assert_eq!(record.label, "four");
+    assert_eq!(record.value, 5);
+    let pair = MixedPair(true, 2, 3);
+    assert!(pair.0);
+    assert_eq!(pair.1 as u16 + pair.2, 5);
+    let unit = MixedUnit(4, 5);
+    assert_eq!(unit.0 as u16 + unit.1, 9);
+}
+
+#[cfg(feature = "inherit_impl")]
+struct Triangle(u32);
+
+#[cfg(feature = "inherit_impl")]
+#[inherit_impl(external_crate::inherit::square_shape)]
+impl external_crate::inherit::Shape for Triangle {
+    const SIDES: u32 = 3;
+
+    fn describe(&self) -> u32 {
+        self.0
+    }
+}
+
+#[cfg(feature = "inherit_impl")]
+#[test]
+fn test_inherit_impl() {
+    use external_crate::inherit::Shape;
+    let triangle = Triangle(5);
+    assert_eq!(triangle.sides(), 3);
+    assert_eq!(triangle.describe(), 5);
+    let doubled: Triangle = triangle.double();
+    assert_eq!(doubled.0, 10);
+    let unit: <Triangle as Shape>::Unit = 7u32;
+    assert_eq!(unit, 7);
+}
+
+#[cfg(feature = "extend_enum")]
+#[extend_enum(external_crate::events::Event)]
+#[derive(Debug, PartialEq)]
+enum AppEvent {
+    Custom(&'static str),
+}
+
+#[cfg(feature = "extend_enum")]
+#[test]
+fn test_extend_enum() {
+    use external_crate::events::Event;
+    assert_eq!(AppEvent::from(Event::Progress(5)), AppEvent::Progress(5));
+    assert_eq!(
+        AppEvent::from(Event::Finished { code: 3 }),
+        AppEvent::Finished { code: 3 }
+    );
+    assert_eq!(Event::try_from(AppEvent::Started), Ok(Event::Started));
+    assert_eq!(
+        Event::try_from(AppEvent::Custom("hey")),
+        Err(AppEvent::Custom("hey"))
+    );
+}
+
+#[cfg(feature = "remote_mirror")]
+mod remote_mirrors {
+    use external_crate::remote::{Duration, Shape};
+    use macro_magic::remote_mirror;
+    use serde::{Deserialize, Serialize};
+
+    #[remote_mirror(external_crate::remote::Duration)]
+    #[derive(Serialize, Deserialize)]
+    pub struct DurationDef;
+
+    #[remote_mirror(external_crate::remote::Shape)]
+    #[derive(Serialize, Deserialize)]
+    pub enum ShapeDef {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Timeout {
+        #[serde(with = "DurationDef")]
+        duration: Duration,
+        #[serde(with = "ShapeDef")]
+        shape: Shape,
+    }
+
+    #[test]
+    fn test_remote_mirror() {
+        let timeout = Timeout {
+            duration: Duration { secs: 3, nanos: 7 },
+            shape: Shape::Rect { w: 1, h: 2 },
+        };
+        let json = serde_json::to_string(&timeout).unwrap();
+        assert_eq!(
+            json,
+            r#"{"duration":{"secs":3,"nanos":7},"shape":{"Rect":{"w":1,"h":2}}}"#
+        );
+        assert_eq!(serde_json::from_str::<Timeout>(&json).unwrap(), timeout);
+        let def = DurationDef::from(Duration { secs: 1, nanos: 2 });
+        assert_eq!((def.secs, def.nanos), (1, 2));
+        assert_eq!(Duration::from(def), Duration { secs: 1, nanos: 2 });
+        assert_eq!(
+            Shape::from(ShapeDef::from(Shape::Circle(4))),
+            Shape::Circle(4)
+        );
+    }
+}
+
+#[cfg(feature = "mirror_type")]
+mod mirror_types {
+    use core::fmt::Display;
+    use external_crate::units;
+    use macro_magic::mirror_type;
+
+    mirror_type!(
+        #[derive(Debug, PartialEq)]
+        external_crate::units::Meters as Meters,
+        deref
+    );
+    mirror_type!(external_crate::units::Point as Point);
+
+    impl Display for Meters {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "{}m", **self)
+        }
+    }
+
+    impl<T: Copy + Display> Display for Point<T> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "({}, {})", self.x, self.y)
+        }
+    }
+
+    #[test]
+    fn test_mirror_type() {
+        let meters = Meters::from(units::Meters(2.5));
+        assert_eq!(meters, Meters(2.5));
+        assert_eq!(meters.to_string(), "2.5m");
+        assert_eq!(*meters + 1.0, 3.5);
+        assert_eq!(units::Meters::from(meters), units::Meters(2.5));
+        let point: Point<u8> = units::Point { x: 1, y: 2 }.into();
+        assert_eq!(point.to_string(), "(1, 2)");
+        assert_eq!(units::Point::from(point), units::Point { x: 1, y: 2 });
+        assert_eq!(
+            core::mem::size_of::<Point<u16>>(),
+            core::mem::size_of::<units::Point<u16>>()
+        );
+    }
+}
+
+#[cfg(feature = "delegate")]
+mod delegates {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use external_crate::storage::{MemStore, Storage};
+    use macro_magic::delegate;
+
+    #[derive(Default)]
+    struct Counted {
+        inner: MemStore,
+        writes: usize,
+    }
+
+    #[delegate(field = inner, ty = MemStore, to = external_crate::storage::Storage)]
+    impl Storage for Counted {
+        fn put(&mut self, key: usize, value: u8) {
+            self.writes += 1;
+            self.inner.put(key, value)
+        }
+    }
+
+    #[derive(Default)]
+    struct Plain(MemStore);
+
+    #[delegate(field = 0, to = external_crate::storage::Storage)]
+    impl Storage for Plain {
+        type Value = u8;
+        const CAPACITY: usize = 2;
+    }
+
+    #[test]
+    fn test_delegate() {
+        let mut counted = Counted::default();
+        counted.put(1, 7);
+        counted.put(2, 8);
+        assert_eq!(counted.writes, 2);
+        assert_eq!(counted.get(1), Some(&7));
+        assert!(counted.contains(2u8));
+        assert!(!counted.contains(3u8));
+        assert_eq!(Counted::CAPACITY, 4);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(pin!(counted.flush()).poll(&mut cx), Poll::Ready(2));
+        assert_eq!(counted.get(1), None);
+
+        let mut plain = Plain::default();
+        plain.put(0, 3);
+        assert_eq!(plain.0.get(0), Some(&3));
+        assert_eq!(Plain::CAPACITY, 2);
+    }
+}
+
+#[cfg(feature = "mock_trait")]
+mod mocks {
+    use external_crate::repo::Repo;
+    use macro_magic::mock_trait;
+
+    mock_trait!(external_crate::repo::Repo);
+    mock_trait!(external_crate::repo::Repo as FakeRepo);
+    mock_trait!(external_crate::repo::Queue);
+
+    fn store<R: Repo<Item = String>>(repo: &mut R, name: &str) -> Option<u32> {
+        let id = repo.find(name)?;
+        repo.put(id as u16, name.to_string());
+        Some(id)
+    }
+
+    #[test]
+    fn test_mock_trait() {
+        let mut repo = MockRepo::<String>::default();
+        repo.find.returns(Some(7)).returns(None);
+        repo.put.returns_with(|_| ());
+        assert_eq!(store(&mut repo, "bob"), Some(7));
+        assert_eq!(store(&mut repo, "alice"), None);
+        assert_eq!(
+            *repo.find.calls(),
+            [("bob".to_string(),), ("alice".to_string(),)]
+        );
+        assert_eq!(*repo.put.calls(), [("u16", "bob".to_string())]);
+
+        static BOB: String = String::new();
+        repo.get.returns(Some(&BOB));
+        assert_eq!(repo.get(1), Some(&BOB));
+        assert_eq!(repo.get.calls()[0], (1,));
+        repo.count.returns_with(|_| 42);
+        assert_eq!(repo.count(), 42);
+        assert_eq!(repo.count.call_count(), 1);
+        repo.into_items.returns(3);
+        assert_eq!(repo.into_items(), 3);
+
+        let fake = FakeRepo::<u8>::default();
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fake.count()));
+        assert!(panic.is_err());
+        assert_eq!(fake.count.call_count(), 0);
+    }
+
+    #[test]
+    fn test_mock_trait_unbounded_assoc_type() {
+        use external_crate::repo::Queue;
+
+        let mut queue = MockQueue::<u8>::default();
+        queue.pop.returns(Some(1)).returns(None);
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.pop.call_count(), 2);
+    }
+}
+
+#[cfg(feature = "builder_for")]
+mod builders {
+    use external_crate::server::ServerConfig;
+    use macro_magic::builder_for;
+
+    builder_for!(external_crate::server::ServerConfig {
+        #[default(8080)]
+        port,
+        #[default]
+        workers,
+    });
+
+    builder_for!(external_crate::server::ServerConfig as StrictBuilder);
+
+    #[test]
+    fn test_builder_for() {
+        let config = ServerConfigBuilder::new()
+            .host("localhost")
+            .state(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            config,
+            ServerConfig {
+                host: "localhost",
+                port: 8080,
+                workers: 0,
+                state: true,
+            }
+        );
+        let config = ServerConfigBuilder::new()
+            .host("example.com")
+            .port(80)
+            .workers(4)
+            .state(())
+            .build()
+            .unwrap();
+        assert_eq!((config.port, config.workers), (80, 4));
+
+        let err = StrictBuilder::<u8>::new().port(1).build().unwrap_err();
+        assert_eq!(err.missing, ["host", "workers", "state"]);
+        assert_eq!(
+            err.to_string(),
+            "missing required fields: host, workers, state"
+        );
+    }
+}
+
+#[cfg(feature = "reflect")]
+mod reflection {
+    use macro_magic::reflect;
+    use macro_magic::reflect::{ItemInfo, ItemKind};
+
+    const POINT: ItemInfo = reflect!(external_crate::units::Point);
+    const EVENT: ItemInfo = reflect!(external_crate::events::Event);
+    const REPO: ItemInfo = reflect!(external_crate::repo::Repo);
+    const SQUARE_SHAPE: ItemInfo = reflect!(external_crate::inherit::square_shape);
+
+    #[test]
+    fn test_reflect() {
+        assert_eq!(POINT.name, "Point");
+        assert_eq!(POINT.path, "external_crate::units::Point");
+        assert_eq!(POINT.kind, ItemKind::Struct);
+        assert_eq!(POINT.generics, "<T: Copy>");
+        assert_eq!(POINT.docs, "A point in 2D space.");
+        let x = POINT.field("x").unwrap();
+        assert_eq!((x.ty, x.visibility), ("T", "pub"));
+        assert!(POINT.field("z").is_none());
+
+        assert_eq!(EVENT.kind, ItemKind::Enum);
+        let names: Vec<&str> = EVENT.variants.iter().map(|v| v.name).collect();
+        assert_eq!(names, ["Started", "Progress", "Finished"]);
+        let finished = EVENT.variant("Finished").unwrap();
+        assert_eq!(finished.fields[0].name, Some("code"));
+        assert_eq!(EVENT.variant("Progress").unwrap().fields[0].ty, "u8");
+
+        assert_eq!(REPO.kind, ItemKind::Trait);
+        assert_eq!(
+            REPO.method("put").unwrap().signature,
+            "fn put<T: Into<u32>>(&mut self, id: T, item: Self::Item)"
+        );
+        assert_eq!(REPO.methods.len(), 5);
+
+        assert_eq!(SQUARE_SHAPE.kind, ItemKind::Impl);
+        assert_eq!(SQUARE_SHAPE.name, "Square");
+        assert!(SQUARE_SHAPE.method("double").is_some());
+    }
+}
+
+#[test]
+fn test_export_manifest() {
+    use macro_magic::manifest::find_export;
+
+    let timeout = find_export(external_crate::EXPORTS, "external_crate::config::Timeout").unwrap();
+    assert_eq!(
+        timeout.macro_path,
+        "external_crate::config::__export_tokens_tt_timeout"
+    );
+    assert_eq!(
+        (timeout.kind, timeout.emit, timeout.accessible),
+        ("struct", true, true)
+    );
+    let square_shape = find_export(
+        external_crate::EXPORTS,
+        "external_crate::inherit::square_shape",
+    )
+    .unwrap();
+    assert_eq!(square_shape.kind, "impl");
+    assert!(find_export(external_crate::EXPORTS, "external_crate::config::Missing").is_none());
+    assert!(external_crate::EXPORTS
+        .iter()
+        .any(|export| export.path == "external_crate::an_external_function"));
+}
+
+#[test]
+fn test_dependency_shims() {
+    const LINE_COLUMN: &str = stringify_item!(
+        external_crate::shims::proc_macro2::location::LineColumn,
+        pretty
+    );
+    assert!(LINE_COLUMN.contains("#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]\n"));
+    assert!(LINE_COLUMN.contains("pub struct LineColumn {\n"));
+    assert!(LINE_COLUMN.contains("    pub line: usize,\n"));
+}
+
+#[test]
+fn test_stringify_item() {
+    assert_eq!(
+        stringify_item!(external_crate::config::Timeout),
+        "pub struct Timeout { secs : u64, }"
+    );
+    assert_eq!(
+        external_crate::config::TIMEOUT_SOURCE,
+        "pub struct Timeout {\n    secs: u64,\n}"
+    );
+    const EVENT: &str = stringify_item!(external_crate::events::Event, pretty);
+    assert!(EVENT.starts_with("#[derive(Debug, PartialEq)]\npub enum Event {\n    Started,\n"));
+}
+
+#[cfg(feature = "doc_item")]
+mod doc_items {
+    use macro_magic::*;
+
+    /// Documents a point.
+    #[doc_item(external_crate::units::Point)]
+    #[export_tokens]
+    pub struct PointDocs;
+
+    #[doc_item(external_crate::repo::Repo, signature)]
+    #[export_tokens]
+    pub struct RepoDocs;
+
+    #[doc_item(external_crate::events::Event, members(Started, Finished))]
+    #[export_tokens]
+    pub struct EventDocs;
+
+    const POINT_DOCS: reflect::ItemInfo = reflect!(PointDocs);
+    const REPO_DOCS: reflect::ItemInfo = reflect!(RepoDocs);
+    const EVENT_DOCS: reflect::ItemInfo = reflect!(EventDocs);
+
+    #[test]
+    fn test_doc_item() {
+        assert_eq!(
+            POINT_DOCS.docs,
+            "Documents a point.\n\n```rust,ignore\n/// A point in 2D space.\n#[derive(Debug, PartialEq)]\n#[repr(C)]\n\
+            pub struct Point<T: Copy> {\n    pub x: T,\n    pub y: T,\n}\n```"
+        );
+        assert!(REPO_DOCS
+            .docs
+            .starts_with("```rust,ignore\npub trait Repo {\n"));
+        assert!(REPO_DOCS.docs.contains("    fn count(&self) -> usize;\n"));
+        assert!(EVENT_DOCS.docs.contains("Started"));
+        assert!(EVENT_DOCS.docs.contains("Finished"));
+        assert!(!EVENT_DOCS.docs.contains("Progress"));
+    }
+}
+
+#[cfg(feature = "assert_same_shape")]
+mod same_shapes {
+    use macro_magic::*;
+
+    #[export_tokens]
+    #[derive(Debug, PartialEq)]
+    pub struct LocalDuration {
+        pub secs: u64,
+        pub nanos: u32,
+    }
+
+    /// A local copy of `Shape`.
+    #[export_tokens]
+    #[derive(Clone)]
+    pub(crate) enum LocalShape {
+        Circle(u32),
+        Rect { w: u32, h: u32 },
+        Empty,
+    }
+
+    assert_same_shape!(external_crate::remote::Duration, LocalDuration);
+    assert_same_shape!(
+        external_crate::remote::Shape,
+        LocalShape,
+        ignore(attrs, docs, visibility)
+    );
+
+    #[test]
+    fn test_assert_same_shape() {
+        assert_same_shape!(LocalDuration, external_crate::remote::Duration);
+        let shape = LocalShape::Rect { w: 1, h: 2 };
+        assert!(matches!(shape.clone(), LocalShape::Rect { w: 1, h: 2 }));
+        assert_ne!(
+            LocalDuration { secs: 1, nanos: 0 },
+            LocalDuration { secs: 0, nanos: 1 }
+        );
+        let _ = (LocalShape::Circle(0), LocalShape::Empty);
+    }
+}
+
+#[test]
+fn test_forward_pipeline() {
+    let (items, args) = forward_pipeline!(LionStruct, TigerStruct => pipeline_collect);
+    assert_eq!(
+        items,
+        &[
+            "LionStruct => struct LionStruct {}",
+            "TigerStruct => struct TigerStruct {}"
+        ]
+    );
+    assert_eq!(args, "");
+}
+
+#[test]
+fn test_forward_bundle() {
+    let (root, deps) = forward_bundle!(external_crate::config::Config, bundle_collect);
+    assert!(root.starts_with("pub struct Config"));
+    assert!(root.contains("retry : Option < Retry >"));
+    assert_eq!(deps.len(), 2);
+    assert!(deps[0].starts_with("external_crate :: config :: Timeout => pub struct Timeout"));
+    assert!(deps[1].starts_with("external_crate :: config :: Retry => pub struct Retry"));
+    assert!(deps[1].contains("backoff : Timeout"));
+}
+
+#[export_group]
+mod local_plugins {
+    use macro_magic::*;
+
+    #[export_tokens(group = local_plugins)]
+    pub struct Tracer;
+}
+
+#[test]
+fn test_collect_group() {
+    let (items, args) = collect_group!(external_crate::plugins, pipeline_collect, args = { app });
+    assert_eq!(
+        items,
+        &[
+            "external_crate :: registry :: Logger => pub struct Logger;",
+            "external_crate :: registry :: LevelFilter => pub enum Level { Info, Debug, }"
+        ]
+    );
+    assert_eq!(args, "app");
+    let (items, _) = collect_group!(external_crate::hooks, pipeline_collect);
+    assert_eq!(
+        items,
+        &["external_crate :: registry :: on_start => pub fn on_start() {}"]
+    );
+    let (items, _) = collect_group!(crate::local_plugins, pipeline_collect);
+    assert_eq!(
+        items,
+        &["crate :: local_plugins :: Tracer => pub struct Tracer;"]
+    );
+    let _ = (
+        external_crate::registry::Logger,
+        external_crate::registry::Level::Info,
+        external_crate::registry::Helper,
+        local_plugins::Tracer,
+    );
+}
+
+mod instantiated {
+    use macro_magic::*;
+
+    pub struct Packet;
+
+    instantiate!(
+        external_crate::templates::sized_buffer,
+        T = Packet,
+        N = 2 * 2
+    );
+    instantiate!(
+        external_crate::templates::Wrapper,
+        Wrapper = ResultWrapper,
+        Inner = Result<u8, u16>,
+    );
+}
+
+#[test]
+fn test_instantiate() {
+    use instantiated::*;
+
+    assert_eq!(Packet::CAPACITY, 4);
+    assert_eq!(Packet::buffer(), [0u8; 4]);
+    let wrapper = ResultWrapper { inner: Ok(3) };
+    assert_eq!(wrapper, ResultWrapper { inner: Ok(3) });
+    assert_ne!(wrapper, ResultWrapper { inner: Err(3) });
+}
+
+#[test]
+fn test_forward_pipeline_stages() {
+    let (items, args) = forward_pipeline! {
+        LionStruct => pipeline_rename_stage,
+        external_crate::an_external_function => pipeline_collect;
+        args = { roar }
+    };
+    assert_eq!(
+        items,
+        &[
+            "LionStruct => struct LionStructRenamed {}",
+            "external_crate :: an_external_function => fn an_external_function(my_num : u32) -> u32 { my_num + 33 }"
+        ]
+    );
+    assert_eq!(args, "roar");
+}
+
 #[cfg(feature = "proc_support")]
 #[test]
 fn import_tokens_same_mod_no_ident() {
@@ -174,6 +856,32 @@ fn import_tokens_different_mod_ident() {
     );
 }
 
+#[cfg(feature = "proc_support")]
+#[test]
+fn test_import_tokens_from_file() {
+    import_tokens_from_file!(let tokens = "tests/fixtures/vendored.rs" :: ClientSettings);
+    assert_eq!(
+        tokens.to_string(),
+        "# [doc = \" Settings for the vendored client.\"] pub struct ClientSettings { pub retries : u8 , pub verbose : bool , }"
+    );
+    import_tokens_from_file!(let encode = "tests/fixtures/vendored.rs" :: codec::encode);
+    assert!(encode
+        .to_string()
+        .starts_with("pub fn encode (input : & [u8]) -> usize"));
+}
+
+#[cfg(feature = "proc_support")]
+#[import_tokens_from_file_attr(let tokens = "tests/fixtures/vendored.rs" :: codec::encode)]
+fn vendored_encode_source() -> String {
+    tokens.to_string()
+}
+
+#[cfg(feature = "proc_support")]
+#[test]
+fn test_import_tokens_from_file_attr() {
+    assert!(vendored_encode_source().contains("input . len ()"));
+}
+
 #[export_tokens]
 fn a_random_fn() {
     println!("hey");